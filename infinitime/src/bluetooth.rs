@@ -1,10 +1,23 @@
 mod device;
+mod discovery;
 mod services;
+mod snapshot;
 mod uuids;
 
 pub use device::{
-    media_player::MediaPlayerEvent, notification::Notification,
-    InfiniTime, ProgressEvent, ProgressRx, ProgressTx,
-    progress_channel,
+    fwupd::{dfu_target, is_pinetime_target},
+    gatt::{decode_value, gatt_name, to_hex, GattCharacteristic, GattService},
+    heart_rate::HeartRate,
+    immediate_alert::AlertLevel,
+    media_player::MediaPlayerEvent,
+    notification::{CallResponse, Notification, MAX_ALERT_SIZE},
+    resources::resources_version_from_filename,
+    weather::{CurrentWeather, DayForecast, WeatherIcon, MAX_FORECAST_DAYS},
+    CharacteristicsMap, ReadCharacteristic, WriteCharacteristic,
+    InfiniTime, InfiniTimeError, DEFAULT_NAME_FILTER,
+    ProgressEvent, ProgressRx, ProgressTx, progress_channel,
+    Sensor, SensorEvent, SensorStream, retry_with_timeout,
 };
+pub use discovery::{scan_for, DiscoveredDevice, ScanAdapter};
 pub use services::start_gatt_services;
+pub use snapshot::{snapshot, DeviceSnapshot};
@@ -1,32 +1,69 @@
 use super::uuids;
 use uuid::Uuid;
-use anyhow::{anyhow, Result};
-use bluer::{gatt::remote::Characteristic, Adapter, Device};
-use futures::{Stream, StreamExt};
-use std::{sync::{Arc, atomic::{AtomicBool, Ordering}}, collections::HashMap};
-use tokio::sync::mpsc;
+use bluer::{gatt::remote::Characteristic, Adapter, Device, DeviceEvent, DeviceProperty};
+use futures::{future, pin_mut, stream::{self, BoxStream}, Stream, StreamExt};
+use std::{sync::{Arc, atomic::{AtomicBool, Ordering}}, collections::HashMap, future::Future, pin::Pin, task::{Context, Poll}, time::Duration};
+use tokio::{sync::{mpsc, OnceCell}, time};
 
+mod characteristics;
+mod error;
+pub use characteristics::{CharacteristicsMap, ReadCharacteristic, WriteCharacteristic};
+pub use error::InfiniTimeError;
+
+pub mod current_time;
 pub mod fs;
 pub mod fwupd;
+pub mod gatt;
+pub mod heart_rate;
+pub mod immediate_alert;
 pub mod notification;
 pub mod media_player;
 pub mod resources;
+pub mod weather;
+
+
+/// Characteristics without which the device is not considered InfiniTime,
+/// along with their service names for error reporting
+const REQUIRED_CHARACTERISTICS: &[(Uuid, &str)] = &[
+    (uuids::CHR_FIRMWARE_REVISION, "Device Information service"),
+    (uuids::CHR_BATTERY_LEVEL, "Battery service"),
+    (uuids::CHR_FWUPD_CONTROL_POINT, "DFU service"),
+];
+
+/// Name prefix that InfiniTime advertises with
+pub const DEFAULT_NAME_FILTER: &str = "InfiniTime";
 
+const RETRY_ATTEMPTS: u32 = 3;
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+const RETRY_TIMEOUT: Duration = Duration::from_secs(2);
+/// BlueZ resolves services some time after connecting, noticeably
+/// later on some adapters
+const SERVICES_RESOLVE_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[derive(Debug)]
 pub struct InfiniTime {
     device: Arc<Device>,
-    characteristics: HashMap<Uuid, Characteristic>,
+    characteristics: CharacteristicsMap,
+    firmware_version: OnceCell<String>,
     is_upgrading_firmware: AtomicBool,
 }
 
 impl InfiniTime {
-    pub async fn new(device: Arc<Device>) -> Result<Self> {
+    pub async fn new(device: Arc<Device>) -> Result<Self, InfiniTimeError> {
+        Self::wait_services_resolved(&device).await?;
         let characteristics = Self::read_characteristics_map(&device).await?;
-        log::debug!("Characteristics: {:#?}", characteristics.keys());
+        log::debug!("Characteristics: {:#?}", characteristics.uuids().collect::<Vec<_>>());
+        let missing = REQUIRED_CHARACTERISTICS.iter()
+            .filter(|(uuid, _)| !characteristics.contains(uuid))
+            .map(|(_, service)| *service)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(InfiniTimeError::MissingServices(missing));
+        }
         Ok(Self {
             device,
             characteristics,
+            firmware_version: OnceCell::new(),
             is_upgrading_firmware: AtomicBool::new(false),
         })
     }
@@ -37,51 +74,106 @@ impl InfiniTime {
 
     // -- Basic getters --
 
-    pub async fn read_battery_level(&self) -> Result<u8> {
-        Ok(self.chr(&uuids::CHR_BATTERY_LEVEL)?.read().await?[0])
+    pub async fn read_battery_level(&self) -> Result<u8, InfiniTimeError> {
+        let data = self.characteristics.read(&uuids::CHR_BATTERY_LEVEL).await?;
+        data.first().cloned()
+            .ok_or(InfiniTimeError::Protocol(String::from("Empty battery level value")))
+    }
+
+    pub async fn read_firmware_version(&self) -> Result<String, InfiniTimeError> {
+        self.read_string(&uuids::CHR_FIRMWARE_REVISION, "Firmware version").await
     }
 
-    pub async fn read_firmware_version(&self) -> Result<String> {
-        let bytes = self.chr(&uuids::CHR_FIRMWARE_REVISION)?.read().await?;
-        Ok(String::from_utf8(bytes)?)
+    pub async fn read_hardware_revision(&self) -> Result<String, InfiniTimeError> {
+        self.read_string(&uuids::CHR_HARDWARE_REVISION, "Hardware revision").await
     }
 
-    pub async fn read_heart_rate(&self) -> Result<u8> {
-        // TODO: Parse properly according to 3.106 Heart Rate Measurement
-        // from https://www.bluetooth.org/docman/handlers/DownloadDoc.ashx?doc_id=539729
-        Ok(self.chr(&uuids::CHR_HEART_RATE)?.read().await?[1])
+    pub async fn read_model_number(&self) -> Result<String, InfiniTimeError> {
+        self.read_string(&uuids::CHR_MODEL_NUMBER, "Model number").await
     }
 
-    pub async fn read_step_count(&self) -> Result<u32> {
-        let data = self.chr(&uuids::CHR_STEP_COUNT)?
-            .read().await?
+    pub async fn read_manufacturer(&self) -> Result<String, InfiniTimeError> {
+        self.read_string(&uuids::CHR_MANUFACTURER_NAME, "Manufacturer name").await
+    }
+
+    /// Firmware version, read from the device once and cached afterwards
+    pub async fn firmware_version(&self) -> Result<String, InfiniTimeError> {
+        self.firmware_version
+            .get_or_try_init(|| self.read_firmware_version())
+            .await
+            .cloned()
+    }
+
+    pub async fn read_step_count(&self) -> Result<u32, InfiniTimeError> {
+        let data = self.characteristics.read(&uuids::CHR_STEP_COUNT).await?
             .try_into()
-            .map_err(|_| anyhow!("Failed to convert Vec<u8> to [u8;4]"))?;
+            .map_err(|_| InfiniTimeError::Protocol(String::from("Failed to convert Vec<u8> to [u8;4]")))?;
         Ok(u32::from_le_bytes(data))
     }
 
+    /// Read UTF-8 string characteristic, such as Device Information service fields
+    async fn read_string(&self, uuid: &Uuid, name: &str) -> Result<String, InfiniTimeError> {
+        let bytes = self.characteristics.read(uuid).await?;
+        String::from_utf8(bytes)
+            .map_err(|_| InfiniTimeError::Protocol(format!("{name} is not valid UTF-8")))
+    }
+
     // -- Media player control --
 
     // -- Event streams --
 
-    pub async fn get_battery_level_stream(&self) -> Result<impl Stream<Item = u8>> {
+    pub async fn get_battery_level_stream(&self) -> Result<impl Stream<Item = u8>, InfiniTimeError> {
         let stream = self.chr(&uuids::CHR_BATTERY_LEVEL)?.notify().await?;
         Ok(stream.filter_map(|v| async move { v.get(0).cloned() }))
     }
 
-    pub async fn get_heart_rate_stream(&self) -> Result<impl Stream<Item = u8>> {
-        let stream = self.chr(&uuids::CHR_HEART_RATE)?.notify().await?;
-        Ok(stream.filter_map(|v| async move { v.get(1).cloned() }))
-    }
-
-    pub async fn get_step_count_stream(&self) -> Result<impl Stream<Item = u32>> {
+    pub async fn get_step_count_stream(&self) -> Result<impl Stream<Item = u32>, InfiniTimeError> {
         let stream = self.chr(&uuids::CHR_STEP_COUNT)?.notify().await?;
         Ok(stream.filter_map(|v| async move {
             v.try_into().ok().map(u32::from_le_bytes)
         }))
     }
 
-    pub async fn get_property_stream(&self) -> Result<impl Stream<Item = bluer::DeviceProperty>> {
+    pub async fn get_motion_stream(&self) -> Result<impl Stream<Item = [i16; 3]>, InfiniTimeError> {
+        let stream = self.chr(&uuids::CHR_MOTION)?.notify().await?;
+        Ok(stream.filter_map(|v| async move {
+            let values: [u8; 6] = v.try_into().ok()?;
+            let axis = |i: usize| i16::from_le_bytes([values[2 * i], values[2 * i + 1]]);
+            Some([axis(0), axis(1), axis(2)])
+        }))
+    }
+
+    /// Subscribe to all sensor notifications as a single stream,
+    /// see [`Self::subscribe`]
+    pub async fn subscribe_all(&self) -> SensorStream<'_> {
+        self.subscribe(&Sensor::ALL).await
+    }
+
+    /// Subscribe to notifications of the given sensors as a single stream.
+    /// Sensors which fail to subscribe (e.g. missing in the firmware) are
+    /// omitted. Dropping the stream unsubscribes from all of them
+    pub async fn subscribe(&self, sensors: &[Sensor]) -> SensorStream<'_> {
+        let mut streams = Vec::new();
+        for sensor in sensors {
+            let result = match sensor {
+                Sensor::BatteryLevel => self.get_battery_level_stream().await
+                    .map(|s| s.map(SensorEvent::BatteryLevel).boxed()),
+                Sensor::HeartRate => self.get_heart_rate_stream().await
+                    .map(|s| s.map(SensorEvent::HeartRate).boxed()),
+                Sensor::StepCount => self.get_step_count_stream().await
+                    .map(|s| s.map(SensorEvent::StepCount).boxed()),
+                Sensor::Motion => self.get_motion_stream().await
+                    .map(|s| s.map(SensorEvent::Motion).boxed()),
+            };
+            match result {
+                Ok(stream) => streams.push(stream),
+                Err(error) => log::warn!("Failed to subscribe to {} notifications: {error}", sensor.name()),
+            }
+        }
+        SensorStream(stream::select_all(streams))
+    }
+
+    pub async fn get_property_stream(&self) -> Result<impl Stream<Item = bluer::DeviceProperty>, InfiniTimeError> {
         Ok(self.device.events().await?.map(|event| {
             let bluer::DeviceEvent::PropertyChanged(property) = event;
             property
@@ -94,30 +186,60 @@ impl InfiniTime {
         self.is_upgrading_firmware.load(Ordering::SeqCst)
     }
 
-    pub async fn check_device(device: &Device) -> bool {
-        match device.name().await {
-            Ok(Some(name)) => name.as_str() == "InfiniTime",
-            _ => false,
+    /// Check whether device name starts with `name_filter`.
+    /// Empty filter matches any device
+    pub async fn check_device(device: &Device, name_filter: &str) -> bool {
+        if name_filter.is_empty() {
+            return true;
         }
+        Self::check_name(device.name().await.ok().flatten().as_deref(), name_filter)
+    }
+
+    /// Name-only version of [`Self::check_device`]
+    pub fn check_name(name: Option<&str>, name_filter: &str) -> bool {
+        name_filter.is_empty() || name.map_or(false, |name| name.starts_with(name_filter))
     }
 
-    pub async fn list_known_devices(adapter: &Adapter) -> Result<Vec<Device>> {
+    pub async fn list_known_devices(adapter: &Adapter, name_filter: &str) -> Result<Vec<Device>, InfiniTimeError> {
         let mut result = Vec::new();
         for address in adapter.device_addresses().await? {
             let device = adapter.device(address)?;
-            if Self::check_device(&device).await {
+            if Self::check_device(&device, name_filter).await {
                 result.push(device);
             }
         }
         Ok(result)
     }
 
-    fn chr<'s>(&'s self, uuid: &Uuid) -> Result<&'s Characteristic> {
+    fn chr<'s>(&'s self, uuid: &Uuid) -> Result<&'s Characteristic, InfiniTimeError> {
         self.characteristics.get(uuid)
-            .ok_or(anyhow!("Characteristic not found by UUID: {}", uuid.to_string()))
     }
 
-    async fn read_characteristics_map(device: &Device) -> Result<HashMap<Uuid, Characteristic>> {
+    /// Wait until BlueZ resolves the services, otherwise some of them
+    /// might be missing from the characteristics map
+    async fn wait_services_resolved(device: &Device) -> Result<(), InfiniTimeError> {
+        // Subscribe before checking, to not miss the change in between
+        let events = device.events().await?;
+        if device.is_services_resolved().await? {
+            return Ok(());
+        }
+        log::debug!("Waiting for services to be resolved");
+        let resolved = events.filter(|event| future::ready(matches!(
+            event,
+            DeviceEvent::PropertyChanged(DeviceProperty::ServicesResolved(true))
+        )));
+        pin_mut!(resolved);
+        match time::timeout(SERVICES_RESOLVE_TIMEOUT, resolved.next()).await {
+            Ok(Some(_)) => log::debug!("Services are resolved"),
+            // Device is gone, reading services reports the actual error
+            Ok(None) => {}
+            // Try anyway, required services might be already available
+            Err(_) => log::warn!("Services are not resolved in {:?}", SERVICES_RESOLVE_TIMEOUT),
+        }
+        Ok(())
+    }
+
+    async fn read_characteristics_map(device: &Device) -> Result<CharacteristicsMap, InfiniTimeError> {
         let mut map = HashMap::new();
         for service in device.services().await? {
             for characteristic in service.characteristics().await? {
@@ -125,7 +247,84 @@ impl InfiniTime {
                 map.insert(uuid, characteristic);
             }
         }
-        Ok(map)
+        Ok(CharacteristicsMap::new(map))
+    }
+}
+
+
+/// Run `operation` up to 3 times, 500ms apart, limiting each attempt with
+/// a timeout. Useful right after connection, when services might not be
+/// fully resolved yet. Returns the last error if all attempts fail
+pub async fn retry_with_timeout<T, E, F, Fut>(mut operation: F) -> Result<T, E>
+    where F: FnMut() -> Fut,
+          Fut: Future<Output = Result<T, E>>,
+          E: From<InfiniTimeError> + std::fmt::Display,
+{
+    let mut attempt = 1;
+    loop {
+        let error = match time::timeout(RETRY_TIMEOUT, operation()).await {
+            Ok(Ok(value)) => return Ok(value),
+            Ok(Err(error)) => error,
+            Err(_) => InfiniTimeError::Timeout(RETRY_TIMEOUT).into(),
+        };
+        if attempt >= RETRY_ATTEMPTS {
+            return Err(error);
+        }
+        log::debug!("Attempt {} of {} failed: {}", attempt, RETRY_ATTEMPTS, error);
+        attempt += 1;
+        time::sleep(RETRY_INTERVAL).await;
+    }
+}
+
+
+/// Sensor to subscribe to, see [`InfiniTime::subscribe`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sensor {
+    BatteryLevel,
+    HeartRate,
+    StepCount,
+    /// Notified at a high rate, so it drains the watch battery faster
+    Motion,
+}
+
+impl Sensor {
+    pub const ALL: [Sensor; 4] = [Sensor::BatteryLevel, Sensor::HeartRate, Sensor::StepCount, Sensor::Motion];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Sensor::BatteryLevel => "battery level",
+            Sensor::HeartRate => "heart rate",
+            Sensor::StepCount => "step count",
+            Sensor::Motion => "motion",
+        }
+    }
+}
+
+/// Sensor notification, see [`InfiniTime::subscribe`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SensorEvent {
+    BatteryLevel(u8),
+    HeartRate(heart_rate::HeartRate),
+    StepCount(u32),
+    /// Accelerometer values for X, Y and Z axes
+    Motion([i16; 3]),
+}
+
+/// Merged sensor notification streams. They're owned here, so dropping it
+/// unsubscribes from all of them
+pub struct SensorStream<'a>(stream::SelectAll<BoxStream<'a, SensorEvent>>);
+
+impl Stream for SensorStream<'_> {
+    type Item = SensorEvent;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.poll_next_unpin(cx)
+    }
+}
+
+impl Drop for SensorStream<'_> {
+    fn drop(&mut self) {
+        log::info!("Unsubscribed from sensor notifications");
     }
 }
 
@@ -136,6 +335,19 @@ pub enum ProgressEvent {
     Numbers { current: u32, total: u32 },
 }
 
+impl ProgressEvent {
+    /// Progress fraction in range `[0.0, 1.0]` for `Numbers` event,
+    /// `None` for messages or if the total is unknown (zero)
+    pub fn fraction(&self) -> Option<f32> {
+        match self {
+            ProgressEvent::Numbers { current, total } if *total > 0 => {
+                Some((*current as f32 / *total as f32).min(1.0))
+            }
+            _ => None,
+        }
+    }
+}
+
 pub type ProgressRx = mpsc::Receiver<ProgressEvent>;
 pub type ProgressTx = mpsc::Sender<ProgressEvent>;
 
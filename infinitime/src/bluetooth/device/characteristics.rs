@@ -0,0 +1,128 @@
+use super::InfiniTimeError;
+use bluer::gatt::remote::Characteristic;
+use std::{collections::HashMap, future::Future};
+use uuid::Uuid;
+
+
+/// Readable GATT characteristic. Abstracts over `bluer` characteristic,
+/// so that the lookup logic can be tested without hardware
+pub trait ReadCharacteristic {
+    fn read_value(&self) -> impl Future<Output = Result<Vec<u8>, InfiniTimeError>> + Send;
+}
+
+impl ReadCharacteristic for Characteristic {
+    async fn read_value(&self) -> Result<Vec<u8>, InfiniTimeError> {
+        Ok(self.read().await?)
+    }
+}
+
+/// Writable GATT characteristic, see [`ReadCharacteristic`]
+pub trait WriteCharacteristic {
+    fn write_value(&self, value: &[u8]) -> impl Future<Output = Result<(), InfiniTimeError>> + Send;
+}
+
+impl WriteCharacteristic for Characteristic {
+    async fn write_value(&self, value: &[u8]) -> Result<(), InfiniTimeError> {
+        Ok(self.write(value).await?)
+    }
+}
+
+
+/// Device characteristics indexed by UUID
+#[derive(Debug)]
+pub struct CharacteristicsMap<C = Characteristic>(HashMap<Uuid, C>);
+
+impl<C> CharacteristicsMap<C> {
+    pub fn new(map: HashMap<Uuid, C>) -> Self {
+        Self(map)
+    }
+
+    pub fn contains(&self, uuid: &Uuid) -> bool {
+        self.0.contains_key(uuid)
+    }
+
+    pub fn uuids(&self) -> impl Iterator<Item = &Uuid> {
+        self.0.keys()
+    }
+
+    pub fn get(&self, uuid: &Uuid) -> Result<&C, InfiniTimeError> {
+        self.0.get(uuid).ok_or(InfiniTimeError::CharacteristicNotFound(*uuid))
+    }
+
+    /// Remove characteristic from the map, taking ownership of it
+    pub fn take(&mut self, uuid: &Uuid) -> Result<C, InfiniTimeError> {
+        self.0.remove(uuid).ok_or(InfiniTimeError::CharacteristicNotFound(*uuid))
+    }
+}
+
+impl<C: ReadCharacteristic> CharacteristicsMap<C> {
+    pub async fn read(&self, uuid: &Uuid) -> Result<Vec<u8>, InfiniTimeError> {
+        self.get(uuid)?.read_value().await
+    }
+}
+
+impl<C: WriteCharacteristic> CharacteristicsMap<C> {
+    pub async fn write(&self, uuid: &Uuid, value: &[u8]) -> Result<(), InfiniTimeError> {
+        self.get(uuid)?.write_value(value).await
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bluetooth::uuids;
+    use futures::executor::block_on;
+
+    struct MockCharacteristic(Vec<u8>);
+
+    impl ReadCharacteristic for MockCharacteristic {
+        async fn read_value(&self) -> Result<Vec<u8>, InfiniTimeError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    fn mock_map() -> CharacteristicsMap<MockCharacteristic> {
+        CharacteristicsMap::new(HashMap::from([
+            (uuids::CHR_BATTERY_LEVEL, MockCharacteristic(vec![42])),
+            (uuids::CHR_FIRMWARE_REVISION, MockCharacteristic(b"1.14.0".to_vec())),
+        ]))
+    }
+
+    #[test]
+    fn take_existing() {
+        let mut map = mock_map();
+        let chr = map.take(&uuids::CHR_BATTERY_LEVEL).unwrap();
+        assert_eq!(chr.0, vec![42]);
+        assert!(!map.contains(&uuids::CHR_BATTERY_LEVEL));
+        assert!(map.contains(&uuids::CHR_FIRMWARE_REVISION));
+    }
+
+    #[test]
+    fn take_removed() {
+        let mut map = mock_map();
+        map.take(&uuids::CHR_BATTERY_LEVEL).unwrap();
+        let error = map.take(&uuids::CHR_BATTERY_LEVEL).unwrap_err();
+        assert!(matches!(
+            error,
+            InfiniTimeError::CharacteristicNotFound(uuid) if uuid == uuids::CHR_BATTERY_LEVEL
+        ));
+    }
+
+    #[test]
+    fn not_found_message() {
+        let map = mock_map();
+        let error = map.get(&uuids::CHR_HEART_RATE).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("Characteristic not found by UUID: {}", uuids::CHR_HEART_RATE),
+        );
+    }
+
+    #[test]
+    fn read_mock() {
+        let map = mock_map();
+        assert_eq!(block_on(map.read(&uuids::CHR_FIRMWARE_REVISION)).unwrap(), b"1.14.0");
+        assert!(block_on(map.read(&uuids::CHR_HEART_RATE)).is_err());
+    }
+}
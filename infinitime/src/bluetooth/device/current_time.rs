@@ -0,0 +1,73 @@
+use super::{uuids, InfiniTime, InfiniTimeError};
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, Timelike};
+
+
+/// Current Time characteristic value size, without the optional fields
+const CURRENT_TIME_SIZE: usize = 10;
+/// Adjust reason flag of the Current Time characteristic
+const ADJUST_REASON_MANUAL: u8 = 0x01;
+
+
+/// Encode Current Time characteristic value (Bluetooth CTS 3.62)
+pub(crate) fn encode_current_time(time: &NaiveDateTime, adjust_reason: u8) -> Vec<u8> {
+    let year = (time.year() as u16).to_le_bytes();
+    vec![
+        year[0],
+        year[1],
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+        time.weekday().number_from_monday() as u8,
+        (time.nanosecond().min(999_999_999) as u64 * 256 / 1_000_000_000) as u8,
+        adjust_reason,
+    ]
+}
+
+fn decode_current_time(data: &[u8]) -> Option<NaiveDateTime> {
+    if data.len() < CURRENT_TIME_SIZE {
+        return None;
+    }
+    let year = u16::from_le_bytes([data[0], data[1]]);
+    let date = NaiveDate::from_ymd_opt(year as i32, data[2] as u32, data[3] as u32)?;
+    let nanos = (data[8] as u64 * 1_000_000_000 / 256) as u32;
+    date.and_hms_nano_opt(data[4] as u32, data[5] as u32, data[6] as u32, nanos)
+}
+
+
+impl InfiniTime {
+    /// Local time on the watch
+    pub async fn read_current_time(&self) -> Result<NaiveDateTime, InfiniTimeError> {
+        let data = self.characteristics.read(&uuids::CHR_CURRENT_TIME).await?;
+        decode_current_time(&data)
+            .ok_or(InfiniTimeError::Protocol(String::from("Invalid current time value")))
+    }
+
+    /// Set the watch clock to the host local time
+    pub async fn sync_current_time(&self) -> Result<(), InfiniTimeError> {
+        let value = encode_current_time(&Local::now().naive_local(), ADJUST_REASON_MANUAL);
+        Ok(self.chr(&uuids::CHR_CURRENT_TIME)?.write(&value).await?)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let time = NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+            .and_hms_milli_opt(23, 59, 58, 500).unwrap();
+        let data = encode_current_time(&time, 0);
+        assert_eq!(data, [0xe8, 0x07, 2, 29, 23, 59, 58, 4, 128, 0]);
+        assert_eq!(decode_current_time(&data), Some(time));
+    }
+
+    #[test]
+    fn invalid() {
+        assert_eq!(decode_current_time(&[0xe8, 0x07, 2, 30, 0, 0, 0, 5, 0, 0]), None);
+        assert_eq!(decode_current_time(&[0xe8, 0x07, 2]), None);
+    }
+}
@@ -0,0 +1,20 @@
+use uuid::Uuid;
+
+
+#[derive(Debug, thiserror::Error)]
+pub enum InfiniTimeError {
+    /// The watch doesn't provide the characteristic (e.g. older firmware)
+    #[error("Characteristic not found by UUID: {0}")]
+    CharacteristicNotFound(Uuid),
+    /// The device doesn't provide services required for InfiniTime
+    #[error("Missing {}", .0.join(", "))]
+    MissingServices(Vec<&'static str>),
+    /// The watch responded with unexpected data
+    #[error("Protocol error: {0}")]
+    Protocol(String),
+    #[error("Timed out after {0:?}")]
+    Timeout(std::time::Duration),
+    /// Bluetooth stack error, e.g. write failure or the device is gone
+    #[error(transparent)]
+    Bluer(#[from] bluer::Error),
+}
@@ -1,16 +1,28 @@
 use crate::utils;
 use super::{uuids, InfiniTime, ProgressTx, ProgressTxWrapper};
 use anyhow::{anyhow, ensure, Result};
-use futures::{pin_mut, StreamExt};
+use bluer::gatt::remote::Characteristic;
+use futures::{pin_mut, Stream, StreamExt};
 use serde::Deserialize;
 use std::{
-    io::{Cursor, Read},
+    io::{Cursor, Read, Seek},
     sync::atomic::Ordering,
+    time::Duration,
 };
+use tokio::time;
 
 
 pub const MAX_FIRMWARE_SIZE: usize = 512 * 1024;
 
+/// How many times to run the DFU session before giving up. Single writes and
+/// notifications aren't retried, because the watch counts received bytes,
+/// so the whole transfer is started over instead
+const SESSION_ATTEMPTS: u32 = 3;
+/// Gives the watch time to drop the interrupted session before starting over
+const SESSION_RETRY_DELAY: Duration = Duration::from_secs(15);
+/// How long to wait for a control point notification
+const RECEIPT_TIMEOUT: Duration = Duration::from_secs(10);
+
 
 #[derive(Deserialize, Debug)]
 struct Manifest {
@@ -39,6 +51,85 @@ struct Application {
 //     softdevice_req: Vec<u16>,
 // }
 
+impl ManifestInner {
+    fn from_archive<R: Read + Seek>(zip: &mut zip::ZipArchive<R>) -> Result<Self> {
+        let mut json = String::new();
+        zip.by_name("manifest.json")?.read_to_string(&mut json)?;
+        let manifest = serde_json::from_str::<Manifest>(&json)
+            .map_err(|_| anyhow!("Invalid manifest.json"))?;
+        Ok(manifest.manifest)
+    }
+}
+
+/// Application the DFU package is built for, named after the firmware
+/// image in its `manifest.json` without the version suffix,
+/// e.g. `pinetime-mcuboot-app` for `pinetime-mcuboot-app-image-1.14.0.bin`
+pub fn dfu_target(dfu_content: &[u8]) -> Result<String> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(dfu_content))?;
+    let manifest = ManifestInner::from_archive(&mut zip)?;
+    Ok(dfu_target_from_filename(&manifest.application.bin_file))
+}
+
+fn dfu_target_from_filename(filename: &str) -> String {
+    let stem = filename.strip_suffix(".bin").unwrap_or(filename);
+    // Version suffix starts with the first dash followed by a digit
+    let name = stem.match_indices('-')
+        .find(|(i, _)| stem[i + 1..].starts_with(|c: char| c.is_ascii_digit()))
+        .map_or(stem, |(i, _)| &stem[..i]);
+    name.strip_suffix("-image").unwrap_or(name).to_string()
+}
+
+/// Whether the DFU target (see [`dfu_target`]) looks like PineTime firmware
+pub fn is_pinetime_target(target: &str) -> bool {
+    let target = target.to_lowercase();
+    target.contains("pinetime") || target.contains("infinitime")
+}
+
+/// DFU session failure
+#[derive(Debug)]
+enum SessionError {
+    /// Failed write or missed notification, worth starting over
+    Transient(anyhow::Error),
+    Fatal(anyhow::Error),
+}
+
+impl From<bluer::Error> for SessionError {
+    fn from(error: bluer::Error) -> Self {
+        match error.kind {
+            bluer::ErrorKind::NotConnected => Self::Fatal(error.into()),
+            _ => Self::Transient(error.into()),
+        }
+    }
+}
+
+impl From<anyhow::Error> for SessionError {
+    fn from(error: anyhow::Error) -> Self {
+        Self::Fatal(error)
+    }
+}
+
+async fn next_receipt<S>(stream: &mut S) -> Result<Vec<u8>, SessionError>
+    where S: Stream<Item = Vec<u8>> + Unpin
+{
+    match time::timeout(RECEIPT_TIMEOUT, stream.next()).await {
+        Ok(Some(receipt)) => Ok(receipt),
+        Ok(None) => Err(anyhow!("Control point notification stream ended").into()),
+        Err(_) => Err(SessionError::Transient(anyhow!("No response from the watch within {:?}", RECEIPT_TIMEOUT))),
+    }
+}
+
+fn check_receipt(receipt: &[u8], expected: &[u8]) -> Result<(), SessionError> {
+    match receipt == expected {
+        true => Ok(()),
+        false => Err(anyhow!("Unexpected DFU response: {:02x?}", receipt).into()),
+    }
+}
+
+/// Bytes received by the watch, from a packet receipt notification
+fn received_bytes(receipt: &[u8]) -> Option<u32> {
+    Some(u32::from_le_bytes(receipt.get(1..5)?.try_into().ok()?))
+}
+
 
 impl InfiniTime {
     pub async fn firmware_upgrade(&self, dfu_content: &[u8], progress_sender: Option<ProgressTx>) -> Result<()> {
@@ -56,11 +147,7 @@ impl InfiniTime {
 
         // Parse manifest from the archive
         let mut zip = zip::ZipArchive::new(Cursor::new(dfu_content))?;
-        let mut json = String::new();
-        zip.by_name("manifest.json")?.read_to_string(&mut json)?;
-        let manifest = serde_json::from_str::<Manifest>(&json)
-            .map_err(|_| anyhow!("Invalid manifest.json"))?.manifest;
-
+        let manifest = ManifestInner::from_archive(&mut zip)?;
 
         // Read DFU data
         let mut init_packet = Vec::new();
@@ -74,75 +161,128 @@ impl InfiniTime {
             file.read_to_end(&mut firmware_buffer)?;
         }
 
-        // Obtain characteristics
-        let control_point_stream = chr_ctrl.notify().await?;
-        pin_mut!(control_point_stream);
-
-        // Step 1
-        progress.report_msg("Initiating firmware upgrade...").await;
-        chr_ctrl.write(&[0x01, 0x04]).await?;
-
-        // Step 2
-        let mut size_packet = vec![0; 8];
-        let firmware_size = firmware_buffer.len() as u32;
-        size_packet.extend_from_slice(&firmware_size.to_le_bytes());
-        chr_packet.write(&size_packet).await?;
-
-        let receipt = control_point_stream.next().await
-            .ok_or(anyhow!("Control point notification stream ended"))?;
-        ensure!(receipt == &[0x10, 0x01, 0x01]);
-
-        // Step 3
-        progress.report_msg("Sending DFU init packet...").await;
-        chr_ctrl.write(&[0x02, 0x00]).await?;
-
-        // Step 4
-        chr_packet.write(&init_packet).await?;
-        chr_ctrl.write(&[0x02, 0x01]).await?;
-
-        let receipt = control_point_stream.next().await
-            .ok_or(anyhow!("Control point notification stream ended"))?;
-        ensure!(receipt == &[0x10, 0x02, 0x01]);
-
-        // Step 5
-        progress.report_msg("Configuring receipt interval...").await;
-        let receipt_interval = 100;
-        chr_ctrl.write(&[0x08, receipt_interval]).await?;
-
-        // Step 6
-        chr_ctrl.write(&[0x03]).await?;
-
-        // Step 7
-        progress.report_msg("Sending firmware...").await;
-        let mut bytes_sent = 0;
-        for (idx, packet) in firmware_buffer.chunks(20).enumerate() {
-            chr_packet.write(&packet).await?;
-            bytes_sent += packet.len() as u32;
-            if (idx + 1) % receipt_interval as usize == 0 {
-                let receipt = control_point_stream.next().await
-                    .ok_or(anyhow!("Control point notification stream ended"))?;
-                let bytes_received = u32::from_le_bytes(receipt[1..5].try_into()?);
-                ensure!(bytes_sent == bytes_received);
-                progress.report_num(bytes_sent, firmware_size).await;
+        let mut attempt = 1;
+        loop {
+            let result = dfu_session(chr_ctrl, chr_packet, &init_packet, &firmware_buffer, &progress).await;
+            match result {
+                Ok(()) => break,
+                Err(SessionError::Transient(error)) if attempt < SESSION_ATTEMPTS => {
+                    log::warn!("DFU attempt {} of {} failed: {}", attempt, SESSION_ATTEMPTS, error);
+                    progress.report_msg("Retrying...").await;
+                    attempt += 1;
+                    time::sleep(SESSION_RETRY_DELAY).await;
+                }
+                Err(SessionError::Transient(error) | SessionError::Fatal(error)) => return Err(error),
             }
         }
 
-        // Step 8
-        progress.report_msg("Waiting for firmware receipt...").await;
-        let receipt = control_point_stream.next().await
-            .ok_or(anyhow!("Control point notification stream ended"))?;
-        ensure!(receipt == &[0x10, 0x03, 0x01]);
-        chr_ctrl.write(&[0x04]).await?;
-
-        // Step 9
-        progress.report_msg("Waiting for firmware validation...").await;
-        let receipt = control_point_stream.next().await
-            .ok_or(anyhow!("Control point notification stream ended"))?;
-        ensure!(receipt == &[0x10, 0x04, 0x01]);
-        chr_ctrl.write(&[0x05]).await?;
-
         progress.report_msg("Done!").await;
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+
+async fn dfu_session(
+    chr_ctrl: &Characteristic, chr_packet: &Characteristic, init_packet: &[u8],
+    firmware: &[u8], progress: &ProgressTxWrapper,
+) -> Result<(), SessionError> {
+    let control_point_stream = chr_ctrl.notify().await?;
+    pin_mut!(control_point_stream);
+
+    // Step 1
+    progress.report_msg("Initiating firmware upgrade...").await;
+    chr_ctrl.write(&[0x01, 0x04]).await?;
+
+    // Step 2
+    let mut size_packet = vec![0; 8];
+    let firmware_size = firmware.len() as u32;
+    size_packet.extend_from_slice(&firmware_size.to_le_bytes());
+    chr_packet.write(&size_packet).await?;
+
+    let receipt = next_receipt(&mut control_point_stream).await?;
+    check_receipt(&receipt, &[0x10, 0x01, 0x01])?;
+
+    // Step 3
+    progress.report_msg("Sending DFU init packet...").await;
+    chr_ctrl.write(&[0x02, 0x00]).await?;
+
+    // Step 4
+    chr_packet.write(init_packet).await?;
+    chr_ctrl.write(&[0x02, 0x01]).await?;
+
+    let receipt = next_receipt(&mut control_point_stream).await?;
+    check_receipt(&receipt, &[0x10, 0x02, 0x01])?;
+
+    // Step 5
+    progress.report_msg("Configuring receipt interval...").await;
+    let receipt_interval = 100;
+    chr_ctrl.write(&[0x08, receipt_interval]).await?;
+
+    // Step 6
+    chr_ctrl.write(&[0x03]).await?;
+
+    // Step 7
+    progress.report_msg("Sending firmware...").await;
+    let mut bytes_sent = 0;
+    for (idx, packet) in firmware.chunks(20).enumerate() {
+        chr_packet.write(packet).await?;
+        bytes_sent += packet.len() as u32;
+        if (idx + 1) % receipt_interval as usize == 0 {
+            let receipt = next_receipt(&mut control_point_stream).await?;
+            let bytes_received = received_bytes(&receipt)
+                .ok_or(anyhow!("Invalid packet receipt: {:02x?}", receipt))?;
+            if bytes_received != bytes_sent {
+                return Err(anyhow!("Watch received {} bytes out of {}", bytes_received, bytes_sent).into());
+            }
+            progress.report_num(bytes_sent, firmware_size).await;
+        }
+    }
+
+    // Step 8
+    progress.report_msg("Waiting for firmware receipt...").await;
+    let receipt = next_receipt(&mut control_point_stream).await?;
+    check_receipt(&receipt, &[0x10, 0x03, 0x01])?;
+    chr_ctrl.write(&[0x04]).await?;
+
+    // Step 9
+    progress.report_msg("Waiting for firmware validation...").await;
+    let receipt = next_receipt(&mut control_point_stream).await?;
+    check_receipt(&receipt, &[0x10, 0x04, 0x01])?;
+    // The image is validated already, starting over makes no sense
+    chr_ctrl.write(&[0x05]).await.map_err(|error| SessionError::Fatal(error.into()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_from_filename() {
+        assert_eq!(dfu_target_from_filename("pinetime-mcuboot-app-image-1.14.0.bin"), "pinetime-mcuboot-app");
+        assert_eq!(dfu_target_from_filename("pinetime-mcuboot-app-image-1.14.0-rc1.bin"), "pinetime-mcuboot-app");
+        assert_eq!(dfu_target_from_filename("app.bin"), "app");
+        assert_eq!(dfu_target_from_filename("some-other-watch"), "some-other-watch");
+    }
+
+    #[test]
+    fn pinetime_target() {
+        assert!(is_pinetime_target("pinetime-mcuboot-app"));
+        assert!(is_pinetime_target("InfiniTime"));
+        assert!(!is_pinetime_target("bangle-app"));
+    }
+
+    #[test]
+    fn packet_receipt() {
+        assert_eq!(received_bytes(&[0x11, 0xd0, 0x07, 0x00, 0x00]), Some(2000));
+        assert_eq!(received_bytes(&[0x11, 0xd0]), None);
+    }
+
+    #[test]
+    fn receipt_mismatch() {
+        assert!(check_receipt(&[0x10, 0x01, 0x01], &[0x10, 0x01, 0x01]).is_ok());
+        assert!(matches!(check_receipt(&[0x10, 0x01, 0x06], &[0x10, 0x01, 0x01]), Err(SessionError::Fatal(_))));
+    }
+}
@@ -0,0 +1,150 @@
+use super::{heart_rate::{decode_heart_rate, HeartRate}, uuids, InfiniTime, InfiniTimeError};
+use uuid::Uuid;
+
+
+/// GATT service exposed by the watch, for inspection
+#[derive(Debug, Clone)]
+pub struct GattService {
+    pub uuid: Uuid,
+    pub characteristics: Vec<GattCharacteristic>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GattCharacteristic {
+    pub uuid: Uuid,
+    pub readable: bool,
+    pub notifiable: bool,
+}
+
+impl InfiniTime {
+    /// List all services and characteristics of the device, as opposed to
+    /// only the known ones used by the rest of the API. Useful for debugging
+    pub async fn gatt_services(&self) -> Result<Vec<GattService>, InfiniTimeError> {
+        let mut services = Vec::new();
+        for service in self.device.services().await? {
+            let mut characteristics = Vec::new();
+            for characteristic in service.characteristics().await? {
+                let flags = characteristic.flags().await?;
+                characteristics.push(GattCharacteristic {
+                    uuid: characteristic.uuid().await?,
+                    readable: flags.read,
+                    notifiable: flags.notify,
+                });
+            }
+            characteristics.sort_by_key(|c| c.uuid);
+            services.push(GattService { uuid: service.uuid().await?, characteristics });
+        }
+        services.sort_by_key(|s| s.uuid);
+        Ok(services)
+    }
+
+    /// Read raw value of any characteristic
+    pub async fn read_raw(&self, uuid: &Uuid) -> Result<Vec<u8>, InfiniTimeError> {
+        self.characteristics.read(uuid).await
+    }
+}
+
+
+/// Human-readable name of a service or characteristic known to this library
+pub fn gatt_name(uuid: &Uuid) -> Option<&'static str> {
+    let name = match *uuid {
+        uuids::SRV_CURRENT_TIME => "Current Time Service",
+        uuids::CHR_CURRENT_TIME => "Current Time",
+        uuids::SRV_IMMEDIATE_ALERT => "Immediate Alert Service",
+        uuids::CHR_ALERT_LEVEL => "Alert Level",
+        uuids::CHR_BATTERY_LEVEL => "Battery Level",
+        uuids::CHR_MODEL_NUMBER => "Model Number",
+        uuids::CHR_FIRMWARE_REVISION => "Firmware Revision",
+        uuids::CHR_HARDWARE_REVISION => "Hardware Revision",
+        uuids::CHR_MANUFACTURER_NAME => "Manufacturer Name",
+        uuids::CHR_HEART_RATE => "Heart Rate Measurement",
+        uuids::CHR_UNREAD_ALERT_STATUS => "Unread Alert Status",
+        uuids::CHR_NEW_ALERT => "New Alert",
+        uuids::CHR_NOTIFICATION_EVENT => "Notification Event",
+        uuids::CHR_FS_VERSION => "File System Version",
+        uuids::CHR_FS_TRANSFER => "File System Transfer",
+        uuids::CHR_FWUPD_CONTROL_POINT => "DFU Control Point",
+        uuids::CHR_FWUPD_PACKET => "DFU Packet",
+        uuids::CHR_MP_EVENTS => "Music Events",
+        uuids::CHR_MP_STATUS => "Music Status",
+        uuids::CHR_MP_ARTIST => "Music Artist",
+        uuids::CHR_MP_TRACK => "Music Track",
+        uuids::CHR_MP_ALBUM => "Music Album",
+        uuids::CHR_MP_POSITION => "Music Position",
+        uuids::CHR_MP_DURATION => "Music Duration",
+        uuids::CHR_MP_SPEED => "Music Speed",
+        uuids::CHR_MP_REPEAT => "Music Repeat",
+        uuids::CHR_MP_SHUFFLE => "Music Shuffle",
+        uuids::CHR_STEP_COUNT => "Step Count",
+        uuids::CHR_MOTION => "Motion Values",
+        uuids::CHR_WEATHER => "Simple Weather",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Format bytes as space-separated hex, e.g. "2a 00"
+pub fn to_hex(value: &[u8]) -> String {
+    value.iter().map(|b| format!("{b:02x}")).collect::<Vec<_>>().join(" ")
+}
+
+/// Decode value of a known characteristic, `None` if it's unknown or malformed
+pub fn decode_value(uuid: &Uuid, value: &[u8]) -> Option<String> {
+    match *uuid {
+        uuids::CHR_BATTERY_LEVEL => value.first().map(|v| format!("{v}%")),
+        uuids::CHR_HEART_RATE => decode_heart_rate(value).map(|hr| match hr {
+            HeartRate::Bpm(bpm) => format!("{bpm} bpm"),
+            HeartRate::Measuring => "measuring".into(),
+            HeartRate::NotWorn => "not worn".into(),
+        }),
+        uuids::CHR_STEP_COUNT => {
+            let bytes = value.try_into().ok()?;
+            Some(format!("{} steps", u32::from_le_bytes(bytes)))
+        }
+        uuids::CHR_MODEL_NUMBER
+        | uuids::CHR_FIRMWARE_REVISION
+        | uuids::CHR_HARDWARE_REVISION
+        | uuids::CHR_MANUFACTURER_NAME
+        | uuids::CHR_MP_ARTIST
+        | uuids::CHR_MP_TRACK
+        | uuids::CHR_MP_ALBUM => {
+            std::str::from_utf8(value).ok().map(|s| format!("\"{s}\""))
+        }
+        _ => None,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex() {
+        assert_eq!(to_hex(&[]), "");
+        assert_eq!(to_hex(&[0x2a, 0x00, 0xff]), "2a 00 ff");
+    }
+
+    #[test]
+    fn decode_known() {
+        assert_eq!(decode_value(&uuids::CHR_BATTERY_LEVEL, &[87]).as_deref(), Some("87%"));
+        assert_eq!(decode_value(&uuids::CHR_HEART_RATE, &[0, 72]).as_deref(), Some("72 bpm"));
+        assert_eq!(decode_value(&uuids::CHR_HEART_RATE, &[0x01, 0x2c, 0x01]).as_deref(), Some("300 bpm"));
+        assert_eq!(decode_value(&uuids::CHR_STEP_COUNT, &[0x10, 0x27, 0, 0]).as_deref(), Some("10000 steps"));
+        assert_eq!(decode_value(&uuids::CHR_FIRMWARE_REVISION, b"1.14.0").as_deref(), Some("\"1.14.0\""));
+    }
+
+    #[test]
+    fn decode_malformed_or_unknown() {
+        assert_eq!(decode_value(&uuids::CHR_BATTERY_LEVEL, &[]), None);
+        assert_eq!(decode_value(&uuids::CHR_STEP_COUNT, &[1, 2]), None);
+        assert_eq!(decode_value(&uuids::CHR_MODEL_NUMBER, &[0xff, 0xfe]), None);
+        assert_eq!(decode_value(&uuids::CHR_FWUPD_PACKET, &[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(gatt_name(&uuids::CHR_BATTERY_LEVEL), Some("Battery Level"));
+        assert_eq!(gatt_name(&Uuid::nil()), None);
+    }
+}
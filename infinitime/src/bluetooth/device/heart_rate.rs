@@ -0,0 +1,106 @@
+use super::{uuids, InfiniTime, InfiniTimeError};
+use futures::{Stream, StreamExt};
+
+
+// Heart Rate Measurement flags (Bluetooth HRS 3.1)
+const FLAG_VALUE_U16: u8 = 0x01;
+const FLAG_CONTACT_DETECTED: u8 = 0x02;
+const FLAG_CONTACT_SUPPORTED: u8 = 0x04;
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeartRate {
+    /// No valid value yet, e.g. the sensor hasn't locked on.
+    /// InfiniTime reports it as 0 bpm
+    Measuring,
+    /// The sensor reports no skin contact
+    NotWorn,
+    Bpm(u16),
+}
+
+/// Decode Heart Rate Measurement characteristic value
+pub(super) fn decode_heart_rate(data: &[u8]) -> Option<HeartRate> {
+    let flags = *data.first()?;
+    let bpm = if flags & FLAG_VALUE_U16 != 0 {
+        u16::from_le_bytes([*data.get(1)?, *data.get(2)?])
+    } else {
+        *data.get(1)? as u16
+    };
+    let not_worn = flags & FLAG_CONTACT_SUPPORTED != 0 && flags & FLAG_CONTACT_DETECTED == 0;
+    Some(match bpm {
+        _ if not_worn => HeartRate::NotWorn,
+        0 => HeartRate::Measuring,
+        bpm => HeartRate::Bpm(bpm),
+    })
+}
+
+/// Whether the reading is spurious after the `previous` reported one
+fn skip_heart_rate(hr: HeartRate, previous: Option<HeartRate>) -> bool {
+    match (hr, previous) {
+        (HeartRate::Bpm(_), _) => false,
+        (HeartRate::Measuring, Some(HeartRate::Bpm(_))) => true,
+        (hr, previous) => previous == Some(hr),
+    }
+}
+
+
+impl InfiniTime {
+    pub async fn read_heart_rate(&self) -> Result<HeartRate, InfiniTimeError> {
+        let data = self.characteristics.read(&uuids::CHR_HEART_RATE).await?;
+        decode_heart_rate(&data)
+            .ok_or(InfiniTimeError::Protocol(String::from("Heart rate value is too short")))
+    }
+
+    /// Heart rate notifications. The watch sends zeros before it locks on
+    /// and after the measurement stops, so `Measuring` is only reported
+    /// until the first valid value, and repeated states are skipped
+    pub async fn get_heart_rate_stream(&self) -> Result<impl Stream<Item = HeartRate>, InfiniTimeError> {
+        let stream = self.chr(&uuids::CHR_HEART_RATE)?.notify().await?;
+        let mut previous = None;
+        Ok(stream
+            .filter_map(|v| async move { decode_heart_rate(&v) })
+            .filter(move |hr| {
+                let skip = skip_heart_rate(*hr, previous);
+                if !skip {
+                    previous = Some(*hr);
+                }
+                async move { !skip }
+            }))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(decode_heart_rate(&[0, 72]), Some(HeartRate::Bpm(72)));
+        assert_eq!(decode_heart_rate(&[0, 0]), Some(HeartRate::Measuring));
+        assert_eq!(decode_heart_rate(&[FLAG_VALUE_U16, 0x2c, 0x01]), Some(HeartRate::Bpm(300)));
+        assert_eq!(decode_heart_rate(&[0]), None);
+        assert_eq!(decode_heart_rate(&[FLAG_VALUE_U16, 72]), None);
+    }
+
+    #[test]
+    fn sensor_contact() {
+        let contact = FLAG_CONTACT_SUPPORTED | FLAG_CONTACT_DETECTED;
+        assert_eq!(decode_heart_rate(&[contact, 72]), Some(HeartRate::Bpm(72)));
+        assert_eq!(decode_heart_rate(&[FLAG_CONTACT_SUPPORTED, 72]), Some(HeartRate::NotWorn));
+        // Contact bit alone is meaningless
+        assert_eq!(decode_heart_rate(&[FLAG_CONTACT_DETECTED, 0]), Some(HeartRate::Measuring));
+    }
+
+    #[test]
+    fn spurious_readings() {
+        use HeartRate::*;
+        assert!(!skip_heart_rate(Measuring, None));
+        assert!(skip_heart_rate(Measuring, Some(Measuring)));
+        assert!(skip_heart_rate(Measuring, Some(Bpm(72))));
+        assert!(!skip_heart_rate(Measuring, Some(NotWorn)));
+        assert!(!skip_heart_rate(Bpm(72), Some(Bpm(72))));
+        assert!(!skip_heart_rate(NotWorn, Some(Bpm(72))));
+        assert!(skip_heart_rate(NotWorn, Some(NotWorn)));
+    }
+}
@@ -0,0 +1,32 @@
+use crate::utils::value_enum;
+use super::{uuids, InfiniTime, InfiniTimeError};
+use bluer::gatt::{remote::CharacteristicWriteRequest, WriteOp};
+
+
+value_enum! {
+    /// Alert Level characteristic value (Bluetooth IAS)
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum AlertLevel::<u8> {
+        None = 0,
+        Mild = 1,
+        High = 2
+    }
+}
+
+
+impl InfiniTime {
+    /// Make the watch ring until `stop_find_watch` is called
+    pub async fn trigger_find_watch(&self) -> Result<(), InfiniTimeError> {
+        self.write_alert_level(AlertLevel::High).await
+    }
+
+    pub async fn stop_find_watch(&self) -> Result<(), InfiniTimeError> {
+        self.write_alert_level(AlertLevel::None).await
+    }
+
+    async fn write_alert_level(&self, level: AlertLevel) -> Result<(), InfiniTimeError> {
+        // Alert Level only supports write without response
+        let request = CharacteristicWriteRequest { op_type: WriteOp::Command, ..Default::default() };
+        Ok(self.chr(&uuids::CHR_ALERT_LEVEL)?.write_ext(&[level as u8], &request).await?)
+    }
+}
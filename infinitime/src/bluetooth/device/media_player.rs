@@ -1,5 +1,4 @@
-use super::{uuids, InfiniTime};
-use anyhow::Result;
+use super::{uuids, InfiniTime, InfiniTimeError};
 use futures::{Stream, StreamExt};
 
 #[derive(Debug)]
@@ -30,45 +29,45 @@ impl MediaPlayerEvent {
 
 
 impl InfiniTime {
-    pub async fn get_media_player_events_stream(&self) -> Result<impl Stream<Item = MediaPlayerEvent>> {
+    pub async fn get_media_player_events_stream(&self) -> Result<impl Stream<Item = MediaPlayerEvent>, InfiniTimeError> {
         let stream = self.chr(&uuids::CHR_MP_EVENTS)?.notify().await?;
-        Ok(stream.filter_map(|v| async move { MediaPlayerEvent::from_raw(v[0]) }))
+        Ok(stream.filter_map(|v| async move { v.first().cloned().and_then(MediaPlayerEvent::from_raw) }))
     }
 
-    pub async fn write_mp_artist(&self, artist: &str) -> Result<()> {
+    pub async fn write_mp_artist(&self, artist: &str) -> Result<(), InfiniTimeError> {
         Ok(self.chr(&uuids::CHR_MP_ARTIST)?.write(artist.as_ref()).await?)
     }
 
-    pub async fn write_mp_album(&self, album: &str) -> Result<()> {
+    pub async fn write_mp_album(&self, album: &str) -> Result<(), InfiniTimeError> {
         Ok(self.chr(&uuids::CHR_MP_ALBUM)?.write(album.as_ref()).await?)
     }
 
-    pub async fn write_mp_track(&self, track: &str) -> Result<()> {
+    pub async fn write_mp_track(&self, track: &str) -> Result<(), InfiniTimeError> {
         Ok(self.chr(&uuids::CHR_MP_TRACK)?.write(track.as_ref()).await?)
     }
 
-    pub async fn write_mp_playback_status(&self, playing: bool) -> Result<()> {
+    pub async fn write_mp_playback_status(&self, playing: bool) -> Result<(), InfiniTimeError> {
         Ok(self.chr(&uuids::CHR_MP_STATUS)?.write(&[u8::from(playing)]).await?)
     }
 
-    pub async fn write_mp_position(&self, position: u32) -> Result<()> {
+    pub async fn write_mp_position(&self, position: u32) -> Result<(), InfiniTimeError> {
         Ok(self.chr(&uuids::CHR_MP_POSITION)?.write(&position.to_be_bytes()).await?)
     }
 
-    pub async fn write_mp_duration(&self, duration: u32) -> Result<()> {
+    pub async fn write_mp_duration(&self, duration: u32) -> Result<(), InfiniTimeError> {
         Ok(self.chr(&uuids::CHR_MP_DURATION)?.write(&duration.to_be_bytes()).await?)
     }
 
-    pub async fn write_mp_playback_speed(&self, speed: f32) -> Result<()> {
+    pub async fn write_mp_playback_speed(&self, speed: f32) -> Result<(), InfiniTimeError> {
         let percentage = (speed * 100.0) as u32;
         Ok(self.chr(&uuids::CHR_MP_SPEED)?.write(&percentage.to_be_bytes()).await?)
     }
 
-    pub async fn write_mp_repeat(&self, repeat: bool) -> Result<()> {
+    pub async fn write_mp_repeat(&self, repeat: bool) -> Result<(), InfiniTimeError> {
         Ok(self.chr(&uuids::CHR_MP_REPEAT)?.write(&[u8::from(repeat)]).await?)
     }
 
-    pub async fn write_mp_shuffle(&self, shuffle: bool) -> Result<()> {
+    pub async fn write_mp_shuffle(&self, shuffle: bool) -> Result<(), InfiniTimeError> {
         Ok(self.chr(&uuids::CHR_MP_SHUFFLE)?.write(&[u8::from(shuffle)]).await?)
     }
 }
\ No newline at end of file
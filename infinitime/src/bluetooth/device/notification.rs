@@ -1,7 +1,12 @@
-use super::{uuids, InfiniTime};
-use anyhow::Result;
+use crate::utils::value_enum;
+use super::{uuids, CharacteristicsMap, InfiniTime, InfiniTimeError, WriteCharacteristic};
+use futures::{Stream, StreamExt};
 
 
+/// InfiniTime keeps at most this many bytes of an alert (title, separator,
+/// content and terminating zero), the rest is cut off
+pub const MAX_ALERT_SIZE: usize = 100;
+
 pub enum Notification<'s> {
     // InfiniTime defines 10 categories, but at the time of writing only 2 of them
     // are implemented in the firmware: simple alert and call. It's not clear
@@ -18,21 +23,126 @@ impl<'s> Notification<'s> {
             Self::Call { title: _ } => 3,
         }
     }
-}
-
 
-impl InfiniTime {
-    pub async fn write_notification<'s>(&self, notification: Notification<'s>) -> Result<()> {
-        let header = &[notification.category(), 1];
-        let message = match notification {
+    /// New Alert characteristic value: category ID, number of new alerts
+    /// (always 1), then zero-separated title and content
+    pub fn serialize(&self) -> Vec<u8> {
+        let header = &[self.category(), 1];
+        match self {
             Notification::Alert { title, content } => {
                 [header, title.as_bytes(), content.as_bytes()].join(&0)
             }
             Notification::Call { title } => {
                 [header, title.as_bytes()].join(&0)
             }
-        };
-        let characteristic = self.chr(&uuids::CHR_NEW_ALERT)?;
-        Ok(characteristic.write(&message).await?)
+        }
+    }
+}
+
+async fn write_new_alert<C: WriteCharacteristic>(
+    characteristics: &CharacteristicsMap<C>, notification: &Notification<'_>
+) -> Result<(), InfiniTimeError> {
+    characteristics.write(&uuids::CHR_NEW_ALERT, &notification.serialize()).await
+}
+
+value_enum! {
+    /// Watch user's response to a call notification
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum CallResponse::<u8> {
+        HangUp = 0x00,
+        Answer = 0x01,
+        Mute = 0x02
+    }
+}
+
+
+impl InfiniTime {
+    /// Write the notification to the New Alert characteristic (0x2A46)
+    /// of the Alert Notification Service
+    pub async fn write_notification<'s>(&self, notification: Notification<'s>) -> Result<(), InfiniTimeError> {
+        write_new_alert(&self.characteristics, &notification).await
+    }
+
+    /// Show a simple alert on the watch. InfiniTime keeps at most
+    /// [`MAX_ALERT_SIZE`] bytes of it, the rest is cut off
+    pub async fn send_notification(&self, title: &str, body: &str) -> Result<(), InfiniTimeError> {
+        self.write_notification(Notification::Alert { title, content: body }).await
+    }
+
+    /// Whether the watch provides the Unread Alert Status characteristic (0x2A45).
+    /// InfiniTime's Alert Notification Service doesn't, so
+    /// [`Self::write_unread_count`] is only useful with firmware that adds it
+    pub fn supports_unread_count(&self) -> bool {
+        self.characteristics.contains(&uuids::CHR_UNREAD_ALERT_STATUS)
+    }
+
+    /// Set the number of unread simple alerts, shown as a badge on the watch
+    pub async fn write_unread_count(&self, count: u8) -> Result<(), InfiniTimeError> {
+        let category = Notification::Alert { title: "", content: "" }.category();
+        let characteristic = self.chr(&uuids::CHR_UNREAD_ALERT_STATUS)?;
+        Ok(characteristic.write(&[category, count]).await?)
     }
-}
\ No newline at end of file
+
+    /// Show host battery level on the watch. InfiniTime has no dedicated
+    /// service for that, nor silent alerts, so it's delivered as a simple
+    /// alert and the watch vibrates
+    pub async fn write_host_battery(&self, level: u8) -> Result<(), InfiniTimeError> {
+        let content = format!("{level}%");
+        let alert = Notification::Alert { title: "Host battery", content: &content };
+        self.write_notification(alert).await
+    }
+
+    pub async fn get_call_response_stream(&self) -> Result<impl Stream<Item = CallResponse>, InfiniTimeError> {
+        let stream = self.chr(&uuids::CHR_NOTIFICATION_EVENT)?.notify().await?;
+        Ok(stream.filter_map(|v| async move {
+            v.first().and_then(|&value| CallResponse::try_from(value).ok())
+        }))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+    use std::{collections::HashMap, sync::Mutex};
+
+    #[derive(Default)]
+    struct MockCharacteristic(Mutex<Vec<Vec<u8>>>);
+
+    impl WriteCharacteristic for MockCharacteristic {
+        async fn write_value(&self, value: &[u8]) -> Result<(), InfiniTimeError> {
+            self.0.lock().unwrap().push(value.to_vec());
+            Ok(())
+        }
+    }
+
+    fn written(notification: Notification) -> Vec<u8> {
+        let mut map = CharacteristicsMap::new(HashMap::from([
+            (uuids::CHR_NEW_ALERT, MockCharacteristic::default()),
+        ]));
+        block_on(write_new_alert(&map, &notification)).unwrap();
+        let mut writes = map.take(&uuids::CHR_NEW_ALERT).unwrap().0.into_inner().unwrap();
+        assert_eq!(writes.len(), 1);
+        writes.remove(0)
+    }
+
+    #[test]
+    fn alert_layout() {
+        let data = written(Notification::Alert { title: "Mail", content: "Hi" });
+        assert_eq!(data, [0, 1, 0, b'M', b'a', b'i', b'l', 0, b'H', b'i']);
+    }
+
+    #[test]
+    fn call_layout() {
+        let data = written(Notification::Call { title: "Alice" });
+        assert_eq!(data, [3, 1, 0, b'A', b'l', b'i', b'c', b'e']);
+    }
+
+    #[test]
+    fn missing_characteristic() {
+        let map = CharacteristicsMap::<MockCharacteristic>::new(HashMap::new());
+        let error = block_on(write_new_alert(&map, &Notification::Call { title: "" })).unwrap_err();
+        assert!(matches!(error, InfiniTimeError::CharacteristicNotFound(_)));
+    }
+}
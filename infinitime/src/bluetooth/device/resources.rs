@@ -1,6 +1,6 @@
 use super::{fs, InfiniTime, ProgressTx, ProgressTxWrapper};
 // use std::sync::mpsc;
-use std::io::{Cursor, Read};
+use std::io::{Cursor, Read, Seek};
 // use futures::{pin_mut, StreamExt};
 use anyhow::{anyhow, ensure, Result};
 use serde::Deserialize;
@@ -27,6 +27,22 @@ struct ObsoleteFile {
 }
 
 
+impl Resources {
+    fn from_archive<R: Read + Seek>(zip: &mut zip::ZipArchive<R>) -> Result<Self> {
+        let mut json = String::new();
+        zip.by_name("resources.json")?.read_to_string(&mut json)?;
+        serde_json::from_str(&json).map_err(|_| anyhow!("Invalid resources.json"))
+    }
+}
+
+/// Firmware version the resources archive is intended for. `resources.json`
+/// doesn't specify it, but release archives are named
+/// `infinitime-resources-<version>.zip`
+pub fn resources_version_from_filename(filename: &str) -> Option<&str> {
+    filename.strip_prefix("infinitime-resources-")?.strip_suffix(".zip")
+}
+
+
 impl InfiniTime {
     pub async fn upload_resources(&self, resources_archive: &[u8], progress_sender: Option<ProgressTx>) -> Result<()>
     {
@@ -34,10 +50,7 @@ impl InfiniTime {
 
         // Parse manifest from the archive
         let mut zip = zip::ZipArchive::new(Cursor::new(resources_archive))?;
-        let mut json = String::new();
-        zip.by_name("resources.json")?.read_to_string(&mut json)?;
-        let manifest: Resources = serde_json::from_str(&json)
-            .map_err(|_| anyhow!("Invalid resources.json"))?;
+        let manifest = Resources::from_archive(&mut zip)?;
 
         // Make dirs
         let files = manifest.resources.iter().map(|r| r.path.as_str());
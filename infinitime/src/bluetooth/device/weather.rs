@@ -0,0 +1,192 @@
+use crate::utils::value_enum;
+use super::{uuids, InfiniTime, InfiniTimeError};
+
+
+/// InfiniTime shows at most this many days of forecast
+pub const MAX_FORECAST_DAYS: usize = 5;
+
+/// Location name field is zero-padded to this size
+const LOCATION_SIZE: usize = 32;
+const FORECAST_DAY_SIZE: usize = 5;
+
+// Message types of the simple weather service
+const MSG_CURRENT_WEATHER: u8 = 0;
+const MSG_FORECAST: u8 = 1;
+const MSG_VERSION: u8 = 0;
+
+value_enum! {
+    /// Condition icons of InfiniTime simple weather service
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum WeatherIcon::<u8> {
+        Sun = 0,
+        FewClouds = 1,
+        Clouds = 2,
+        HeavyClouds = 3,
+        CloudsAndRain = 4,
+        Rain = 5,
+        Thunderstorm = 6,
+        Snow = 7,
+        Mist = 8
+    }
+}
+
+impl WeatherIcon {
+    /// Icon for WMO weather interpretation code, which most forecast providers use
+    pub fn from_wmo_code(code: u8) -> Self {
+        match code {
+            0 => Self::Sun,
+            1 => Self::FewClouds,
+            2 => Self::Clouds,
+            3 => Self::HeavyClouds,
+            45 | 48 => Self::Mist,
+            // Drizzle and rain showers
+            51..=57 | 80..=82 => Self::CloudsAndRain,
+            61..=67 => Self::Rain,
+            71..=77 | 85 | 86 => Self::Snow,
+            95..=99 => Self::Thunderstorm,
+            _ => Self::Clouds,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurrentWeather {
+    /// Local time of the observation, in seconds since 1970-01-01 00:00
+    pub timestamp: i64,
+    /// Temperatures in °C
+    pub temperature: f32,
+    pub min_temperature: f32,
+    pub max_temperature: f32,
+    /// Truncated to 32 bytes
+    pub location: String,
+    pub icon: WeatherIcon,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DayForecast {
+    /// Temperatures in °C
+    pub min_temperature: f32,
+    pub max_temperature: f32,
+    pub icon: WeatherIcon,
+}
+
+fn header(message_type: u8, timestamp: i64) -> Vec<u8> {
+    let mut packet = vec![message_type, MSG_VERSION];
+    packet.extend_from_slice(&(timestamp.max(0) as u64).to_le_bytes());
+    packet
+}
+
+/// Temperature in hundredths of °C
+fn encode_temperature(celsius: f32) -> [u8; 2] {
+    let value = (celsius * 100.0).round().clamp(i16::MIN as f32, i16::MAX as f32);
+    (value as i16).to_le_bytes()
+}
+
+fn current_weather_packet(weather: &CurrentWeather) -> Vec<u8> {
+    let mut packet = header(MSG_CURRENT_WEATHER, weather.timestamp);
+    packet.extend_from_slice(&encode_temperature(weather.temperature));
+    packet.extend_from_slice(&encode_temperature(weather.min_temperature));
+    packet.extend_from_slice(&encode_temperature(weather.max_temperature));
+    let mut location = [0; LOCATION_SIZE];
+    let mut len = weather.location.len().min(LOCATION_SIZE);
+    while !weather.location.is_char_boundary(len) {
+        len -= 1;
+    }
+    location[..len].copy_from_slice(&weather.location.as_bytes()[..len]);
+    packet.extend_from_slice(&location);
+    packet.push(weather.icon as u8);
+    packet
+}
+
+fn forecast_packet(timestamp: i64, days: &[DayForecast]) -> Vec<u8> {
+    let days = &days[..days.len().min(MAX_FORECAST_DAYS)];
+    let mut packet = header(MSG_FORECAST, timestamp);
+    packet.push(days.len() as u8);
+    for day in days {
+        packet.extend_from_slice(&encode_temperature(day.min_temperature));
+        packet.extend_from_slice(&encode_temperature(day.max_temperature));
+        packet.push(day.icon as u8);
+    }
+    // Unused days are still expected to be there
+    packet.resize(packet.len() + (MAX_FORECAST_DAYS - days.len()) * FORECAST_DAY_SIZE, 0);
+    packet
+}
+
+
+impl InfiniTime {
+    pub async fn write_current_weather(&self, weather: &CurrentWeather) -> Result<(), InfiniTimeError> {
+        let characteristic = self.chr(&uuids::CHR_WEATHER)?;
+        Ok(characteristic.write(&current_weather_packet(weather)).await?)
+    }
+
+    /// Write forecast for up to `MAX_FORECAST_DAYS` days starting with today,
+    /// `timestamp` is local time in seconds like for the current weather
+    pub async fn write_forecast(&self, timestamp: i64, days: &[DayForecast]) -> Result<(), InfiniTimeError> {
+        let characteristic = self.chr(&uuids::CHR_WEATHER)?;
+        Ok(characteristic.write(&forecast_packet(timestamp, days)).await?)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_weather() {
+        let weather = CurrentWeather {
+            timestamp: 0x0102,
+            temperature: -1.5,
+            min_temperature: -3.0,
+            max_temperature: 2.25,
+            location: "Kyiv".into(),
+            icon: WeatherIcon::Snow,
+        };
+        let packet = current_weather_packet(&weather);
+        assert_eq!(packet.len(), 49);
+        assert_eq!(&packet[..10], &[0, 0, 0x02, 0x01, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&packet[10..16], &[0x6a, 0xff, 0xd4, 0xfe, 0xe1, 0x00]);
+        assert_eq!(&packet[16..21], b"Kyiv\0");
+        assert_eq!(packet[48], 7);
+    }
+
+    #[test]
+    fn long_location() {
+        let weather = CurrentWeather {
+            timestamp: 0,
+            temperature: 0.0,
+            min_temperature: 0.0,
+            max_temperature: 0.0,
+            location: "Ї".repeat(20),
+            icon: WeatherIcon::Sun,
+        };
+        let packet = current_weather_packet(&weather);
+        assert_eq!(packet.len(), 49);
+        // Multi-byte characters are not split
+        assert_eq!(&packet[16..48], ["Ї".repeat(16).as_bytes()].concat().as_slice());
+    }
+
+    #[test]
+    fn forecast() {
+        let day = DayForecast { min_temperature: 1.0, max_temperature: 2.0, icon: WeatherIcon::Rain };
+        let packet = forecast_packet(0, &[day; 2]);
+        assert_eq!(packet.len(), 36);
+        assert_eq!(packet[0], 1);
+        assert_eq!(packet[10], 2);
+        assert_eq!(&packet[11..16], &[100, 0, 200, 0, 5]);
+        assert!(packet[21..].iter().all(|b| *b == 0));
+
+        let packet = forecast_packet(0, &[day; 7]);
+        assert_eq!(packet.len(), 36);
+        assert_eq!(packet[10], 5);
+    }
+
+    #[test]
+    fn wmo_codes() {
+        assert_eq!(WeatherIcon::from_wmo_code(0), WeatherIcon::Sun);
+        assert_eq!(WeatherIcon::from_wmo_code(48), WeatherIcon::Mist);
+        assert_eq!(WeatherIcon::from_wmo_code(81), WeatherIcon::CloudsAndRain);
+        assert_eq!(WeatherIcon::from_wmo_code(75), WeatherIcon::Snow);
+        assert_eq!(WeatherIcon::from_wmo_code(96), WeatherIcon::Thunderstorm);
+    }
+}
@@ -0,0 +1,149 @@
+use super::{InfiniTime, InfiniTimeError};
+use bluer::{Adapter, AdapterEvent, Address};
+use futures::{stream::BoxStream, StreamExt};
+use std::{future::Future, time::Duration};
+use tokio::time;
+
+
+/// Device found by [`scan_for`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredDevice {
+    pub address: Address,
+    pub name: Option<String>,
+    pub rssi: Option<i16>,
+}
+
+/// Adapter operations needed for scanning. Abstracts over `bluer` adapter,
+/// so that scanning can be tested without hardware
+pub trait ScanAdapter {
+    /// Start discovery, yielding addresses of the found devices
+    fn discover(&self) -> impl Future<Output = Result<BoxStream<'_, Address>, InfiniTimeError>> + Send;
+    fn device_info(&self, address: Address) -> impl Future<Output = Option<DiscoveredDevice>> + Send;
+}
+
+impl ScanAdapter for Adapter {
+    async fn discover(&self) -> Result<BoxStream<'_, Address>, InfiniTimeError> {
+        let events = self.discover_devices().await?;
+        Ok(events.filter_map(|event| async move {
+            match event {
+                AdapterEvent::DeviceAdded(address) => Some(address),
+                _ => None,
+            }
+        }).boxed())
+    }
+
+    async fn device_info(&self, address: Address) -> Option<DiscoveredDevice> {
+        let device = self.device(address).ok()?;
+        Some(DiscoveredDevice {
+            address,
+            name: device.name().await.ok().flatten(),
+            rssi: device.rssi().await.ok().flatten(),
+        })
+    }
+}
+
+
+/// Discover devices for `duration` and return the ones which pass `name_filter`
+/// (see [`InfiniTime::check_device`]), each reported once
+pub async fn scan_for<A: ScanAdapter>(
+    adapter: &A,
+    name_filter: &str,
+    duration: Duration,
+) -> Result<Vec<DiscoveredDevice>, InfiniTimeError> {
+    let mut found = Vec::<DiscoveredDevice>::new();
+    let mut addresses = adapter.discover().await?;
+    let collect = async {
+        while let Some(address) = addresses.next().await {
+            if found.iter().any(|d| d.address == address) {
+                continue;
+            }
+            if let Some(info) = adapter.device_info(address).await {
+                if InfiniTime::check_name(info.name.as_deref(), name_filter) {
+                    found.push(info);
+                }
+            }
+        }
+    };
+    // Discovery stream doesn't end on its own
+    _ = time::timeout(duration, collect).await;
+    Ok(found)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::stream;
+
+    struct MockAdapter {
+        devices: Vec<DiscoveredDevice>,
+        // Keep the stream open after all devices are reported, like a real adapter
+        endless: bool,
+    }
+
+    impl ScanAdapter for MockAdapter {
+        async fn discover(&self) -> Result<BoxStream<'_, Address>, InfiniTimeError> {
+            let addresses = stream::iter(self.devices.iter().map(|d| d.address));
+            Ok(if self.endless {
+                addresses.chain(stream::pending()).boxed()
+            } else {
+                addresses.boxed()
+            })
+        }
+
+        async fn device_info(&self, address: Address) -> Option<DiscoveredDevice> {
+            self.devices.iter().find(|d| d.address == address).cloned()
+        }
+    }
+
+    fn device(last_byte: u8, name: Option<&str>) -> DiscoveredDevice {
+        DiscoveredDevice {
+            address: Address::new([0xAA, 0xBB, 0xCC, 0xDD, 0xEE, last_byte]),
+            name: name.map(String::from),
+            rssi: Some(-60),
+        }
+    }
+
+    fn scan(adapter: &MockAdapter, name_filter: &str) -> Vec<DiscoveredDevice> {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+            .block_on(scan_for(adapter, name_filter, Duration::from_millis(100)))
+            .unwrap()
+    }
+
+    #[test]
+    fn filters_by_name() {
+        let adapter = MockAdapter {
+            devices: vec![
+                device(1, Some("InfiniTime")),
+                device(2, Some("Headphones")),
+                device(3, None),
+                device(4, Some("InfiniTime-2")),
+            ],
+            endless: false,
+        };
+        let found = scan(&adapter, "InfiniTime");
+        assert_eq!(found, vec![adapter.devices[0].clone(), adapter.devices[3].clone()]);
+        assert_eq!(scan(&adapter, "").len(), 4);
+    }
+
+    #[test]
+    fn reports_each_device_once() {
+        let adapter = MockAdapter {
+            devices: vec![device(1, Some("InfiniTime")), device(1, Some("InfiniTime"))],
+            endless: false,
+        };
+        assert_eq!(scan(&adapter, "InfiniTime").len(), 1);
+    }
+
+    #[test]
+    fn stops_after_duration() {
+        let adapter = MockAdapter {
+            devices: vec![device(1, Some("InfiniTime"))],
+            endless: true,
+        };
+        assert_eq!(scan(&adapter, "InfiniTime"), vec![adapter.devices[0].clone()]);
+    }
+}
@@ -1,17 +1,23 @@
-use super::uuids;
+use super::{device::{current_time::encode_current_time, immediate_alert::AlertLevel}, uuids};
 use futures::FutureExt;
 use bluer::{
     gatt::local::{
-        Application, ApplicationHandle, Characteristic, CharacteristicRead, Service,
+        Application, ApplicationHandle, Characteristic, CharacteristicRead, CharacteristicWrite,
+        CharacteristicWriteMethod, ReqError, Service,
     },
     Adapter, Result,
 };
-use chrono::{Local, Datelike, Timelike};
+use chrono::Local;
+use tokio::sync::mpsc;
 
-pub async fn start_gatt_services(adapter: &Adapter) -> Result<ApplicationHandle> {
+/// Serve the services that the watch uses on the host. Alert levels that
+/// the watch writes to find the host (e.g. from the "Find my phone" app)
+/// are sent to `find_host`
+pub async fn start_gatt_services(adapter: &Adapter, find_host: mpsc::UnboundedSender<AlertLevel>) -> Result<ApplicationHandle> {
     let app = Application {
         services: vec![
-            current_time_service()
+            current_time_service(),
+            immediate_alert_service(find_host),
         ],
         ..Default::default()
     };
@@ -31,20 +37,7 @@ fn current_time_service() -> Service {
                 fun: Box::new(move |req| {
                     async move {
                         log::debug!("{:?}", &req);
-                        let now = Local::now();
-                        let year = (now.year() as u16).to_le_bytes();
-                        Ok(vec![
-                            year[0],
-                            year[1],
-                            now.month() as u8,
-                            now.day() as u8,
-                            now.hour() as u8,
-                            now.minute() as u8,
-                            now.second() as u8,
-                            now.weekday().number_from_monday() as u8,
-                            0x00,   // Fractions256
-                            0x00,   // Adjust reason
-                        ])
+                        Ok(encode_current_time(&Local::now().naive_local(), 0x00))
                     }.boxed()
                 }),
                 ..Default::default()
@@ -55,4 +48,32 @@ fn current_time_service() -> Service {
         }],
         ..Default::default()
     }
-}
\ No newline at end of file
+}
+
+fn immediate_alert_service(alerts: mpsc::UnboundedSender<AlertLevel>) -> Service {
+    Service {
+        uuid: uuids::SRV_IMMEDIATE_ALERT,
+        primary: true,
+        characteristics: vec![Characteristic {
+            uuid: uuids::CHR_ALERT_LEVEL,
+            write: Some(CharacteristicWrite {
+                write_without_response: true,
+                method: CharacteristicWriteMethod::Fun(Box::new(move |value, req| {
+                    log::debug!("{:?}: {:?}", &req, &value);
+                    let level = value.first().map(|v| AlertLevel::try_from(*v));
+                    let result = match level {
+                        Some(Ok(level)) => {
+                            _ = alerts.send(level);
+                            Ok(())
+                        }
+                        _ => Err(ReqError::InvalidValueLength),
+                    };
+                    async move { result }.boxed()
+                })),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
@@ -0,0 +1,91 @@
+use super::{retry_with_timeout, HeartRate, InfiniTime, InfiniTimeError};
+use bluer::{Address, Device};
+use std::sync::Arc;
+
+
+/// Basic watch state read in one go, see [`snapshot`]
+#[derive(Debug)]
+pub struct DeviceSnapshot {
+    pub address: Address,
+    pub alias: Option<String>,
+    pub firmware_version: Option<String>,
+    pub battery_level: Option<u8>,
+    pub heart_rate: Option<HeartRate>,
+    pub step_count: Option<u32>,
+    /// Reads that failed, by field name. The corresponding fields are `None`
+    pub errors: Vec<(&'static str, InfiniTimeError)>,
+}
+
+impl DeviceSnapshot {
+    /// Whether all the values were read
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+
+/// Connect to the device if needed, and read its alias, firmware version,
+/// battery level, heart rate and step count concurrently. Failure of an
+/// individual read doesn't fail the snapshot, it's recorded in
+/// [`DeviceSnapshot::errors`] instead. Disconnects afterwards if
+/// `disconnect` is set.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// use std::sync::Arc;
+///
+/// let session = infinitime::bluer::Session::new().await?;
+/// let adapter = session.default_adapter().await?;
+/// let device = adapter.device("AA:BB:CC:DD:EE:FF".parse()?)?;
+/// let snapshot = infinitime::snapshot(Arc::new(device), true).await?;
+/// if let Some(level) = snapshot.battery_level {
+///     println!("Battery: {level}%");
+/// }
+/// for (field, error) in &snapshot.errors {
+///     eprintln!("Failed to read {field}: {error}");
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub async fn snapshot(device: Arc<Device>, disconnect: bool) -> Result<DeviceSnapshot, InfiniTimeError> {
+    if !device.is_connected().await? {
+        device.connect().await?;
+    }
+    let result = read_snapshot(device.clone()).await;
+    if disconnect {
+        if let Err(error) = device.disconnect().await {
+            log::warn!("Failed to disconnect: {error}");
+        }
+    }
+    result
+}
+
+async fn read_snapshot(device: Arc<Device>) -> Result<DeviceSnapshot, InfiniTimeError> {
+    let address = device.address();
+    let infinitime = InfiniTime::new(device).await?;
+    let (alias, firmware_version, battery_level, heart_rate, step_count) = futures::join!(
+        async { infinitime.device().alias().await.map_err(InfiniTimeError::from) },
+        retry_with_timeout(|| infinitime.firmware_version()),
+        retry_with_timeout(|| infinitime.read_battery_level()),
+        retry_with_timeout(|| infinitime.read_heart_rate()),
+        retry_with_timeout(|| infinitime.read_step_count()),
+    );
+    let mut errors = Vec::new();
+    Ok(DeviceSnapshot {
+        address,
+        alias: check(&mut errors, "alias", alias),
+        firmware_version: check(&mut errors, "firmware version", firmware_version),
+        battery_level: check(&mut errors, "battery level", battery_level),
+        heart_rate: check(&mut errors, "heart rate", heart_rate),
+        step_count: check(&mut errors, "step count", step_count),
+        errors,
+    })
+}
+
+fn check<T>(
+    errors: &mut Vec<(&'static str, InfiniTimeError)>,
+    field: &'static str,
+    result: Result<T, InfiniTimeError>,
+) -> Option<T> {
+    result.map_err(|error| errors.push((field, error))).ok()
+}
@@ -1,15 +1,21 @@
 use uuid::{uuid, Uuid};
 
 pub const SRV_CURRENT_TIME: Uuid = uuid!("00001805-0000-1000-8000-00805f9b34fb");
+pub const SRV_IMMEDIATE_ALERT: Uuid = uuid!("00001802-0000-1000-8000-00805f9b34fb");
 
 pub const CHR_CURRENT_TIME: Uuid = uuid!("00002a2b-0000-1000-8000-00805f9b34fb");
+pub const CHR_ALERT_LEVEL: Uuid = uuid!("00002a06-0000-1000-8000-00805f9b34fb");
 
 pub const CHR_BATTERY_LEVEL: Uuid = uuid!("00002a19-0000-1000-8000-00805f9b34fb");
+pub const CHR_MODEL_NUMBER: Uuid = uuid!("00002a24-0000-1000-8000-00805f9b34fb");
 pub const CHR_FIRMWARE_REVISION: Uuid = uuid!("00002a26-0000-1000-8000-00805f9b34fb");
+pub const CHR_HARDWARE_REVISION: Uuid = uuid!("00002a27-0000-1000-8000-00805f9b34fb");
+pub const CHR_MANUFACTURER_NAME: Uuid = uuid!("00002a29-0000-1000-8000-00805f9b34fb");
 pub const CHR_HEART_RATE: Uuid = uuid!("00002a37-0000-1000-8000-00805f9b34fb");
 
+pub const CHR_UNREAD_ALERT_STATUS: Uuid = uuid!("00002a45-0000-1000-8000-00805f9b34fb");
 pub const CHR_NEW_ALERT: Uuid = uuid!("00002a46-0000-1000-8000-00805f9b34fb");
-pub const _CHR_NOTIFICATION_EVENT: Uuid = uuid!("00020001-78fc-48fe-8e23-433b3a1942d0");
+pub const CHR_NOTIFICATION_EVENT: Uuid = uuid!("00020001-78fc-48fe-8e23-433b3a1942d0");
 
 pub const CHR_FS_VERSION: Uuid = uuid!("adaf0100-4669-6c65-5472-616e73666572");
 pub const CHR_FS_TRANSFER: Uuid = uuid!("adaf0200-4669-6c65-5472-616e73666572");
@@ -29,4 +35,6 @@ pub const CHR_MP_REPEAT: Uuid = uuid!("0000000b-78fc-48fe-8e23-433b3a1942d0");
 pub const CHR_MP_SHUFFLE: Uuid = uuid!("0000000c-78fc-48fe-8e23-433b3a1942d0");
 
 pub const CHR_STEP_COUNT: Uuid = uuid!("00030001-78fc-48fe-8e23-433b3a1942d0");
-pub const _CHR_MOTION: Uuid = uuid!("00030002-78fc-48fe-8e23-433b3a1942d0");
\ No newline at end of file
+pub const CHR_MOTION: Uuid = uuid!("00030002-78fc-48fe-8e23-433b3a1942d0");
+
+pub const CHR_WEATHER: Uuid = uuid!("00050001-78fc-48fe-8e23-433b3a1942d0");
\ No newline at end of file
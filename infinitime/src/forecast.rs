@@ -0,0 +1,182 @@
+use crate::bt::{self, CurrentWeather, DayForecast, WeatherIcon};
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::time::Duration;
+use tokio::time;
+
+/// How often to refresh the weather on the watch
+pub const UPDATE_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+const OPEN_METEO_URL: &str = "https://api.open-meteo.com/v1/forecast";
+
+
+/// Weather forecast source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Provider {
+    /// Free, doesn't require an API key
+    OpenMeteo,
+}
+
+impl Provider {
+    pub const ALL: [Provider; 1] = [Provider::OpenMeteo];
+
+    /// Stable identifier, e.g. for settings
+    pub fn name(&self) -> &'static str {
+        match self {
+            Provider::OpenMeteo => "open-meteo",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    /// Shown on the watch
+    pub name: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Forecast {
+    pub current: CurrentWeather,
+    /// Starting with today
+    pub days: Vec<DayForecast>,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoResponse {
+    current: OpenMeteoCurrent,
+    daily: OpenMeteoDaily,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoCurrent {
+    temperature_2m: f32,
+    weather_code: u8,
+}
+
+#[derive(Deserialize, Debug)]
+struct OpenMeteoDaily {
+    weather_code: Vec<u8>,
+    temperature_2m_min: Vec<f32>,
+    temperature_2m_max: Vec<f32>,
+}
+
+impl OpenMeteoResponse {
+    fn into_forecast(self, location: &Location, timestamp: i64) -> Result<Forecast> {
+        let daily = self.daily;
+        let days = daily.weather_code.iter()
+            .zip(&daily.temperature_2m_min)
+            .zip(&daily.temperature_2m_max)
+            .map(|((code, min), max)| DayForecast {
+                min_temperature: *min,
+                max_temperature: *max,
+                icon: WeatherIcon::from_wmo_code(*code),
+            })
+            .collect::<Vec<_>>();
+        let today = days.first().ok_or(anyhow!("Forecast has no days"))?;
+        let current = CurrentWeather {
+            timestamp,
+            temperature: self.current.temperature_2m,
+            min_temperature: today.min_temperature,
+            max_temperature: today.max_temperature,
+            location: location.name.clone(),
+            icon: WeatherIcon::from_wmo_code(self.current.weather_code),
+        };
+        Ok(Forecast { current, days })
+    }
+}
+
+
+pub async fn fetch(provider: Provider, location: &Location) -> Result<Forecast> {
+    // The watch compares the timestamp with its clock, which is in local time
+    let timestamp = chrono::Local::now().naive_local().and_utc().timestamp();
+    match provider {
+        Provider::OpenMeteo => {
+            let response = reqwest::Client::new()
+                .get(OPEN_METEO_URL)
+                .query(&[
+                    ("latitude", location.latitude.to_string()),
+                    ("longitude", location.longitude.to_string()),
+                    ("current", "temperature_2m,weather_code".to_string()),
+                    ("daily", "weather_code,temperature_2m_min,temperature_2m_max".to_string()),
+                    ("forecast_days", bt::MAX_FORECAST_DAYS.to_string()),
+                    ("timezone", "auto".to_string()),
+                ])
+                .send().await?
+                .error_for_status()?
+                .json::<OpenMeteoResponse>().await?;
+            response.into_forecast(location, timestamp)
+        }
+    }
+}
+
+/// Periodically send the weather to the watch. Forecast is sent as well,
+/// unless the watch rejects it, then only the current weather is sent.
+/// Failures are logged and retried on the next update, the session only
+/// ends when its task is aborted
+pub async fn run_weather_session(infinitime: &bt::InfiniTime, provider: Provider, location: &Location) {
+    let mut forecast_supported = true;
+    loop {
+        match fetch(provider, location).await {
+            Ok(forecast) => match infinitime.write_current_weather(&forecast.current).await {
+                Ok(()) => {
+                    if forecast_supported {
+                        let timestamp = forecast.current.timestamp;
+                        if let Err(error) = infinitime.write_forecast(timestamp, &forecast.days).await {
+                            log::warn!("Forecast is rejected, sending current weather only: {error}");
+                            forecast_supported = false;
+                        }
+                    }
+                    log::info!("Weather sent: {} °C", forecast.current.temperature);
+                }
+                Err(error) => log::error!("Failed to send weather: {error}"),
+            },
+            Err(error) => log::error!("Failed to fetch weather: {error}"),
+        }
+        time::sleep(UPDATE_INTERVAL).await;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn open_meteo_response() {
+        let json = r#"{
+            "latitude": 50.45, "longitude": 30.52,
+            "current": {"time": "2024-05-01T12:00", "temperature_2m": 18.4, "weather_code": 2},
+            "daily": {
+                "time": ["2024-05-01", "2024-05-02"],
+                "weather_code": [61, 0],
+                "temperature_2m_min": [9.1, 10.0],
+                "temperature_2m_max": [19.5, 22.3]
+            }
+        }"#;
+        let location = Location { name: "Kyiv".into(), latitude: 50.45, longitude: 30.52 };
+        let response = serde_json::from_str::<OpenMeteoResponse>(json).unwrap();
+        let forecast = response.into_forecast(&location, 42).unwrap();
+        assert_eq!(forecast.current.timestamp, 42);
+        assert_eq!(forecast.current.location, "Kyiv");
+        assert_eq!(forecast.current.icon, WeatherIcon::Clouds);
+        assert_eq!(forecast.current.min_temperature, 9.1);
+        assert_eq!(forecast.current.max_temperature, 19.5);
+        assert_eq!(forecast.days.len(), 2);
+        assert_eq!(forecast.days[0].icon, WeatherIcon::Rain);
+        assert_eq!(forecast.days[1].icon, WeatherIcon::Sun);
+    }
+
+    #[test]
+    fn provider_names() {
+        for provider in Provider::ALL {
+            assert_eq!(Provider::from_name(provider.name()), Some(provider));
+        }
+        assert_eq!(Provider::from_name("none"), None);
+    }
+}
@@ -1,2 +1,3 @@
 pub mod mpris;
 pub mod notifications;
+pub mod upower;
@@ -3,14 +3,42 @@ use anyhow::Result;
 use futures::{pin_mut, stream, Stream, StreamExt};
 use mpris2_zbus::{
     metadata::Metadata,
-    player::{LoopStatus, PlaybackStatus, Player},
+    player::{LoopStatus, Player},
 };
-use std::str::FromStr;
+use std::{ops::RangeInclusive, str::FromStr, time::Duration};
+use tokio::{sync::mpsc, time::{self, Instant}};
 use zbus::{fdo::DBusProxy, names::OwnedBusName, Connection};
 
-pub use mpris2_zbus::media_player::MediaPlayer;
+pub use mpris2_zbus::{media_player::MediaPlayer, player::PlaybackStatus};
 
-const VOLUME_STEP: f64 = 0.1;
+/// Volume change per watch button press, as a fraction of the full volume
+pub const DEFAULT_VOLUME_STEP: f64 = 0.1;
+pub const VOLUME_STEP_RANGE: RangeInclusive<f64> = 0.01..=0.5;
+/// Minimal interval between playback position writes to the watch
+const POSITION_WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// High-level control session events, for UI feedback
+#[derive(Debug)]
+pub enum ControlSessionEvent {
+    TrackChanged { title: Option<String>, artist: Option<String> },
+    PlaybackChanged(PlaybackStatus),
+}
+
+impl ControlSessionEvent {
+    fn track(metadata: &Metadata) -> Self {
+        Self::TrackChanged {
+            title: metadata.title(),
+            artist: metadata.artists().and_then(|a| a.into_iter().next()),
+        }
+    }
+}
+
+pub type ControlSessionEventTx = mpsc::UnboundedSender<ControlSessionEvent>;
+pub type ControlSessionEventRx = mpsc::UnboundedReceiver<ControlSessionEvent>;
+
+pub fn control_session_channel() -> (ControlSessionEventTx, ControlSessionEventRx) {
+    mpsc::unbounded_channel()
+}
 
 #[derive(Debug)]
 pub enum PlayersListEvent {
@@ -104,10 +132,22 @@ pub async fn update_player_info(player: &Player, infinitime: &bt::InfiniTime) ->
     Ok(())
 }
 
+/// Forward player state to the watch and watch's control events to the player.
+/// If `events` is provided, initial track and playback status, as well as their
+/// changes, are reported there. `volume_step` is clamped to [`VOLUME_STEP_RANGE`]
 pub async fn run_control_session(
     media_player: &MediaPlayer,
     infinitime: &bt::InfiniTime,
+    volume_step: f64,
+    events: Option<ControlSessionEventTx>,
 ) -> Result<()> {
+    let volume_step = volume_step.clamp(*VOLUME_STEP_RANGE.start(), *VOLUME_STEP_RANGE.end());
+    let emit = |event: ControlSessionEvent| {
+        if let Some(tx) = &events {
+            // Nobody is listening anymore, that's fine
+            _ = tx.send(event);
+        }
+    };
     let player = media_player.player().await?;
 
     // Obtain even streams
@@ -132,9 +172,20 @@ pub async fn run_control_session(
     let mut can_pause = player.can_pause().await?;
     let mut can_play = player.can_play().await?;
 
+    // Position updates can be very frequent, so they are throttled,
+    // keeping only the latest value until the next write is allowed
+    let mut pending_position: Option<u32> = None;
+    let mut last_position_write = Instant::now();
+
     // Send initial player info to the watch
     log::debug!("Sending player info to the watch...");
     update_player_info(&player, infinitime).await?;
+    if let Ok(status) = player.playback_status().await {
+        emit(ControlSessionEvent::PlaybackChanged(status));
+    }
+    if let Ok(metadata) = player.metadata().await {
+        emit(ControlSessionEvent::track(&metadata));
+    }
 
     // Process events
     log::info!(
@@ -146,7 +197,11 @@ pub async fn run_control_session(
             Some(event) = control_event_stream.next() => {
                 log::debug!("Control event: {:?}", event);
                 match event {
-                    bt::MediaPlayerEvent::AppOpenned => (),
+                    bt::MediaPlayerEvent::AppOpenned => {
+                        update_player_info(&player, infinitime).await?;
+                        pending_position = None;
+                        last_position_write = Instant::now();
+                    }
                     bt::MediaPlayerEvent::Play => if can_play {
                         player.play().await?;
                     }
@@ -161,11 +216,11 @@ pub async fn run_control_session(
                     }
                     bt::MediaPlayerEvent::VolumeUp => {
                         let volume = player.volume().await?;
-                        player.set_volume(1.0f64.min(volume + VOLUME_STEP)).await?;
+                        player.set_volume(1.0f64.min(volume + volume_step)).await?;
                     }
                     bt::MediaPlayerEvent::VolumeDown => {
                         let volume = player.volume().await?;
-                        player.set_volume(0.0f64.max(volume - VOLUME_STEP)).await?;
+                        player.set_volume(0.0f64.max(volume - volume_step)).await?;
                     }
                 }
             }
@@ -174,6 +229,17 @@ pub async fn run_control_session(
                 log::debug!("Playback status: {:?}", status);
                 let is_playing = status == PlaybackStatus::Playing;
                 infinitime.write_mp_playback_status(is_playing).await?;
+                emit(ControlSessionEvent::PlaybackChanged(status));
+                // Don't let throttling delay position sync on play/pause
+                let position = match pending_position.take() {
+                    Some(position) => Some(position),
+                    None => player.position().await.ok()
+                        .map(|p| p.unwrap_or_default().as_seconds_f32() as u32),
+                };
+                if let Some(position) = position {
+                    infinitime.write_mp_position(position).await?;
+                    last_position_write = Instant::now();
+                }
             }
             Some(property) = loop_status_stream.next() => {
                 let status = LoopStatus::from_str(&property.get().await?)?;
@@ -189,7 +255,19 @@ pub async fn run_control_session(
             Some(property) = position_stream.next() => {
                 let position = (property.get().await? / 1_000_000) as u32;
                 log::debug!("Position: {:?}", position);
-                infinitime.write_mp_position(position).await?;
+                if last_position_write.elapsed() >= POSITION_WRITE_INTERVAL {
+                    infinitime.write_mp_position(position).await?;
+                    last_position_write = Instant::now();
+                    pending_position = None;
+                } else {
+                    pending_position = Some(position);
+                }
+            }
+            _ = time::sleep_until(last_position_write + POSITION_WRITE_INTERVAL), if pending_position.is_some() => {
+                if let Some(position) = pending_position.take() {
+                    infinitime.write_mp_position(position).await?;
+                    last_position_write = Instant::now();
+                }
             }
             Some(property) = rate_stream.next() => {
                 let rate = property.get().await? as f32;
@@ -200,6 +278,7 @@ pub async fn run_control_session(
                 let metadata = Metadata::from(property.get().await?);
                 log::debug!("Metadata: {:?}", metadata);
                 update_track_metadata(&metadata, infinitime).await?;
+                emit(ControlSessionEvent::track(&metadata));
             }
             Some(property) = can_go_next_stream.next() => {
                 can_go_next = property.get().await?;
@@ -1,7 +1,8 @@
 use anyhow::Result;
-use futures::TryStreamExt;
+use futures::{pin_mut, stream::{self, BoxStream}, StreamExt};
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::{borrow::Cow, collections::HashMap, time::Duration};
+use tokio::time::{self, Instant};
 use zbus::{
     match_rule::MatchRule,
     zvariant::{Type, Value},
@@ -9,6 +10,136 @@ use zbus::{
 
 use crate::bt;
 
+/// Default format of the notification title on the watch
+pub const DEFAULT_FORMAT: &str = "{app}: {summary}";
+
+/// InfiniTime doesn't report when alerts are dismissed on the watch,
+/// so the unread count is cleared after this long without new ones
+const UNREAD_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+#[zbus::proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+}
+
+#[zbus::proxy(
+    interface = "org.gnome.ScreenSaver",
+    default_service = "org.gnome.ScreenSaver",
+    default_path = "/org/gnome/ScreenSaver"
+)]
+trait GnomeScreenSaver {
+    fn get_active(&self) -> zbus::Result<bool>;
+    #[zbus(signal)]
+    fn active_changed(&self, new_value: bool) -> zbus::Result<()>;
+}
+
+#[zbus::proxy(
+    interface = "org.freedesktop.ScreenSaver",
+    default_service = "org.freedesktop.ScreenSaver",
+    default_path = "/org/freedesktop/ScreenSaver"
+)]
+trait ScreenSaver {
+    fn get_active(&self) -> zbus::Result<bool>;
+    #[zbus(signal)]
+    fn active_changed(&self, new_value: bool) -> zbus::Result<()>;
+}
+
+/// Whether notifications are forwarded depending on the host screen lock state
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LockCondition {
+    #[default]
+    Always,
+    WhenLocked,
+    WhenUnlocked,
+}
+
+impl LockCondition {
+    pub const ALL: [Self; 3] = [Self::Always, Self::WhenLocked, Self::WhenUnlocked];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Always => "always",
+            Self::WhenLocked => "when-locked",
+            Self::WhenUnlocked => "when-unlocked",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    fn allows(&self, locked: bool) -> bool {
+        match self {
+            Self::Always => true,
+            Self::WhenLocked => locked,
+            Self::WhenUnlocked => !locked,
+        }
+    }
+}
+
+/// What and how to forward during a notification session
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionConfig {
+    /// Forward notifications with [`Urgency::Low`]
+    pub forward_low_urgency: bool,
+    pub lock_condition: LockCondition,
+    /// Title template, see [`format_title`]
+    pub format: String,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        Self {
+            forward_low_urgency: true,
+            lock_condition: LockCondition::default(),
+            format: DEFAULT_FORMAT.to_string(),
+        }
+    }
+}
+
+/// Urgency level of a desktop notification. InfiniTime has no alert priorities,
+/// so it's only used to filter out notifications which are not worth forwarding
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Urgency {
+    Low,
+    #[default]
+    Normal,
+    Critical,
+}
+
+impl Urgency {
+    /// Parse `urgency` hint. Missing or invalid one means normal urgency
+    fn from_hints(hints: &HashMap<&str, Value<'_>>) -> Self {
+        // The spec defines it as a byte, but not every sender follows it
+        let level = match hints.get("urgency") {
+            Some(Value::U8(level)) => Some(*level as i64),
+            Some(Value::I32(level)) => Some(*level as i64),
+            Some(Value::U32(level)) => Some(*level as i64),
+            _ => None,
+        };
+        match level {
+            Some(0) => Self::Low,
+            Some(2) => Self::Critical,
+            _ => Self::Normal,
+        }
+    }
+}
+
 #[allow(unused)]
 #[derive(Debug, Deserialize, Type)]
 struct DesktopNotification<'s> {
@@ -22,7 +153,8 @@ struct DesktopNotification<'s> {
     expire_timeout: i32,
 }
 
-pub async fn run_notification_session(infinitime: &bt::InfiniTime) -> Result<()> {
+/// Open a session bus connection monitoring desktop notification calls
+async fn monitor_connection() -> Result<zbus::Connection> {
     // Monitor requires a separate connection
     let connection = zbus::Connection::session().await?;
     let proxy = zbus::fdo::MonitoringProxy::builder(&connection)
@@ -38,35 +170,343 @@ pub async fn run_notification_session(infinitime: &bt::InfiniTime) -> Result<()>
         .path("/org/freedesktop/Notifications")?
         .build();
     proxy.become_monitor(&[rule], 0).await?;
+    Ok(connection)
+}
+
+/// Check whether notifications can be monitored, without forwarding them anywhere.
+/// Fails with [`is_access_denied`] error if session bus permission is missing
+pub async fn check_monitor_permission() -> Result<()> {
+    monitor_connection().await.map(|_| ())
+}
+
+/// Whether the error is caused by missing permission to monitor session bus
+/// (e.g. when running in a sandbox without `--socket=session-bus`)
+pub fn is_access_denied(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref(), Some(zbus::fdo::Error::AccessDenied(_)))
+}
 
-    let mut stream = zbus::MessageStream::from(&connection);
-    while let Some(msg) = stream.try_next().await? {
-        match msg.body().deserialize::<DesktopNotification>() {
-            Ok(notification) => {
-                // Dirty hack to avoid duplicated notifications:
-                // For some reason, every notification produces two calls on DBus
-                // with identical fields, except the second contains extra hints:
-                // "x-shell-sender" and "x-shell-sender-pid".
-                // TODO: Find proper solution.
-                if notification.hints.contains_key("x-shell-sender") {
+/// Current screen lock state and the stream of its changes. GNOME Shell only
+/// reports it on its own interface, other desktops use the freedesktop one
+async fn screen_lock_stream() -> Result<(bool, BoxStream<'static, bool>)> {
+    let connection = zbus::Connection::session().await?;
+    // Subscribe before reading the state, to not miss the change in between
+    let proxy = GnomeScreenSaverProxy::new(&connection).await?;
+    let changes = proxy.receive_active_changed().await?;
+    if let Ok(locked) = proxy.get_active().await {
+        let changes = changes.filter_map(|s| async move { s.args().ok().map(|a| a.new_value) });
+        return Ok((locked, changes.boxed()));
+    }
+    let proxy = ScreenSaverProxy::new(&connection).await?;
+    let changes = proxy.receive_active_changed().await?;
+    let locked = proxy.get_active().await?;
+    let changes = changes.filter_map(|s| async move { s.args().ok().map(|a| a.new_value) });
+    Ok((locked, changes.boxed()))
+}
+
+/// Forward desktop notifications to the watch until the session bus connection
+/// ends, filtered and formatted according to `config`
+pub async fn run_notification_session(infinitime: &bt::InfiniTime, config: &SessionConfig) -> Result<()> {
+    let connection = monitor_connection().await?;
+    let mut lock_condition = config.lock_condition;
+
+    let mut locked = false;
+    let lock_changes = match lock_condition {
+        LockCondition::Always => stream::empty().boxed(),
+        _ => match screen_lock_stream().await {
+            Ok((initial, changes)) => {
+                locked = initial;
+                changes
+            }
+            Err(error) => {
+                log::warn!("Failed to monitor screen lock state, forwarding regardless of it: {error}");
+                lock_condition = LockCondition::Always;
+                stream::empty().boxed()
+            }
+        },
+    };
+
+    // Watch responses to call notifications are only logged for now
+    let call_responses = match infinitime.get_call_response_stream().await {
+        Ok(stream) => stream.boxed(),
+        Err(error) => {
+            log::warn!("Failed to subscribe to call responses: {error}");
+            stream::empty().boxed()
+        }
+    };
+
+    let messages = zbus::MessageStream::from(&connection).map(Event::Message);
+    let events = stream::select(
+        stream::select(messages, call_responses.map(Event::CallResponse)),
+        lock_changes.map(Event::LockChanged),
+    );
+    pin_mut!(events);
+    // Not tracked at all unless the watch can show it
+    let mut unread = infinitime.supports_unread_count().then(UnreadCount::default);
+    if unread.is_none() {
+        log::debug!("Unread alert count is not supported by the watch");
+    }
+    loop {
+        let event = match unread.as_ref().and_then(|u| u.deadline) {
+            Some(deadline) => match time::timeout_at(deadline, events.next()).await {
+                Ok(event) => event,
+                Err(_) => {
+                    if let Some(unread) = &mut unread {
+                        unread.clear(infinitime).await;
+                    }
                     continue;
                 }
-
-                if infinitime.is_upgrading_firmware() {
+            },
+            None => events.next().await,
+        };
+        match event {
+            Some(Event::Message(msg)) => {
+                let msg = msg?;
+                if !lock_condition.allows(locked) {
+                    log::debug!("Skipping notification, screen lock state doesn't match");
                     continue;
                 }
-
-                log::debug!("Forwarding notification: {notification:?}");
-                let alert = bt::Notification::Alert {
-                    title: &format!("{}: {}", notification.app_name, notification.summary),
-                    content: notification.body,
-                };
-                _ = infinitime.write_notification(alert).await;
+                if forward_notification(infinitime, msg, config).await {
+                    if let Some(unread) = &mut unread {
+                        unread.increment(infinitime).await;
+                    }
+                }
             }
-            Err(error) => {
-                log::error!("Failed to parse notification: {error}");
+            Some(Event::CallResponse(response)) => {
+                log::info!("Call notification response from the watch: {response:?}");
             }
+            Some(Event::LockChanged(state)) => {
+                log::debug!("Screen lock state changed: locked={state}");
+                locked = state;
+            }
+            None => break,
         }
     }
     Ok(())
 }
+
+/// Number of forwarded alerts, synced to the watch
+#[derive(Default)]
+struct UnreadCount {
+    count: u8,
+    deadline: Option<Instant>,
+}
+
+impl UnreadCount {
+    async fn increment(&mut self, infinitime: &bt::InfiniTime) {
+        self.count = self.count.saturating_add(1);
+        self.deadline = Some(Instant::now() + UNREAD_TIMEOUT);
+        self.sync(infinitime).await;
+    }
+
+    async fn clear(&mut self, infinitime: &bt::InfiniTime) {
+        self.count = 0;
+        self.deadline = None;
+        self.sync(infinitime).await;
+    }
+
+    async fn sync(&self, infinitime: &bt::InfiniTime) {
+        if let Err(error) = infinitime.write_unread_count(self.count).await {
+            log::warn!("Failed to write unread alert count: {error}");
+        }
+    }
+}
+
+enum Event {
+    Message(zbus::Result<zbus::Message>),
+    CallResponse(bt::CallResponse),
+    LockChanged(bool),
+}
+
+/// Returns whether the notification is forwarded to the watch
+async fn forward_notification(infinitime: &bt::InfiniTime, msg: zbus::Message, config: &SessionConfig) -> bool {
+    match msg.body().deserialize::<DesktopNotification>() {
+        Ok(notification) => {
+            // Dirty hack to avoid duplicated notifications:
+            // For some reason, every notification produces two calls on DBus
+            // with identical fields, except the second contains extra hints:
+            // "x-shell-sender" and "x-shell-sender-pid".
+            // TODO: Find proper solution.
+            if notification.hints.contains_key("x-shell-sender") {
+                return false;
+            }
+
+            if infinitime.is_upgrading_firmware() {
+                return false;
+            }
+
+            let urgency = Urgency::from_hints(&notification.hints);
+            if urgency == Urgency::Low && !config.forward_low_urgency {
+                log::debug!("Skipping low urgency notification from {}", notification.app_name);
+                return false;
+            }
+
+            log::debug!("Forwarding notification: {notification:?}");
+            let title = format_title(&config.format, notification.app_name, notification.summary, notification.body);
+            let (title, content) = fit_alert(&title, notification.body);
+            let alert = bt::Notification::Alert { title: &title, content: &content };
+            infinitime.write_notification(alert).await.is_ok()
+        }
+        Err(error) => {
+            log::error!("Failed to parse notification: {error}");
+            false
+        }
+    }
+}
+
+/// Substitute `{app}`, `{summary}` and `{body}` placeholders in the title
+/// template. Unknown placeholders are kept as is
+pub fn format_title(format: &str, app: &str, summary: &str, body: &str) -> String {
+    let mut title = String::with_capacity(format.len() + summary.len());
+    let mut rest = format;
+    while let Some(start) = rest.find('{') {
+        title.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| match &rest[1..end] {
+            "app" => Some((app, end)),
+            "summary" => Some((summary, end)),
+            "body" => Some((body, end)),
+            _ => None,
+        });
+        match value {
+            Some((value, end)) => {
+                title.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                title.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    title.push_str(rest);
+    title.trim().to_string()
+}
+
+/// Truncate the title and content so that together with the separator and
+/// the terminating zero they fit into [`bt::MAX_ALERT_SIZE`]. Title is more
+/// important, the content gets what's left of it
+fn fit_alert<'a>(title: &'a str, content: &'a str) -> (Cow<'a, str>, Cow<'a, str>) {
+    let title = truncate(title, bt::MAX_ALERT_SIZE - 2);
+    let content = truncate(content, bt::MAX_ALERT_SIZE - 2 - title.len());
+    (title, content)
+}
+
+/// Cut the text to at most `max` bytes at a character boundary,
+/// ending it with an ellipsis if it's cut
+fn truncate(text: &str, max: usize) -> Cow<'_, str> {
+    const ELLIPSIS: &str = "…";
+    if text.len() <= max {
+        return Cow::Borrowed(text);
+    }
+    let boundary = |limit: usize| (0..=limit).rev().find(|i| text.is_char_boundary(*i)).unwrap_or(0);
+    match max.checked_sub(ELLIPSIS.len()) {
+        Some(limit) => Cow::Owned(format!("{}{ELLIPSIS}", &text[..boundary(limit)])),
+        None => Cow::Borrowed(&text[..boundary(max)]),
+    }
+}
+
+/// Show desktop notification on the host, or update the existing one
+/// if `replaces_id` is not 0. Returns the ID of the shown notification.
+pub async fn send_desktop_notification(
+    connection: &zbus::Connection,
+    app_name: &str,
+    app_icon: &str,
+    replaces_id: u32,
+    summary: &str,
+    body: &str,
+) -> Result<u32> {
+    let proxy = NotificationsProxy::new(connection).await?;
+    let id = proxy
+        .notify(app_name, replaces_id, app_icon, summary, body, &[], HashMap::new(), -1)
+        .await?;
+    Ok(id)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urgency(value: Option<Value<'static>>) -> Urgency {
+        let mut hints = HashMap::new();
+        hints.insert("x-shell-sender-pid", Value::U32(42));
+        if let Some(value) = value {
+            hints.insert("urgency", value);
+        }
+        Urgency::from_hints(&hints)
+    }
+
+    #[test]
+    fn urgency_levels() {
+        assert_eq!(urgency(Some(Value::U8(0))), Urgency::Low);
+        assert_eq!(urgency(Some(Value::U8(1))), Urgency::Normal);
+        assert_eq!(urgency(Some(Value::U8(2))), Urgency::Critical);
+    }
+
+    #[test]
+    fn urgency_non_byte_values() {
+        assert_eq!(urgency(Some(Value::I32(0))), Urgency::Low);
+        assert_eq!(urgency(Some(Value::U32(2))), Urgency::Critical);
+    }
+
+    #[test]
+    fn title_format() {
+        assert_eq!(format_title(DEFAULT_FORMAT, "Mail", "New message", "Hi"), "Mail: New message");
+        assert_eq!(format_title("{summary}", "Mail", "New message", "Hi"), "New message");
+        assert_eq!(format_title("{summary} ({body})", "Mail", "Re", "Hi"), "Re (Hi)");
+        assert_eq!(format_title("{app} {unknown} {", "Mail", "", ""), "Mail {unknown} {");
+        assert_eq!(format_title("{summary} ", "Mail", "New message", ""), "New message");
+    }
+
+    #[test]
+    fn truncation() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("exactly 10", 10), "exactly 10");
+        assert_eq!(truncate("a bit too long", 10), "a bit t…");
+        // Multi-byte characters are not split
+        assert_eq!(truncate("ääääää", 8), "ää…");
+        assert_eq!(truncate("long", 2), "lo");
+        assert_eq!(truncate("long", 0), "");
+    }
+
+    #[test]
+    fn alert_size() {
+        let budget = bt::MAX_ALERT_SIZE - 2;
+        let title = "t".repeat(40);
+        // Exactly at the limit nothing is cut
+        let content = "c".repeat(budget - title.len());
+        let (t, c) = fit_alert(&title, &content);
+        assert_eq!((t.len(), c.as_ref()), (title.len(), content.as_str()));
+        // One byte over the limit cuts the content
+        let content = "c".repeat(budget - title.len() + 1);
+        let (t, c) = fit_alert(&title, &content);
+        assert_eq!(t.len() + c.len(), budget);
+        assert!(c.ends_with('…'));
+        // Long title takes the whole budget
+        let title = "t".repeat(bt::MAX_ALERT_SIZE);
+        let (t, c) = fit_alert(&title, "content");
+        assert_eq!((t.len(), c.as_ref()), (budget, ""));
+    }
+
+    #[test]
+    fn lock_condition() {
+        assert!(LockCondition::Always.allows(true) && LockCondition::Always.allows(false));
+        assert!(LockCondition::WhenLocked.allows(true) && !LockCondition::WhenLocked.allows(false));
+        assert!(!LockCondition::WhenUnlocked.allows(true) && LockCondition::WhenUnlocked.allows(false));
+    }
+
+    #[test]
+    fn lock_condition_names() {
+        for condition in LockCondition::ALL {
+            assert_eq!(LockCondition::from_name(condition.name()), Some(condition));
+        }
+        assert_eq!(LockCondition::from_name("never"), None);
+    }
+
+    #[test]
+    fn urgency_missing_or_invalid() {
+        assert_eq!(urgency(None), Urgency::Normal);
+        assert_eq!(urgency(Some(Value::U8(7))), Urgency::Normal);
+        assert_eq!(urgency(Some(Value::from("critical"))), Urgency::Normal);
+    }
+}
@@ -0,0 +1,52 @@
+use super::super::bt;
+use anyhow::Result;
+use futures::StreamExt;
+use zbus::Connection;
+
+/// Host battery level is forwarded in steps of this size, so that the watch
+/// doesn't get a notification on every percent
+const LEVEL_STEP: u8 = 10;
+
+#[zbus::proxy(
+    interface = "org.freedesktop.UPower.Device",
+    default_service = "org.freedesktop.UPower",
+    default_path = "/org/freedesktop/UPower/devices/DisplayDevice"
+)]
+trait Device {
+    #[zbus(property)]
+    fn is_present(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn percentage(&self) -> zbus::Result<f64>;
+}
+
+/// Forward host battery level to the watch whenever it changes by [`LEVEL_STEP`].
+/// UPower is a system service, so `connection` must be to the system bus.
+pub async fn run_host_battery_session(connection: &Connection, infinitime: &bt::InfiniTime) -> Result<()> {
+    let device = DeviceProxy::new(connection).await?;
+    if !device.is_present().await? {
+        log::info!("Host has no battery, nothing to forward");
+        return Ok(());
+    }
+
+    let mut changes = device.receive_percentage_changed().await;
+    let mut percentage = device.percentage().await?;
+    let mut last_step = None;
+    loop {
+        let level = percentage.round().clamp(0.0, 100.0) as u8;
+        let step = level / LEVEL_STEP;
+        if last_step != Some(step) {
+            log::debug!("Host battery level: {level}%");
+            // A failed write is retried on the next change rather than ending the session
+            match infinitime.write_host_battery(level).await {
+                Ok(()) => last_step = Some(step),
+                Err(error) => log::error!("Failed to send host battery level: {error}"),
+            }
+        }
+        match changes.next().await {
+            Some(change) => percentage = change.get().await?,
+            None => break,
+        }
+    }
+    Ok(())
+}
@@ -1,8 +1,34 @@
-use std::{env, path::{Path, PathBuf}};
-use tokio::{fs::File, io::AsyncWriteExt};
-use anyhow::{anyhow, Result};
-use serde::Deserialize;
-use reqwest::IntoUrl;
+use std::{env, path::{Path, PathBuf}, time::{Duration, SystemTime, UNIX_EPOCH}};
+use tokio::{fs::File, io::AsyncWriteExt, time};
+use anyhow::{anyhow, ensure, Result};
+use serde::{de::DeserializeOwned, Deserialize};
+use reqwest::{header::HeaderMap, IntoUrl, Response, StatusCode, Url};
+
+/// Default firmware releases repository
+pub const DEFAULT_REPO: &str = "InfiniTimeOrg/InfiniTime";
+
+/// Environment variable with GitHub access token, which raises the API rate limit
+pub const TOKEN_ENV: &str = "GITHUB_TOKEN";
+
+/// Rate-limited requests are retried only if the limit resets sooner than this
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+const RETRY_ATTEMPTS: u32 = 3;
+
+
+/// Request was rejected because GitHub API rate limit is exceeded
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("GitHub rate limit reached, try again in {} minutes", self.minutes())]
+pub struct RateLimited {
+    /// Time until the limit resets, if reported by GitHub
+    pub reset_in: Option<Duration>,
+}
+
+impl RateLimited {
+    /// Minutes until the limit resets, rounded up (at least 1)
+    pub fn minutes(&self) -> u64 {
+        self.reset_in.map_or(1, |d| d.as_secs().div_ceil(60).max(1))
+    }
+}
 
 #[derive(Deserialize, Debug, PartialEq)]
 pub struct ReleaseInfo {
@@ -11,6 +37,8 @@ pub struct ReleaseInfo {
     pub tag: String,
     #[serde(rename = "html_url")]
     pub url: String,
+    /// Release notes in markdown
+    pub body: Option<String>,
     pub assets: Vec<Asset>,
 }
 
@@ -40,44 +68,106 @@ impl ReleaseInfo {
     }
 }
 
-pub async fn list_releases() -> Result<Vec<ReleaseInfo>> {
+/// Check that `repo` is in "owner/repo" format
+pub fn is_valid_repo(repo: &str) -> bool {
+    let is_valid_part = |part: &str| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+    };
+    matches!(repo.split_once('/'), Some((owner, name)) if is_valid_part(owner) && is_valid_part(name))
+}
+
+fn releases_url(repo: &str) -> Result<String> {
+    ensure!(is_valid_repo(repo), "Invalid repository '{}', expected 'owner/repo'", repo);
+    Ok(format!("https://api.github.com/repos/{repo}/releases"))
+}
+
+/// List releases of `repo` in "owner/repo" format
+pub async fn list_releases(repo: &str) -> Result<Vec<ReleaseInfo>> {
+    request_json(releases_url(repo)?).await
+}
+
+/// Latest published (non-prerelease) release of `repo` in "owner/repo" format
+pub async fn latest_release(repo: &str) -> Result<ReleaseInfo> {
+    request_json(format!("{}/latest", releases_url(repo)?)).await
+}
+
+async fn request_json<T: DeserializeOwned>(url: impl IntoUrl) -> Result<T> {
+    let response = request(url, "application/vnd.github+json").await?;
+    Ok(response.json().await?)
+}
+
+pub async fn download_content(url: impl IntoUrl) -> Result<Vec<u8>> {
+    let response = request(url, "application/octet-stream").await?;
+    let content = response.bytes().await?;
+    Ok(content.to_vec())
+}
+
+/// Send GET request, retrying it if rate limit resets soon. Fails with
+/// [`RateLimited`] otherwise
+async fn request(url: impl IntoUrl, accept: &str) -> Result<Response> {
+    let url = url.into_url()?;
     let client = reqwest::Client::new();
-    let response = client
-        .get("https://api.github.com/repos/InfiniTimeOrg/InfiniTime/releases")
-        .header("Accept", "application/vnd.github+json")
-        .header("User-Agent", "Watchmate")
-        .send().await?;
-
-    let status = response.status();
-    if status.is_success() {
-        let releases = response.json().await?;
-        // dbg!(&releases);
-        Ok(releases)
-    } else {
+    let mut attempt = 1;
+    loop {
+        let mut request = client
+            .get(url.clone())
+            .header("Accept", accept)
+            .header("User-Agent", "Watchmate");
+        if let Some(token) = token_for(&url, env::var(TOKEN_ENV).ok()) {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send().await?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        if let Some(limit) = rate_limit(status, response.headers(), SystemTime::now()) {
+            match limit.reset_in {
+                Some(delay) if delay <= MAX_RETRY_DELAY && attempt < RETRY_ATTEMPTS => {
+                    log::warn!("GitHub rate limit reached, retrying in {:?}", delay);
+                    attempt += 1;
+                    time::sleep(delay).await;
+                    continue;
+                }
+                _ => return Err(limit.into()),
+            }
+        }
         let text = response.text().await?;
         log::error!("Request failed: {}\n{}", status, text);
-        Err(anyhow!("Request failed: {}", status))
+        return Err(anyhow!("Request failed: {}", status));
     }
 }
 
-pub async fn download_content(url: impl IntoUrl) -> Result<Vec<u8>>
-{
-    let client = reqwest::Client::new();
-    let response = client
-        .get(url)
-        .header("Accept", "application/octet-stream")
-        .header("User-Agent", "Watchmate")
-        .send().await?;
-
-    let status = response.status();
-    if status.is_success() {
-        let content = response.bytes().await?;
-        Ok(content.to_vec())
-    } else {
-        let text = response.text().await?;
-        log::error!("Request failed: {}\n{}", status, text);
-        Err(anyhow!("Request failed: {}", status))
+/// Pass the token (from [`TOKEN_ENV`]) only to GitHub hosts to avoid leaking it elsewhere
+fn token_for(url: &Url, token: Option<String>) -> Option<String> {
+    match url.host_str() {
+        Some("api.github.com" | "github.com") => token.filter(|t| !t.is_empty()),
+        _ => None,
+    }
+}
+
+/// Detect rate limit response. GitHub reports it either with 403 or 429 status,
+/// and either `Retry-After` (secondary limits) or `X-RateLimit-*` headers
+fn rate_limit(status: StatusCode, headers: &HeaderMap, now: SystemTime) -> Option<RateLimited> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let header = |name: &str| -> Option<u64> {
+        headers.get(name)?.to_str().ok()?.trim().parse().ok()
+    };
+    if let Some(seconds) = header("retry-after") {
+        return Some(RateLimited { reset_in: Some(Duration::from_secs(seconds)) });
     }
+    if header("x-ratelimit-remaining") == Some(0) {
+        let reset_in = header("x-ratelimit-reset").map(|reset| {
+            let now = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+            Duration::from_secs(reset).saturating_sub(now)
+        });
+        return Some(RateLimited { reset_in });
+    }
+    // 403 is also returned for other reasons, like missing permissions
+    (status == StatusCode::TOO_MANY_REQUESTS).then_some(RateLimited { reset_in: None })
 }
 
 pub async fn save_file(content: &[u8], filepath: impl AsRef<Path>) -> Result<()> {
@@ -102,3 +192,68 @@ pub fn _get_download_dir() -> Result<PathBuf> {
 pub fn _get_download_filepath(filename: impl AsRef<Path>) -> Result<PathBuf> {
     Ok(_get_download_dir()?.join(&filename))
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn check(status: StatusCode, headers: &[(&'static str, &str)]) -> Option<RateLimited> {
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(*name, value.parse().unwrap());
+        }
+        rate_limit(status, &map, UNIX_EPOCH + Duration::from_secs(NOW))
+    }
+
+    #[test]
+    fn primary_rate_limit() {
+        let reset = (NOW + 150).to_string();
+        let limit = check(StatusCode::FORBIDDEN, &[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", &reset),
+        ]).unwrap();
+        assert_eq!(limit.reset_in, Some(Duration::from_secs(150)));
+        assert_eq!(limit.minutes(), 3);
+        assert_eq!(limit.to_string(), "GitHub rate limit reached, try again in 3 minutes");
+    }
+
+    #[test]
+    fn reset_in_the_past() {
+        let reset = (NOW - 5).to_string();
+        let limit = check(StatusCode::FORBIDDEN, &[
+            ("x-ratelimit-remaining", "0"),
+            ("x-ratelimit-reset", &reset),
+        ]).unwrap();
+        assert_eq!(limit.reset_in, Some(Duration::ZERO));
+        assert_eq!(limit.minutes(), 1);
+    }
+
+    #[test]
+    fn secondary_rate_limit() {
+        let limit = check(StatusCode::TOO_MANY_REQUESTS, &[("retry-after", "30")]).unwrap();
+        assert_eq!(limit.reset_in, Some(Duration::from_secs(30)));
+        let limit = check(StatusCode::TOO_MANY_REQUESTS, &[]).unwrap();
+        assert_eq!(limit.reset_in, None);
+    }
+
+    #[test]
+    fn not_rate_limited() {
+        assert_eq!(check(StatusCode::FORBIDDEN, &[]), None);
+        assert_eq!(check(StatusCode::FORBIDDEN, &[("x-ratelimit-remaining", "12")]), None);
+        assert_eq!(check(StatusCode::NOT_FOUND, &[("x-ratelimit-remaining", "0")]), None);
+        assert_eq!(check(StatusCode::OK, &[("retry-after", "30")]), None);
+    }
+
+    #[test]
+    fn token_only_for_github() {
+        let token = |url: &str, token: Option<&str>| token_for(&Url::parse(url).unwrap(), token.map(String::from));
+        let api = "https://api.github.com/repos/a/b/releases";
+        assert_eq!(token(api, Some("secret")).as_deref(), Some("secret"));
+        assert_eq!(token("https://example.com/firmware.zip", Some("secret")), None);
+        assert_eq!(token(api, Some("")), None);
+        assert_eq!(token(api, None), None);
+    }
+}
@@ -11,11 +11,19 @@ pub mod github;
 #[cfg(feature = "github")]
 pub use github as gh;
 
+#[cfg(feature = "weather")]
+pub mod forecast;
+
 mod utils;
+mod version;
+
+pub use bluetooth::{snapshot, DeviceSnapshot};
+pub use version::is_update_available;
 
 
 // Dependency reexports
 pub use bluer;
+pub use chrono;
 pub use tokio;
 #[cfg(feature = "freedesktop")]
 pub use zbus;
@@ -0,0 +1,83 @@
+use std::cmp::Ordering;
+use version_compare::Version;
+
+
+/// Check whether `latest` firmware version is newer than `current`.
+/// Leading "v" is ignored, and a release is considered newer than
+/// its pre-releases (e.g. "1.14.0" is newer than "1.14.0-rc1").
+/// Returns false if either of the versions can't be parsed
+pub fn is_update_available(current: &str, latest: &str) -> bool {
+    compare(latest, current) == Some(Ordering::Greater)
+}
+
+fn compare(a: &str, b: &str) -> Option<Ordering> {
+    let (a_core, a_pre) = split(a);
+    let (b_core, b_pre) = split(b);
+    let is_numeric = |core: &str| core.starts_with(|c: char| c.is_ascii_digit());
+    if !is_numeric(a_core) || !is_numeric(b_core) {
+        return None;
+    }
+    let ordering = Version::from(a_core)?.partial_cmp(&Version::from(b_core)?)?;
+    if ordering != Ordering::Equal {
+        return Some(ordering);
+    }
+    match (a_pre, b_pre) {
+        (None, None) => Some(Ordering::Equal),
+        (None, Some(_)) => Some(Ordering::Greater),
+        (Some(_), None) => Some(Ordering::Less),
+        (Some(a_pre), Some(b_pre)) => match (Version::from(a_pre), Version::from(b_pre)) {
+            (Some(a_pre), Some(b_pre)) => a_pre.partial_cmp(&b_pre),
+            _ => Some(a_pre.cmp(b_pre)),
+        },
+    }
+}
+
+/// Split version into release and pre-release parts
+fn split(version: &str) -> (&str, Option<&str>) {
+    let version = version.trim();
+    let version = version.strip_prefix('v').unwrap_or(version);
+    match version.split_once('-') {
+        Some((core, pre)) => (core, Some(pre)),
+        None => (version, None),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newer_release() {
+        assert!(is_update_available("1.13.0", "1.14.0"));
+        assert!(is_update_available("1.9.0", "1.10.0"));
+        assert!(is_update_available("1.14.0", "1.14.1"));
+    }
+
+    #[test]
+    fn same_or_older_release() {
+        assert!(!is_update_available("1.14.0", "1.14.0"));
+        assert!(!is_update_available("1.14.0", "1.13.0"));
+        assert!(!is_update_available("1.14", "1.14.0"));
+    }
+
+    #[test]
+    fn pre_release() {
+        assert!(is_update_available("1.14.0-rc1", "1.14.0"));
+        assert!(!is_update_available("1.14.0", "1.14.0-rc1"));
+        assert!(is_update_available("1.13.0", "1.14.0-rc1"));
+        assert!(is_update_available("1.14.0-rc1", "1.14.0-rc2"));
+    }
+
+    #[test]
+    fn tag_prefix() {
+        assert!(is_update_available("1.13.0", "v1.14.0"));
+        assert!(!is_update_available("v1.14.0", "1.14.0"));
+    }
+
+    #[test]
+    fn unparsable() {
+        assert!(!is_update_available("", "1.14.0"));
+        assert!(!is_update_available("1.14.0", "latest"));
+    }
+}
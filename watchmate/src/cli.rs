@@ -0,0 +1,253 @@
+use crate::ui;
+use infinitime::{bluer, bt::{self, ProgressEvent}, gh, tokio};
+use std::{env, process::ExitCode, str::FromStr, sync::Arc, time::Duration};
+use anyhow::{anyhow, Context, Result};
+use relm4::gtk::{gio, prelude::SettingsExt};
+
+
+const SCAN_DURATION: Duration = Duration::from_secs(5);
+
+const USAGE: &str = "\
+Usage:
+  watchmate [--background] [--dbus]
+  watchmate --flash <path-or-url> [--device <address>]
+  watchmate --resources <path-or-url> [--device <address>]
+  watchmate --info [--device <address>]
+  watchmate --scan [seconds]
+  watchmate --help";
+
+
+#[derive(Debug)]
+pub enum Command {
+    Flash(String),
+    Resources(String),
+    /// Print basic info read from the watch
+    Info,
+    /// Print InfiniTime devices discovered within the duration
+    Scan(Duration),
+    /// Print usage
+    Help,
+}
+
+#[derive(Debug)]
+pub struct Args {
+    command: Command,
+    device: Option<bluer::Address>,
+}
+
+/// Parse headless-mode arguments. Returns `Ok(None)` if no headless
+/// command is requested, so that the GUI can be started instead.
+pub fn parse_args() -> Result<Option<Args>> {
+    let mut command = None;
+    let mut device = None;
+    let mut args = env::args().skip(1).peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--flash" => {
+                let source = args.next().ok_or(anyhow!("--flash requires a file path or URL"))?;
+                command = Some(Command::Flash(source));
+            }
+            "--resources" => {
+                let source = args.next().ok_or(anyhow!("--resources requires a file path or URL"))?;
+                command = Some(Command::Resources(source));
+            }
+            "--info" => {
+                command = Some(Command::Info);
+            }
+            "--scan" => {
+                // Duration is optional
+                let seconds = match args.next_if(|a| !a.starts_with("--")) {
+                    Some(value) => value.parse()
+                        .map_err(|_| anyhow!("Invalid scan duration: {}", value))?,
+                    None => SCAN_DURATION.as_secs(),
+                };
+                command = Some(Command::Scan(Duration::from_secs(seconds)));
+            }
+            "--device" => {
+                let address = args.next().ok_or(anyhow!("--device requires a bluetooth address"))?;
+                device = Some(bluer::Address::from_str(&address)
+                    .map_err(|_| anyhow!("Invalid bluetooth address: {}", address))?);
+            }
+            "--help" | "-h" => {
+                command = Some(Command::Help);
+            }
+            // GUI options, handled in ui::run
+            "--background" | "--dbus" => (),
+            _ => return Err(anyhow!("Unknown argument: {}\n\n{}", arg, USAGE)),
+        }
+    }
+    if command.is_none() && device.is_some() {
+        return Err(anyhow!("--device requires --flash, --resources or --info\n\n{}", USAGE));
+    }
+    Ok(command.map(|command| Args { command, device }))
+}
+
+pub fn run(args: Args) -> ExitCode {
+    if let Command::Help = args.command {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let result = tokio::runtime::Runtime::new()
+        .context("Failed to start async runtime")
+        .and_then(|runtime| runtime.block_on(execute(args)));
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {:#}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+async fn execute(args: Args) -> Result<()> {
+    let session = bluer::Session::new().await
+        .context("Failed to start bluetooth session")?;
+    let adapter = session.default_adapter().await
+        .context("Bluetooth adapter not found")?;
+
+    match args.command {
+        Command::Flash(source) => {
+            let infinitime = connect_infinitime(&adapter, args.device).await?;
+            let content = read_asset(&source).await?;
+            flash(|tx| async move { infinitime.firmware_upgrade(&content, Some(tx)).await }).await
+        }
+        Command::Resources(source) => {
+            let infinitime = connect_infinitime(&adapter, args.device).await?;
+            let content = read_asset(&source).await?;
+            flash(|tx| async move { infinitime.upload_resources(&content, Some(tx)).await }).await
+        }
+        Command::Info => print_info(&adapter, args.device).await,
+        Command::Scan(duration) => print_scan(&adapter, duration).await,
+        Command::Help => unreachable!("Help is printed without bluetooth"),
+    }
+}
+
+async fn connect_infinitime(adapter: &bluer::Adapter, address: Option<bluer::Address>) -> Result<bt::InfiniTime> {
+    let device = connect(adapter, address).await?;
+    bt::InfiniTime::new(device).await
+        .context("Device is rejected")
+}
+
+async fn connect(adapter: &bluer::Adapter, address: Option<bluer::Address>) -> Result<Arc<bluer::Device>> {
+    let address = match address.or_else(saved_address) {
+        Some(address) => address,
+        None => {
+            let devices = bt::InfiniTime::list_known_devices(adapter, &name_filter()).await?;
+            match devices.as_slice() {
+                [device] => device.address(),
+                [] => scan(adapter).await?,
+                _ => return Err(anyhow!("Multiple InfiniTime devices are known, use --device <address>")),
+            }
+        }
+    };
+
+    let device = adapter.device(address)?;
+    if !device.is_connected().await? {
+        println!("Connecting to {}...", address);
+        device.connect().await
+            .with_context(|| format!("Failed to connect to {}", address))?;
+    }
+    println!("Connected to {}", address);
+    Ok(Arc::new(device))
+}
+
+/// Find the only InfiniTime device nearby
+async fn scan(adapter: &bluer::Adapter) -> Result<bluer::Address> {
+    println!("Scanning for InfiniTime devices...");
+    let devices = bt::scan_for(adapter, &name_filter(), SCAN_DURATION).await?;
+    match devices.as_slice() {
+        [device] => Ok(device.address),
+        [] => Err(anyhow!("No InfiniTime devices found, use --device <address>")),
+        _ => {
+            for device in &devices {
+                println!("{} {}", device.address, device.name.as_deref().unwrap_or_default());
+            }
+            Err(anyhow!("Multiple InfiniTime devices found, use --device <address>"))
+        }
+    }
+}
+
+/// Print address, name and signal strength of the InfiniTime devices nearby
+async fn print_scan(adapter: &bluer::Adapter, duration: Duration) -> Result<()> {
+    eprintln!("Scanning for InfiniTime devices for {} seconds...", duration.as_secs());
+    let devices = bt::scan_for(adapter, &name_filter(), duration).await?;
+    for device in &devices {
+        let rssi = device.rssi.map(|r| format!("{r} dBm")).unwrap_or_default();
+        println!("{}\t{}\t{}", device.address, device.name.as_deref().unwrap_or_default(), rssi);
+    }
+    if devices.is_empty() {
+        eprintln!("No InfiniTime devices found");
+    }
+    Ok(())
+}
+
+async fn print_info(adapter: &bluer::Adapter, address: Option<bluer::Address>) -> Result<()> {
+    let device = connect(adapter, address).await?;
+    let snapshot = infinitime::snapshot(device, false).await
+        .context("Device is rejected")?;
+    let or_unknown = |value: Option<String>| value.unwrap_or_else(|| String::from("unknown"));
+    println!("Address: {}", snapshot.address);
+    println!("Name: {}", or_unknown(snapshot.alias));
+    println!("Firmware: {}", or_unknown(snapshot.firmware_version));
+    println!("Battery: {}", or_unknown(snapshot.battery_level.map(|l| format!("{l}%"))));
+    println!("Heart rate: {}", or_unknown(snapshot.heart_rate.map(|hr| match hr {
+        bt::HeartRate::Bpm(bpm) => format!("{bpm} BPM"),
+        bt::HeartRate::Measuring => String::from("measuring"),
+        bt::HeartRate::NotWorn => String::from("not worn"),
+    })));
+    println!("Steps: {}", or_unknown(snapshot.step_count.map(|s| s.to_string())));
+    for (field, error) in &snapshot.errors {
+        eprintln!("Failed to read {}: {}", field, error);
+    }
+    Ok(())
+}
+
+async fn flash<F, Fut>(flasher: F) -> Result<()>
+    where F: FnOnce(bt::ProgressTx) -> Fut,
+          Fut: std::future::Future<Output = Result<()>>,
+{
+    let (progress_tx, mut progress_rx) = bt::progress_channel(32);
+    let progress_printer = async move {
+        while let Some(event) = progress_rx.recv().await {
+            match event {
+                ProgressEvent::Message(text) => {
+                    println!("{}", text);
+                }
+                ProgressEvent::Numbers { current, total } => {
+                    println!("{:.1} KB / {:.1} KB", current as f32 / 1024.0, total as f32 / 1024.0);
+                }
+            }
+        }
+    };
+    let (_, result) = tokio::join!(progress_printer, flasher(progress_tx));
+    result
+}
+
+async fn read_asset(source: &str) -> Result<Vec<u8>> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        println!("Downloading {}...", source);
+        gh::download_content(source).await
+    } else {
+        tokio::fs::read(source).await
+            .with_context(|| format!("Failed to read file '{}'", source))
+    }
+}
+
+fn saved_settings() -> Option<gio::Settings> {
+    // Settings schema might not be installed on headless systems
+    let schema = gio::SettingsSchemaSource::default()?.lookup(ui::APP_ID, true)?;
+    Some(gio::Settings::new_full(&schema, None::<&gio::SettingsBackend>, None))
+}
+
+fn saved_address() -> Option<bluer::Address> {
+    let settings = saved_settings()?;
+    bluer::Address::from_str(settings.string(ui::SETTING_DEVICE_ADDRESS).as_str()).ok()
+}
+
+/// Discovery name filter from the settings, same as in the GUI
+fn name_filter() -> String {
+    match saved_settings() {
+        Some(settings) => settings.string(ui::SETTING_NAME_FILTER).to_string(),
+        None => bt::DEFAULT_NAME_FILTER.to_string(),
+    }
+}
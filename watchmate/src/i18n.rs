@@ -0,0 +1,67 @@
+use gettextrs::LocaleCategory;
+use std::ffi::CStr;
+
+pub use gettextrs::gettext;
+
+pub static GETTEXT_PACKAGE: &str = "watchmate";
+static LOCALEDIR: &str = match option_env!("LOCALEDIR") {
+    Some(dir) => dir,
+    None => "/usr/share/locale",
+};
+
+
+/// Load translations for the current locale. The UI falls back to
+/// English if the catalog is missing
+pub fn init() {
+    gettextrs::setlocale(LocaleCategory::LcAll, "");
+    let result = gettextrs::bindtextdomain(GETTEXT_PACKAGE, LOCALEDIR)
+        .and_then(|_| gettextrs::bind_textdomain_codeset(GETTEXT_PACKAGE, "UTF-8"))
+        .and_then(|_| gettextrs::textdomain(GETTEXT_PACKAGE));
+    if let Err(error) = result {
+        log::error!("Failed to load translations: {error}");
+    }
+}
+
+/// Translate `msgid` and substitute its `{name}` placeholders with values
+pub fn gettext_f(msgid: &str, args: &[(&str, &str)]) -> String {
+    substitute(gettext(msgid), args)
+}
+
+/// Like [`gettext_f`], but picks the plural form for `n`
+pub fn ngettext_f(msgid: &str, msgid_plural: &str, n: u32, args: &[(&str, &str)]) -> String {
+    substitute(gettextrs::ngettext(msgid, msgid_plural, n), args)
+}
+
+/// Format the number with digits grouped by thousands, using the separator
+/// of the current locale (none for the C locale)
+pub fn format_number(value: u32) -> String {
+    let separator = thousands_separator();
+    let digits = value.to_string();
+    let mut result = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            result.push_str(&separator);
+        }
+        result.push(digit);
+    }
+    result
+}
+
+fn thousands_separator() -> String {
+    // SAFETY: localeconv returns a pointer to static data, which stays valid
+    // until the next setlocale or localeconv call, and is copied right away
+    unsafe {
+        let conv = libc::localeconv();
+        if conv.is_null() || (*conv).thousands_sep.is_null() {
+            return String::new();
+        }
+        CStr::from_ptr((*conv).thousands_sep).to_string_lossy().into_owned()
+    }
+}
+
+fn substitute(mut text: String, args: &[(&str, &str)]) -> String {
+    for (name, value) in args {
+        text = text.replace(&format!("{{{name}}}"), value);
+    }
+    text
+}
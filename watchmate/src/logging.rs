@@ -0,0 +1,147 @@
+use infinitime::chrono;
+use log::Log;
+use relm4::gtk::glib;
+use std::{
+    fs::{self, File}, io::{self, Write}, path::PathBuf,
+    sync::{atomic::{AtomicBool, Ordering}, Mutex, OnceLock},
+};
+
+/// Log file is rotated when it grows larger than this, one old file is kept
+const MAX_FILE_SIZE: u64 = 1024 * 1024;
+const FILE_NAME: &str = "watchmate.log";
+const ROTATED_FILE_NAME: &str = "watchmate.log.1";
+/// Level of own messages in verbose mode
+const VERBOSE_LEVEL: log::LevelFilter = log::LevelFilter::Debug;
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+
+struct LogFile {
+    file: File,
+    size: u64,
+}
+
+impl LogFile {
+    fn open() -> io::Result<Self> {
+        let dir = log_dir();
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(FILE_NAME);
+        if fs::metadata(&path).is_ok_and(|m| m.len() > MAX_FILE_SIZE) {
+            fs::rename(&path, dir.join(ROTATED_FILE_NAME))?;
+        }
+        let file = File::options().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { file, size })
+    }
+
+    fn write(&mut self, record: &log::Record) -> io::Result<()> {
+        let line = format!(
+            "{} {:<5} {}: {}\n",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+            record.level(),
+            record.target(),
+            record.args(),
+        );
+        if self.size + line.len() as u64 > MAX_FILE_SIZE {
+            *self = Self::open()?;
+        }
+        self.file.write_all(line.as_bytes())?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+
+/// Logs to stderr according to `RUST_LOG`, additionally to the log file
+/// if enabled. Verbose mode lets through own debug messages regardless
+/// of `RUST_LOG`.
+struct Logger {
+    stderr: env_logger::Logger,
+    verbose: AtomicBool,
+    file: Mutex<Option<LogFile>>,
+}
+
+impl Logger {
+    fn max_level(&self) -> log::LevelFilter {
+        match self.verbose.load(Ordering::Relaxed) {
+            true => self.stderr.filter().max(VERBOSE_LEVEL),
+            false => self.stderr.filter(),
+        }
+    }
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.stderr.enabled(metadata) || (
+            self.verbose.load(Ordering::Relaxed)
+                && metadata.target().starts_with("watchmate")
+                && metadata.level() <= VERBOSE_LEVEL
+        )
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.stderr.enabled(record.metadata()) {
+            self.stderr.log(record);
+        } else if self.enabled(record.metadata()) {
+            eprintln!("[{:<5} {}] {}", record.level(), record.target(), record.args());
+        } else {
+            return;
+        }
+        // Logging from here would deadlock, so report to stderr directly.
+        // File logging is stopped on the first failure, so that a broken
+        // file (e.g. failed rotation) doesn't add an error to every line
+        let mut file = self.file.lock().unwrap();
+        if let Some(Err(error)) = file.as_mut().map(|f| f.write(record)) {
+            eprintln!("Failed to write log file, file logging is stopped: {error}");
+            *file = None;
+        }
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+        if let Some(log_file) = self.file.lock().unwrap().as_mut() {
+            _ = log_file.file.flush();
+        }
+    }
+}
+
+
+pub fn init() {
+    let stderr = env_logger::Builder::new()
+        .format_timestamp(None)
+        .filter_module("watchmate", log::LevelFilter::Info)
+        .parse_default_env()
+        .build();
+    let logger = LOGGER.get_or_init(|| Logger {
+        stderr,
+        verbose: AtomicBool::new(false),
+        file: Mutex::new(None),
+    });
+    log::set_max_level(logger.max_level());
+    log::set_logger(logger).expect("Logger is already set");
+}
+
+/// Log files location, `$XDG_STATE_HOME/watchmate/logs`
+pub fn log_dir() -> PathBuf {
+    glib::user_state_dir().join("watchmate").join("logs")
+}
+
+/// Start or stop writing to the log file
+pub fn set_file_enabled(enabled: bool) -> io::Result<()> {
+    if let Some(logger) = LOGGER.get() {
+        let mut file = logger.file.lock().unwrap();
+        match (enabled, file.is_some()) {
+            (true, false) => *file = Some(LogFile::open()?),
+            (false, true) => *file = None,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+pub fn set_verbose(verbose: bool) {
+    if let Some(logger) = LOGGER.get() {
+        logger.verbose.store(verbose, Ordering::Relaxed);
+        log::set_max_level(logger.max_level());
+    }
+}
@@ -1,11 +1,22 @@
+use std::process::ExitCode;
+
+mod cli;
+mod i18n;
+mod logging;
 mod ui;
 
-fn main() {
-    env_logger::Builder::new()
-        .format_timestamp(None)
-        .filter_module("watchmate", log::LevelFilter::Info)
-        .parse_default_env()
-        .init();
+fn main() -> ExitCode {
+    logging::init();
 
-    ui::run();
+    match cli::parse_args() {
+        Ok(Some(args)) => cli::run(args),
+        Ok(None) => {
+            ui::run();
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
 }
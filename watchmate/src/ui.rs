@@ -1,27 +1,64 @@
-use infinitime::{bluer, bt};
+use crate::{i18n::{self, gettext, gettext_f}, logging};
+use infinitime::{bluer, bt, fdo::notifications, gh};
 use std::{sync::Arc, path::PathBuf, env};
 use futures::{pin_mut, StreamExt};
-use gtk::{gio, glib, prelude::{ApplicationExt, BoxExt, GtkWindowExt, SettingsExt, WidgetExt}};
+use gtk::{gio, glib, prelude::{ApplicationExt, BoxExt, DisplayExt, GtkWindowExt, SettingsExt, SettingsExtManual, WidgetExt}};
 use relm4::{
     adw, gtk, actions::{AccelsPlus, RelmAction, RelmActionGroup},
     Component, ComponentController, ComponentParts,
     ComponentSender, Controller, RelmApp, MessageBroker
 };
+use relm4_components::alert::{Alert, AlertMsg, AlertResponse, AlertSettings};
 
 mod dashboard_page;
+mod dbus;
 mod devices_page;
 mod fwupd_page;
 mod settings_page;
+mod shortcuts;
 mod icon_names {
     include!(concat!(env!("OUT_DIR"), "/icon_names.rs"));
 }
 
 
-static APP_ID: &'static str = "io.gitlab.azymohliad.WatchMate";
+pub static APP_ID: &'static str = "io.gitlab.azymohliad.WatchMate";
 static SETTING_NOTIFICATIONS: &'static str = "forward-notifications";
+static SETTING_LOW_URGENCY_NOTIFICATIONS: &'static str = "forward-low-urgency-notifications";
+static SETTING_NOTIFICATIONS_LOCK_CONDITION: &'static str = "notifications-lock-condition";
+static SETTING_NOTIFICATION_FORMAT: &'static str = "notification-format";
+static SETTING_HOST_BATTERY: &'static str = "forward-host-battery";
+static SETTING_GATT_SERVER: &'static str = "enable-gatt-server";
+static SETTING_MEDIA_VOLUME_STEP: &'static str = "media-volume-step";
+static SETTING_WEATHER_PROVIDER: &'static str = "weather-provider";
+static SETTING_WEATHER_LOCATION: &'static str = "weather-location";
+static SETTING_WEATHER_LATITUDE: &'static str = "weather-latitude";
+static SETTING_WEATHER_LONGITUDE: &'static str = "weather-longitude";
 static SETTING_BACKGROUND: &'static str = "run-in-background";
 static SETTING_AUTO_START: &'static str = "auto-start";
-static SETTING_DEVICE_ADDRESS: &'static str = "auto-connect-address";
+pub static SETTING_DEVICE_ADDRESS: &'static str = "auto-connect-address";
+static SETTING_AUTO_CONNECT: &'static str = "auto-connect-enabled";
+static SETTING_DEVICE_NICKNAMES: &'static str = "device-nicknames";
+static SETTING_RESOURCES_VERSIONS: &'static str = "resources-versions";
+static SETTING_DISCONNECT_ON_QUIT: &'static str = "disconnect-on-quit";
+static SETTING_STEP_GOAL: &'static str = "step-goal";
+static SETTING_ADAPTER: &'static str = "bluetooth-adapter";
+pub static SETTING_NAME_FILTER: &'static str = "discovery-name-filter";
+static SETTING_AUTO_DISCOVERY: &'static str = "auto-discovery";
+static SETTING_SCAN_TIMEOUT: &'static str = "scan-timeout-seconds";
+static SETTING_RSSI_THRESHOLD: &'static str = "discovery-rssi-threshold";
+static SETTING_DUPLICATE_DATA: &'static str = "discovery-duplicate-data";
+static SETTING_RECONNECT_DELAY: &'static str = "reconnect-delay";
+static SETTING_RECONNECT_MAX_ATTEMPTS: &'static str = "reconnect-max-attempts";
+static SETTING_FIRMWARE_REPO: &'static str = "firmware-repo";
+static SETTING_DOWNLOAD_DIR: &'static str = "download-dir";
+static SETTING_DOWNLOAD_AUTO_SAVE: &'static str = "download-auto-save";
+static SETTING_COLOR_SCHEME: &'static str = "color-scheme";
+static SETTING_WINDOW_WIDTH: &'static str = "window-width";
+static SETTING_WINDOW_HEIGHT: &'static str = "window-height";
+static SETTING_LAST_VIEW: &'static str = "last-view";
+static SETTING_LOG_TO_FILE: &'static str = "log-to-file";
+static SETTING_VERBOSE_LOGGING: &'static str = "verbose-logging";
+static SETTING_DEVELOPER_MODE: &'static str = "developer-mode";
 
 static BROKER: relm4::MessageBroker<Input> = MessageBroker::new();
 
@@ -31,52 +68,148 @@ relm4::new_stateless_action!(DashboardViewAction, ViewActionGroup, "dashboard");
 relm4::new_stateless_action!(DevicesViewAction, ViewActionGroup, "devices");
 relm4::new_stateless_action!(SettingsViewAction, ViewActionGroup, "settings");
 relm4::new_stateless_action!(AboutAction, ViewActionGroup, "about");
+relm4::new_stateless_action!(ShortcutsAction, ViewActionGroup, "shortcuts");
 relm4::new_action_group!(WindowActionGroup, "win");
 relm4::new_stateless_action!(CloseAction, WindowActionGroup, "close");
 relm4::new_stateless_action!(QuitAction, WindowActionGroup, "quit");
+relm4::new_stateless_action!(ForgetDevicesAction, WindowActionGroup, "forget-devices");
+relm4::new_stateless_action!(RescanAction, WindowActionGroup, "rescan");
 
 
 #[derive(Debug)]
 enum Input {
     SetView(View),
+    DeviceConnecting,
     DeviceConnected(Arc<bluer::Device>),
+    DeviceConnectionFailed,
     DeviceDisconnected,
+    ConnectionEnded(devices_page::DisconnectReason),
+    Reconnecting(bool),
+    AdapterNames(Vec<String>),
+    StopReconnecting,
     DeviceReady(Arc<bt::InfiniTime>),
     DeviceRejected,
+    Connect,
+    Disconnect,
+    ForgetAllRequest,
+    ForgetAll,
     FlashAssetFromFile(PathBuf, fwupd_page::AssetType),
+    FlashAssetFromFiles(Vec<PathBuf>, fwupd_page::AssetType),
     FlashAssetFromUrl(String, fwupd_page::AssetType),
+    OtaProgress(fwupd_page::AssetType, u32),
+    OtaCompleted(fwupd_page::AssetType),
+    ResourcesInstalled(String),
+    OtaFailed(fwupd_page::AssetType, String),
+    FirmwareVersionExpected(String),
+    FirmwareValidationReminder,
+    FindHost(bt::AlertLevel),
     Toast(String),
-    ToastStatic(&'static str),
     ToastWithLink {
-        message: &'static str,
-        label: &'static str,
+        message: String,
+        label: String,
         url: &'static str,
     },
     WindowShown, // Temporary hack
+    StartDBusService,
     About,
+    Shortcuts,
+    Rescan,
     Close,
     Quit,
+    ShutdownComplete,
+    None,
+}
+
+#[derive(Debug)]
+enum CommandOutput {
+    DBusService(zbus::Result<zbus::Connection>),
+    SessionBus(zbus::Result<zbus::Connection>),
+    DesktopNotificationShown(u32),
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    start_in_background: bool,
+    dbus_service: bool,
 }
 
 struct Model {
     // UI state
     active_view: View,
     is_connected: bool,
+    reconnecting: bool,
+    quitting: bool,
     // Components
     dashboard_page: Controller<dashboard_page::Model>,
     devices_page: Controller<devices_page::Model>,
     fwupd_page: Controller<fwupd_page::Model>,
     settings_page: Controller<settings_page::Model>,
+    forget_all_warning: Controller<Alert>,
+    firmware_validation_reminder: Controller<Alert>,
     // Other
+    settings: gio::Settings,
     infinitime: Option<Arc<bt::InfiniTime>>,
+    // Release tag of the firmware being flashed, and of the flashed one
+    // which is yet to be confirmed after the watch reconnects
+    expected_fw_version: Option<String>,
+    unconfirmed_fw_version: Option<String>,
+    dbus_connection: Option<zbus::Connection>,
+    session_bus: Option<zbus::Connection>,
+    desktop_notification_id: u32,
     toast_overlay: adw::ToastOverlay,
     hide_on_startup: bool,  // Temporary hack
 }
 
+impl Model {
+    fn update_dbus_device(&self, infinitime: Option<Arc<bt::InfiniTime>>) {
+        if let Some(connection) = self.dbus_connection.clone() {
+            relm4::spawn(async move {
+                if let Err(error) = dbus::set_device(&connection, infinitime).await {
+                    log::error!("Failed to update D-Bus control service: {error}");
+                }
+            });
+        }
+    }
+
+    /// Show progress notification on the host if the window is hidden or
+    /// unfocused. Subsequent calls update the same notification.
+    fn notify_desktop(&self, sender: &ComponentSender<Self>, window: &adw::ApplicationWindow, summary: String) {
+        if window.is_visible() && window.is_active() {
+            return;
+        }
+        if let Some(connection) = self.session_bus.clone() {
+            let replaces_id = self.desktop_notification_id;
+            sender.oneshot_command(async move {
+                let result = notifications::send_desktop_notification(
+                    &connection, "Watchmate", APP_ID, replaces_id, &summary, ""
+                ).await;
+                match result {
+                    Ok(id) => CommandOutput::DesktopNotificationShown(id),
+                    Err(error) => {
+                        log::error!("Failed to show desktop notification: {error}");
+                        CommandOutput::DesktopNotificationShown(replaces_id)
+                    }
+                }
+            });
+        }
+    }
+
+    fn emit_dbus_update_finished(&self, atype: fwupd_page::AssetType, success: bool, message: String) {
+        if let Some(connection) = self.dbus_connection.clone() {
+            let asset = atype.name().to_lowercase();
+            relm4::spawn(async move {
+                if let Err(error) = dbus::emit_update_finished(&connection, &asset, success, &message).await {
+                    log::error!("Failed to emit D-Bus signal: {error}");
+                }
+            });
+        }
+    }
+}
+
 #[relm4::component]
 impl Component for Model {
-    type CommandOutput = ();
-    type Init = bool;
+    type CommandOutput = CommandOutput;
+    type Init = Options;
     type Input = Input;
     type Output = ();
     type Widgets = Widgets;
@@ -84,8 +217,6 @@ impl Component for Model {
     view! {
         #[name = "main_window"]
         adw::ApplicationWindow {
-            set_default_width: 480,
-            set_default_height: 720,
             set_hide_on_close: settings.boolean(SETTING_BACKGROUND),
 
             // Temporary hack
@@ -96,33 +227,47 @@ impl Component for Model {
                 // TODO: Use Relm 0.5 conditional widgets here (automatic stack)
                 // I can't make it work here for some reason for now.
                 #[wrap(Some)]
-                set_child = &gtk::Stack {
-                    add_named[Some("dashboard_view")] = &gtk::Box {
-                        // set_visible: watch!(components.dashboard.model.device.is_some()),
-                        append: model.dashboard_page.widget(),
+                set_child = &gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+
+                    adw::Banner {
+                        set_title: &gettext("Connection lost — Reconnecting…"),
+                        set_button_label: Some(&gettext("Stop")),
+                        #[watch]
+                        set_revealed: model.reconnecting,
+                        connect_button_clicked => Input::StopReconnecting,
                     },
-                    add_named[Some("devices_view")] = &gtk::Box {
-                        append: model.devices_page.widget(),
-                    },
-                    add_named[Some("fwupd_view")] = &gtk::Box {
-                        append: model.fwupd_page.widget(),
-                    },
-                    add_named[Some("settings_view")] = &gtk::Box {
-                        append: model.settings_page.widget(),
-                    },
-                    #[watch]
-                    set_visible_child_name: match model.active_view {
-                        View::Dashboard => "dashboard_view",
-                        View::Devices => "devices_view",
-                        View::FirmwareUpdate => "fwupd_view",
-                        View::Settings => "settings_view",
+
+                    gtk::Stack {
+                        set_vexpand: true,
+
+                        add_named[Some("dashboard_view")] = &gtk::Box {
+                            // set_visible: watch!(components.dashboard.model.device.is_some()),
+                            append: model.dashboard_page.widget(),
+                        },
+                        add_named[Some("devices_view")] = &gtk::Box {
+                            append: model.devices_page.widget(),
+                        },
+                        add_named[Some("fwupd_view")] = &gtk::Box {
+                            append: model.fwupd_page.widget(),
+                        },
+                        add_named[Some("settings_view")] = &gtk::Box {
+                            append: model.settings_page.widget(),
+                        },
+                        #[watch]
+                        set_visible_child_name: match model.active_view {
+                            View::Dashboard => "dashboard_view",
+                            View::Devices => "devices_view",
+                            View::FirmwareUpdate => "fwupd_view",
+                            View::Settings => "settings_view",
+                        },
                     },
                 },
             },
         }
     }
 
-    fn init(start_in_background: Self::Init, root: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
+    fn init(options: Self::Init, root: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
         let settings = gio::Settings::new(APP_ID);
 
         // Components
@@ -130,37 +275,106 @@ impl Component for Model {
             .launch((root.clone(), settings.clone()))
             .forward(&sender.input_sender(), |message| match message {
                 dashboard_page::Output::FlashAssetFromFile(file, atype) => Input::FlashAssetFromFile(file, atype),
+                dashboard_page::Output::FlashAssetFromFiles(files, atype) => Input::FlashAssetFromFiles(files, atype),
                 dashboard_page::Output::FlashAssetFromUrl(url, atype) => Input::FlashAssetFromUrl(url, atype),
             });
 
         let devices_page = devices_page::Model::builder()
             .launch(settings.clone())
             .forward(&sender.input_sender(), |message| match message {
+                devices_page::Output::DeviceConnecting => Input::DeviceConnecting,
                 devices_page::Output::DeviceConnected(device) => Input::DeviceConnected(device),
+                devices_page::Output::DeviceConnectionFailed => Input::DeviceConnectionFailed,
+                devices_page::Output::DeviceDisconnected(reason) => Input::ConnectionEnded(reason),
+                devices_page::Output::Reconnecting(active) => Input::Reconnecting(active),
+                devices_page::Output::AdapterNames(names) => Input::AdapterNames(names),
+                devices_page::Output::ShutdownComplete => Input::ShutdownComplete,
             });
 
         let fwupd_page = fwupd_page::Model::builder()
             .launch(())
-            .detach();
+            .forward(&sender.input_sender(), |message| match message {
+                fwupd_page::Output::OtaProgress(atype, percent) => Input::OtaProgress(atype, percent),
+                fwupd_page::Output::OtaCompleted(atype) => Input::OtaCompleted(atype),
+                fwupd_page::Output::ResourcesInstalled(version) => Input::ResourcesInstalled(version),
+                fwupd_page::Output::OtaFailed(atype, error) => Input::OtaFailed(atype, error),
+            });
 
         let settings_page = settings_page::Model::builder()
-            .launch(settings.clone())
+            .launch((root.clone(), settings.clone()))
             .detach();
 
+        let forget_all_warning = Alert::builder()
+            .transient_for(&root)
+            .launch(AlertSettings {
+                text: Some(gettext("Forget all devices?")),
+                secondary_text: Some(gettext(
+                    "The watch will be disconnected, the saved device will be forgotten, \
+                    and discovery will start from scratch."
+                )),
+                confirm_label: Some(gettext("Forget")),
+                cancel_label: Some(gettext("Cancel")),
+                option_label: None,
+                is_modal: true,
+                destructive_accept: true,
+                extra_child: None,
+            })
+            .forward(sender.input_sender(), |message| match message {
+                AlertResponse::Confirm => Input::ForgetAll,
+                AlertResponse::Cancel => Input::None,
+                AlertResponse::Option => Input::None,
+            });
+
+        // InfiniTime boots the new firmware in test mode, and only the user can
+        // validate it on the watch. There's no BLE command for that, nor a way
+        // to read the image state, so all we can do is remind about it
+        let firmware_validation_reminder = Alert::builder()
+            .transient_for(&root)
+            .launch(AlertSettings {
+                text: Some(gettext("Validate the new firmware")),
+                secondary_text: Some(gettext(
+                    "Open Settings → Firmware on the watch and validate the running version, \
+                    otherwise the watch reverts to the previous firmware on the next reboot."
+                )),
+                confirm_label: Some(gettext("OK")),
+                cancel_label: None,
+                option_label: None,
+                is_modal: true,
+                destructive_accept: false,
+                extra_child: None,
+            })
+            .forward(sender.input_sender(), |_| Input::None);
+
+        // Firmware update view requires explicit user action, so it's never restored
+        let active_view = match View::from_name(&settings.string(SETTING_LAST_VIEW)) {
+            Some(View::FirmwareUpdate) | None => View::Devices,
+            Some(view) => view,
+        };
+
         // Initialize model
         let model = Model {
             // UI state
-            active_view: View::Devices,
+            active_view,
             is_connected: false,
+            reconnecting: false,
+            quitting: false,
             // Components
             dashboard_page,
             devices_page,
             fwupd_page,
             settings_page,
+            forget_all_warning,
+            firmware_validation_reminder,
             // Other
+            settings: settings.clone(),
             infinitime: None,
+            expected_fw_version: None,
+            unconfirmed_fw_version: None,
+            dbus_connection: None,
+            session_bus: None,
+            desktop_notification_id: 0,
             toast_overlay: adw::ToastOverlay::new(),
-            hide_on_startup: start_in_background,
+            hide_on_startup: options.start_in_background,
         };
 
         // Widgets
@@ -168,15 +382,36 @@ impl Component for Model {
         let widgets = view_output!();
 
         // Settings
+        settings.bind(SETTING_WINDOW_WIDTH, &widgets.main_window, "default-width").build();
+        settings.bind(SETTING_WINDOW_HEIGHT, &widgets.main_window, "default-height").build();
+
         let window = widgets.main_window.clone();
         settings.connect_changed(Some(SETTING_BACKGROUND), move |settings, _| {
             window.set_hide_on_close(settings.boolean(SETTING_BACKGROUND));
         });
 
+        apply_color_scheme(&settings);
+        settings.connect_changed(Some(SETTING_COLOR_SCHEME), |settings, _| {
+            apply_color_scheme(settings);
+        });
+
+        apply_logging(&settings);
+        settings.connect_changed(Some(SETTING_LOG_TO_FILE), |settings, _| {
+            apply_logging(settings);
+        });
+        settings.connect_changed(Some(SETTING_VERBOSE_LOGGING), |settings, _| {
+            apply_logging(settings);
+        });
+
         // Actions
         let app = relm4::main_application();
         app.set_accelerators_for_action::<CloseAction>(&["<primary>W"]);
         app.set_accelerators_for_action::<QuitAction>(&["<primary>Q"]);
+        app.set_accelerators_for_action::<RescanAction>(&["<primary>R"]);
+        app.set_accelerators_for_action::<DashboardViewAction>(&["<primary>H"]);
+        app.set_accelerators_for_action::<DevicesViewAction>(&["<primary>D"]);
+        app.set_accelerators_for_action::<SettingsViewAction>(&["<primary>comma"]);
+        app.set_accelerators_for_action::<ShortcutsAction>(&["<primary>question"]);
 
         let mut view_group = RelmActionGroup::<ViewActionGroup>::new();
         view_group.add_action(RelmAction::<DashboardViewAction>::new_stateless(
@@ -199,6 +434,11 @@ impl Component for Model {
                 sender.input(Input::About);
             }
         )));
+        view_group.add_action(RelmAction::<ShortcutsAction>::new_stateless(
+            glib::clone!(#[strong] sender, move |_| {
+                sender.input(Input::Shortcuts);
+            }
+        )));
         view_group.register_for_widget(&widgets.main_window);
 
         let mut global_group = RelmActionGroup::<WindowActionGroup>::new();
@@ -212,8 +452,27 @@ impl Component for Model {
                 sender.input(Input::Close);
             }
         )));
+        global_group.add_action(RelmAction::<ForgetDevicesAction>::new_stateless(
+            glib::clone!(#[strong] sender, move |_| {
+                sender.input(Input::ForgetAllRequest);
+            }
+        )));
+        global_group.add_action(RelmAction::<RescanAction>::new_stateless(
+            glib::clone!(#[strong] sender, move |_| {
+                sender.input(Input::Rescan);
+            }
+        )));
         global_group.register_for_widget(&widgets.main_window);
 
+        if options.dbus_service {
+            sender.input(Input::StartDBusService);
+        }
+
+        // Session bus for host desktop notifications
+        sender.oneshot_command(async move {
+            CommandOutput::SessionBus(zbus::Connection::session().await)
+        });
+
         ComponentParts { model, widgets }
     }
 
@@ -226,14 +485,33 @@ impl Component for Model {
                         self.devices_page.emit(devices_page::Input::StopDiscovery);
                     }
                     if view == View::Devices {
-                        self.devices_page.emit(devices_page::Input::StartDiscovery);
+                        self.devices_page.emit(devices_page::Input::AutoStartDiscovery);
+                    }
+                    if view != View::FirmwareUpdate {
+                        _ = self.settings.set_string(SETTING_LAST_VIEW, view.name());
                     }
                     self.active_view = view;
                 }
             }
+            Input::DeviceConnecting => {
+                self.dashboard_page.emit(dashboard_page::Input::Connecting);
+            }
+            Input::DeviceConnectionFailed => {
+                self.dashboard_page.emit(dashboard_page::Input::ConnectionFailed);
+            }
+            Input::Reconnecting(active) => {
+                self.reconnecting = active;
+            }
+            Input::StopReconnecting => {
+                self.devices_page.emit(devices_page::Input::StopReconnecting);
+            }
+            Input::AdapterNames(names) => {
+                self.settings_page.emit(settings_page::Input::AdapterNames(names));
+            }
             Input::DeviceConnected(device) => {
                 log::info!("Device connected: {}", device.address());
                 self.is_connected = true;
+                self.dashboard_page.emit(dashboard_page::Input::Connecting);
                 relm4::spawn(async move {
                     match bt::InfiniTime::new(device).await {
                         Ok(infinitime) => {
@@ -242,20 +520,32 @@ impl Component for Model {
                         Err(error) => {
                             sender.input(Input::DeviceRejected);
                             log::error!("Device is rejected: {}", error);
-                            sender.input(Input::ToastStatic("Device is rejected by the app"));
+                            sender.input(Input::Toast(gettext_f("Device is rejected: {error}", &[("error", &error.to_string())])));
                         }
                     }
                 });
             }
             Input::DeviceDisconnected => {
                 log::info!("PineTime disconnected");
+                if self.quitting {
+                    return;
+                }
                 if let Some(infinitime) = self.infinitime.take() {
-                    self.devices_page.emit(devices_page::Input::DeviceConnectionLost(infinitime.device().address()));
+                    let address = infinitime.device().address();
+                    self.devices_page.emit(devices_page::Input::DeviceConnectionEnded(address));
                 }
                 self.dashboard_page.emit(dashboard_page::Input::Disconnected);
                 self.fwupd_page.emit(fwupd_page::Input::Disconnected);
+                self.update_dbus_device(None);
                 sender.input(Input::SetView(View::Devices));
             }
+            Input::ConnectionEnded(reason) => {
+                let message = match reason {
+                    devices_page::DisconnectReason::Requested => gettext("Disconnected"),
+                    devices_page::DisconnectReason::Lost => gettext("Connection lost"),
+                };
+                sender.input(Input::Toast(message));
+            }
             Input::DeviceReady(infinitime) => {
                 log::info!("PineTime recognized");
                 self.infinitime = Some(infinitime.clone());
@@ -264,6 +554,23 @@ impl Component for Model {
                 }
                 self.dashboard_page.emit(dashboard_page::Input::Connected(infinitime.clone()));
                 self.fwupd_page.emit(fwupd_page::Input::Connected(infinitime.clone()));
+                self.update_dbus_device(Some(infinitime.clone()));
+                if let Some(expected) = self.unconfirmed_fw_version.take() {
+                    let infinitime = infinitime.clone();
+                    let sender = sender.clone();
+                    relm4::spawn(async move {
+                        match bt::retry_with_timeout(|| infinitime.firmware_version()).await {
+                            Ok(version) => match verify_firmware_version(&expected, &version) {
+                                Ok(message) => {
+                                    sender.input(Input::Toast(message));
+                                    sender.input(Input::FirmwareValidationReminder);
+                                }
+                                Err(message) => sender.input(Input::Toast(message)),
+                            }
+                            Err(error) => log::error!("Failed to read firmware version after update: {error}"),
+                        }
+                    });
+                }
                 // Handle disconnection
                 relm4::spawn(async move {
                     match infinitime.get_property_stream().await {
@@ -280,26 +587,96 @@ impl Component for Model {
                 });
             }
             Input::DeviceRejected => {
+                self.dashboard_page.emit(dashboard_page::Input::ConnectionFailed);
                 self.devices_page.emit(devices_page::Input::StartDiscovery);
             }
+            Input::Connect => {
+                self.devices_page.emit(devices_page::Input::ConnectSaved);
+            }
+            Input::Disconnect => {
+                if let Some(infinitime) = &self.infinitime {
+                    self.devices_page.emit(devices_page::Input::Disconnect(infinitime.device().address()));
+                }
+            }
+            Input::ForgetAllRequest => {
+                self.forget_all_warning.emit(AlertMsg::Show);
+            }
+            Input::ForgetAll => {
+                self.devices_page.emit(devices_page::Input::ForgetAll);
+            }
             Input::FlashAssetFromFile(file, atype) => {
+                self.desktop_notification_id = 0;
+                self.notify_desktop(&sender, root, gettext_f("{asset} update started", &[("asset", &atype.display_name())]));
                 self.fwupd_page.emit(fwupd_page::Input::FlashAssetFromFile(file, atype));
                 sender.input(Input::SetView(View::FirmwareUpdate));
             }
+            Input::FlashAssetFromFiles(files, atype) => {
+                self.desktop_notification_id = 0;
+                self.notify_desktop(&sender, root, gettext_f("{asset} update started", &[("asset", &atype.display_name())]));
+                self.fwupd_page.emit(fwupd_page::Input::FlashAssetFromFiles(files, atype));
+                sender.input(Input::SetView(View::FirmwareUpdate));
+            }
             Input::FlashAssetFromUrl(url, atype) => {
+                self.desktop_notification_id = 0;
+                self.notify_desktop(&sender, root, gettext_f("{asset} update started", &[("asset", &atype.display_name())]));
                 self.fwupd_page.emit(fwupd_page::Input::FlashAssetFromUrl(url, atype));
                 sender.input(Input::SetView(View::FirmwareUpdate));
             }
+            Input::OtaProgress(atype, percent) => {
+                let summary = gettext_f("{asset} update: {percent}%", &[
+                    ("asset", &atype.display_name()), ("percent", &percent.to_string())
+                ]);
+                self.notify_desktop(&sender, root, summary);
+            }
+            Input::OtaCompleted(atype) => {
+                if matches!(atype, fwupd_page::AssetType::Firmware) {
+                    // The watch reboots into the new firmware, check it once reconnected
+                    self.unconfirmed_fw_version = self.expected_fw_version.take();
+                }
+                log::info!("Firmware update finished: asset={} result=success", atype.name().to_lowercase());
+                self.notify_desktop(&sender, root, gettext_f("{asset} update complete", &[("asset", &atype.display_name())]));
+                self.emit_dbus_update_finished(atype, true, String::new());
+            }
+            Input::OtaFailed(atype, error) => {
+                if matches!(atype, fwupd_page::AssetType::Firmware) {
+                    self.expected_fw_version = None;
+                }
+                log::info!("Firmware update finished: asset={} result=failure error={:?}", atype.name().to_lowercase(), error);
+                let summary = gettext_f("{asset} update failed: {error}", &[
+                    ("asset", &atype.display_name()), ("error", &error)
+                ]);
+                self.notify_desktop(&sender, root, summary);
+                self.emit_dbus_update_finished(atype, false, error);
+            }
+            Input::ResourcesInstalled(version) => {
+                if let Some(infinitime) = &self.infinitime {
+                    let address = infinitime.device().address().to_string();
+                    set_resources_version(&self.settings, &address, &version);
+                }
+            }
+            Input::FirmwareVersionExpected(version) => {
+                self.expected_fw_version = Some(version);
+            }
+            Input::FirmwareValidationReminder => {
+                self.firmware_validation_reminder.emit(AlertMsg::Show);
+            }
+            Input::FindHost(level) => {
+                log::info!("Watch alert level: {level:?}");
+                if level != bt::AlertLevel::None {
+                    root.display().beep();
+                    let message = gettext("The watch is looking for this computer");
+                    self.desktop_notification_id = 0;
+                    self.notify_desktop(&sender, root, message.clone());
+                    self.toast_overlay.add_toast(adw::Toast::new(&message));
+                }
+            }
             Input::Toast(message) => {
                 self.toast_overlay.add_toast(adw::Toast::new(&message));
             }
-            Input::ToastStatic(message) => {
-                self.toast_overlay.add_toast(adw::Toast::new(message));
-            }
             Input::ToastWithLink { message, label, url } => {
-                let toast = adw::Toast::new(message);
+                let toast = adw::Toast::new(&message);
                 let root = root.clone();
-                toast.set_button_label(Some(label));
+                toast.set_button_label(Some(&label));
                 toast.connect_button_clicked(move |_| {
                     gtk::UriLauncher::new(&url)
                         .launch(Some(&root), gio::Cancellable::NONE, |_| ());
@@ -314,6 +691,11 @@ impl Component for Model {
                 }
                 self.hide_on_startup = false;
             }
+            Input::StartDBusService => {
+                sender.oneshot_command(async move {
+                    CommandOutput::DBusService(dbus::start_control_service().await)
+                });
+            }
             Input::About => {
                 adw::AboutWindow::builder()
                     .transient_for(root)
@@ -326,16 +708,158 @@ impl Component for Model {
                     .build()
                     .present();
             }
+            Input::Shortcuts => {
+                shortcuts::window(root).present();
+            }
+            Input::Rescan => {
+                self.devices_page.emit(devices_page::Input::StopDiscovery);
+                self.devices_page.emit(devices_page::Input::StartDiscovery);
+                sender.input(Input::SetView(View::Devices));
+            }
             Input::Close => {
                 root.close();
             }
             Input::Quit => {
+                if !self.quitting {
+                    self.quitting = true;
+                    self.dashboard_page.emit(dashboard_page::Input::Disconnected);
+                    let disconnect = self.settings.boolean(SETTING_DISCONNECT_ON_QUIT);
+                    self.devices_page.emit(devices_page::Input::Shutdown(disconnect));
+                }
+            }
+            Input::ShutdownComplete => {
                 root.application().unwrap().quit();
             }
+            Input::None => {}
         }
     }
+
+    fn update_cmd(&mut self, msg: Self::CommandOutput, _sender: ComponentSender<Self>, _root: &Self::Root) {
+        match msg {
+            CommandOutput::SessionBus(result) => match result {
+                Ok(connection) => {
+                    self.session_bus = Some(connection);
+                }
+                Err(error) => {
+                    log::error!("Failed to establish D-Bus session connection: {error}");
+                }
+            }
+            CommandOutput::DesktopNotificationShown(id) => {
+                self.desktop_notification_id = id;
+            }
+            CommandOutput::DBusService(result) => match result {
+                Ok(connection) => {
+                    log::info!("D-Bus control service started: {}", dbus::BUS_NAME);
+                    self.dbus_connection = Some(connection);
+                    self.update_dbus_device(self.infinitime.clone());
+                }
+                Err(error) => {
+                    log::error!("Failed to start D-Bus control service: {error}");
+                    self.toast_overlay.add_toast(adw::Toast::new(&gettext("Failed to start D-Bus control service")));
+                }
+            }
+        }
+    }
+}
+
+
+
+/// Follow the system color scheme or override it, according to the settings
+fn apply_color_scheme(settings: &gio::Settings) {
+    let scheme = match settings.string(SETTING_COLOR_SCHEME).as_str() {
+        "light" => adw::ColorScheme::ForceLight,
+        "dark" => adw::ColorScheme::ForceDark,
+        _ => adw::ColorScheme::Default,
+    };
+    adw::StyleManager::default().set_color_scheme(scheme);
+}
+
+/// Directory to save downloads to: the configured one,
+/// or XDG downloads directory by default
+fn download_dir(settings: &gio::Settings) -> PathBuf {
+    match settings.string(SETTING_DOWNLOAD_DIR).as_str() {
+        "" => glib::user_special_dir(glib::UserDirectory::Downloads)
+            .unwrap_or_else(|| glib::home_dir().join("Downloads")),
+        dir => PathBuf::from(dir),
+    }
+}
+
+/// Write logs to file and raise verbosity, according to the settings
+fn apply_logging(settings: &gio::Settings) {
+    logging::set_verbose(settings.boolean(SETTING_VERBOSE_LOGGING));
+    if let Err(error) = logging::set_file_enabled(settings.boolean(SETTING_LOG_TO_FILE)) {
+        log::error!("Failed to open log file: {error}");
+    }
+}
+
+/// Value of the device with `address` from "address=value" entries of `key`
+fn device_entry(settings: &gio::Settings, key: &str, address: &str) -> Option<String> {
+    settings.strv(key).iter()
+        .filter_map(|entry| entry.as_str().split_once('='))
+        .find(|(a, _)| a.eq_ignore_ascii_case(address))
+        .map(|(_, value)| value.to_string())
+}
+
+/// Set value of the device with `address` in "address=value" entries
+/// of `key`, empty one removes it
+fn set_device_entry(settings: &gio::Settings, key: &str, address: &str, value: &str) {
+    let mut entries = settings.strv(key).iter()
+        .map(|entry| entry.to_string())
+        .filter(|entry| !entry.split_once('=').is_some_and(|(a, _)| a.eq_ignore_ascii_case(address)))
+        .collect::<Vec<_>>();
+    let value = value.trim();
+    if !value.is_empty() {
+        entries.push(format!("{address}={value}"));
+    }
+    if let Err(error) = settings.set_strv(key, entries) {
+        log::error!("Failed to save {key}: {error}");
+    }
 }
 
+/// Locally set nickname of the device with `address`, if any
+fn device_nickname(settings: &gio::Settings, address: &str) -> Option<String> {
+    device_entry(settings, SETTING_DEVICE_NICKNAMES, address)
+}
+
+/// Set nickname of the device with `address`, empty one removes it
+fn set_device_nickname(settings: &gio::Settings, address: &str, nickname: &str) {
+    set_device_entry(settings, SETTING_DEVICE_NICKNAMES, address, nickname);
+}
+
+/// Version of the resources last flashed to the device with `address`, if known
+fn resources_version(settings: &gio::Settings, address: &str) -> Option<String> {
+    device_entry(settings, SETTING_RESOURCES_VERSIONS, address)
+}
+
+fn set_resources_version(settings: &gio::Settings, address: &str, version: &str) {
+    set_device_entry(settings, SETTING_RESOURCES_VERSIONS, address, version);
+}
+
+/// User-facing message for a failed GitHub request: a specific one
+/// if the rate limit is reached, `fallback` otherwise
+pub fn github_error_message(error: &anyhow::Error, fallback: String) -> String {
+    match error.downcast_ref::<gh::RateLimited>() {
+        Some(limit) => gettext_f("GitHub rate limit reached, try again in {minutes} minutes", &[
+            ("minutes", &limit.minutes().to_string())
+        ]),
+        None => fallback,
+    }
+}
+
+/// Compare the running firmware version with the flashed release tag,
+/// returning the message for the user, or an error message on mismatch
+fn verify_firmware_version(expected: &str, running: &str) -> Result<String, String> {
+    let normalize = |version: &str| version.trim().trim_start_matches('v').to_string();
+    if normalize(expected) == normalize(running) {
+        log::info!("Firmware update confirmed: {running}");
+        Ok(gettext_f("Updated to {version}, confirmed", &[("version", running)]))
+    } else {
+        log::warn!("Firmware version mismatch after update: expected {expected}, running {running}");
+        Err(gettext_f("Firmware update not confirmed: expected {expected}, running {running}", &[
+            ("expected", expected), ("running", running)
+        ]))
+    }
+}
 
 
 #[derive(Debug, PartialEq)]
@@ -346,8 +870,31 @@ pub enum View {
     Settings,
 }
 
+impl View {
+    fn name(&self) -> &'static str {
+        match self {
+            View::Dashboard => "dashboard",
+            View::Devices => "devices",
+            View::FirmwareUpdate => "fwupd",
+            View::Settings => "settings",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dashboard" => Some(View::Dashboard),
+            "devices" => Some(View::Devices),
+            "fwupd" => Some(View::FirmwareUpdate),
+            "settings" => Some(View::Settings),
+            _ => None,
+        }
+    }
+}
+
 
 pub fn run() {
+    i18n::init();
+
     // Init GTK before libadwaita (ToastOverlay)
     gtk::init().unwrap();
 
@@ -358,14 +905,17 @@ pub fn run() {
     );
 
     // Handle CLI args
-    let known_args = ["--background"];
+    let known_args = ["--background", "--dbus"];
     let (local_args, other_args): (Vec<_>, Vec<_>) = env::args()
         .partition(|a| known_args.contains(&a.as_str()));
-    let start_in_background = local_args.contains(&String::from("--background"));
+    let options = Options {
+        start_in_background: local_args.contains(&String::from("--background")),
+        dbus_service: local_args.contains(&String::from("--dbus")),
+    };
 
     // Run app
     RelmApp::new(APP_ID)
         .with_args(other_args)
         .with_broker(&BROKER)
-        .run::<Model>(start_in_background);
+        .run::<Model>(options);
 }
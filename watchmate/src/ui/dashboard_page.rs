@@ -1,135 +1,552 @@
-use crate::ui::{self, fwupd_page::AssetType};
-use infinitime::{tokio, bt};
+use crate::{i18n::{format_number, gettext, gettext_f}, ui::{self, fwupd_page::AssetType}};
+use infinitime::{chrono, tokio, bt, fdo::upower, forecast, zbus};
 
-use std::{sync::Arc, path::PathBuf};
-use futures::{stream, StreamExt};
-use gtk::prelude::{BoxExt, ButtonExt, OrientableExt, ListBoxRowExt, WidgetExt};
+use std::{sync::Arc, path::PathBuf, rc::Rc, cell::Cell, cmp::Ordering, f64::consts::PI, time::{Duration, Instant}};
+use futures::StreamExt;
+use gtk::prelude::{BoxExt, ButtonExt, DisplayExt, DrawingAreaExtManual, FileExt, OrientableExt, ListBoxRowExt, SettingsExt, StaticType, WidgetExt};
 use adw::prelude::{PreferencesRowExt, ExpanderRowExt};
-use relm4::{adw, gtk::{self, gio}, ComponentController, ComponentParts, ComponentSender, Component, Controller, JoinHandle, RelmWidgetExt};
+use relm4::{
+    adw, gtk::{self, gio, glib},
+    ComponentController, ComponentParts, ComponentSender, Component, Controller, JoinHandle, RelmWidgetExt
+};
+use relm4_components::alert::{Alert, AlertMsg, AlertResponse, AlertSettings};
 use anyhow::{Result, Context};
-use version_compare::Version;
+use ashpd::desktop::background::BackgroundProxy;
 
 mod media_player;
 mod fwupd;
+mod gatt_inspector;
 mod notifications;
 
 
 #[derive(Debug)]
 pub enum Input {
+    Connecting,
+    ConnectionFailed,
     Connected(Arc<bt::InfiniTime>),
     Disconnected,
     LatestFirmwareVersion(Option<String>),
     FlashAssetFromFile(PathBuf, AssetType),
+    FlashAssetFromFiles(Vec<PathBuf>, AssetType),
     FlashAssetFromUrl(String, AssetType),
+    FileDropped(PathBuf),
+    DroppedFileTypeSelected(AssetType),
     BatteryLevel(u8),
-    HeartRate(u8),
+    HeartRate(bt::HeartRate),
     StepCount(u32),
+    StepGoal(u32),
+    ForwardHostBattery(bool),
+    Weather(Option<(forecast::Provider, forecast::Location)>),
+    RunInBackground(bool),
     Alias(String),
     Address(String),
+    NicknamesChanged,
+    EditNickname,
+    SetNickname(String),
     FirmwareVersion(String),
+    ResourcesVersionsChanged,
+    HardwareRevision(Option<String>),
+    ModelNumber(Option<String>),
+    Manufacturer(Option<String>),
+    TimeOffset(Option<i64>),
+    SyncTime,
+    FindWatch,
+    FindWatchStopped,
+    LinkLatency(Duration),
+    DeveloperMode(bool),
+    InspectGatt,
+    UpdateToLatest,
+    UpdateResources,
+    CopyInfo(InfoField),
+    None,
+}
+
+/// Notification counter of a single sensor, for diagnostics
+struct SensorStats {
+    name: &'static str,
+    // Notifications since the last summary
+    count: u32,
+    last_event: Instant,
+    silence_reported: bool,
+}
+
+impl SensorStats {
+    fn new(name: &'static str) -> Self {
+        Self { name, count: 0, last_event: Instant::now(), silence_reported: false }
+    }
+
+    fn record(&mut self) {
+        if self.silence_reported {
+            log::info!("Notifications from {} sensor resumed", self.name);
+        }
+        self.count += 1;
+        self.last_event = Instant::now();
+        self.silence_reported = false;
+    }
+
+    /// Reset the counter, warning once if the sensor is silent for too long
+    fn check_silence(&mut self) {
+        self.count = 0;
+        let silence = self.last_event.elapsed();
+        if silence >= SENSOR_SILENCE_WARNING && !self.silence_reported {
+            log::warn!("No notifications from {} sensor for {} min", self.name, silence.as_secs() / 60);
+            self.silence_reported = true;
+        }
+    }
+}
+
+/// System info values which can be copied to the clipboard
+#[derive(Debug, Clone, Copy)]
+pub enum InfoField {
+    Name,
+    Address,
+    FirmwareVersion,
 }
 
 #[derive(Debug)]
 pub enum Output {
     FlashAssetFromFile(PathBuf, AssetType),
+    FlashAssetFromFiles(Vec<PathBuf>, AssetType),
     FlashAssetFromUrl(String, AssetType),
 }
 
+/// Battery level bar is colored as low below and as high above these levels
+const BATTERY_LOW: u8 = 20;
+const BATTERY_HIGH: u8 = 80;
+/// How often to measure the connection latency
+const LATENCY_INTERVAL: Duration = Duration::from_secs(5);
+/// How often to log the number of sensor notifications received
+const SENSOR_STATS_INTERVAL: Duration = Duration::from_secs(60);
+/// Warn if a sensor sends nothing for this long. Values are only notified
+/// when they change, so it's a hint for debugging rather than an error
+const SENSOR_SILENCE_WARNING: Duration = Duration::from_secs(60 * 60);
+/// Minimum interval between background status updates, heart rate
+/// notifications are too frequent to forward each of them to the portal
+const BACKGROUND_STATUS_INTERVAL: Duration = Duration::from_secs(60);
+/// Stop ringing the watch after this long, unless stopped manually
+const FIND_WATCH_TIMEOUT: Duration = Duration::from_secs(30);
+
 pub struct Model {
     // UI state
     // - InfiniTime data
     battery_level: Option<u8>,
-    heart_rate: Option<u8>,
+    heart_rate: Option<bt::HeartRate>,
     step_count: Option<u32>,
+    step_goal: u32,
+    step_goal_reached: bool,
     alias: Option<String>,
+    // Locally set name, shown instead of the alias
+    nickname: Option<String>,
     address: Option<String>,
     fw_version: Option<String>,
     fw_latest: Option<String>,
     fw_update_available: bool,
+    // Version of the installed resources, if WatchMate recorded it
+    resources_version: Option<String>,
+    resources_outdated: bool,
+    // Round-trip time of a characteristic read
+    link_latency: Option<Duration>,
+    // Device Information fields, inner `None` if the watch doesn't provide them
+    hw_revision: Option<Option<String>>,
+    model_number: Option<Option<String>>,
+    manufacturer: Option<Option<String>>,
+    // Watch clock minus host clock in seconds, inner `None` if it can't be read
+    time_offset: Option<Option<i64>>,
+    // - Connection state
+    is_connecting: bool,
     // Components
     player_panel: Controller<media_player::Model>,
+    gatt_inspector: Controller<gatt_inspector::Model>,
     notifications_panel: Controller<notifications::Model>,
     firmware_panel: Controller<fwupd::Model>,
+    dropped_file_prompt: Controller<Alert>,
+    step_ring: gtk::DrawingArea,
+    step_progress: Rc<Cell<f64>>,
     // Other
     infinitime: Option<Arc<bt::InfiniTime>>,
     data_task: Option<JoinHandle<()>>,
+    latency_task: Option<JoinHandle<()>>,
+    dropped_file: Option<PathBuf>,
+    forward_host_battery: bool,
+    developer_mode: bool,
+    run_in_background: bool,
+    background_status_updated: Option<Instant>,
+    host_battery_task: Option<JoinHandle<()>>,
+    weather: Option<(forecast::Provider, forecast::Location)>,
+    weather_task: Option<JoinHandle<()>>,
+    find_watch_task: Option<JoinHandle<()>>,
+    settings: gio::Settings,
 }
 
 impl Model {
-    async fn read_info(infinitime: Arc<bt::InfiniTime>, sender: ComponentSender<Self>) {
-        let send_checked = |res: Result<Input>| match res {
-            Ok(msg) => {
-                sender.input(msg);
-            }
-            Err(error) => {
-                log::error!("{}: {}", &error, error.root_cause());
-                ui::BROKER.send(ui::Input::Toast(format!("{}", error)));
-            }
-        };
+    fn start_host_battery_task(&mut self) {
+        if let Some(infinitime) = self.infinitime.clone() {
+            self.stop_host_battery_task();
+            log::info!("Host battery session started");
+            self.host_battery_task = Some(relm4::spawn(async move {
+                let result = match zbus::Connection::system().await {
+                    Ok(connection) => upower::run_host_battery_session(&connection, &infinitime).await,
+                    Err(error) => Err(error.into()),
+                };
+                if let Err(error) = result {
+                    log::error!("Host battery session failed: {error}");
+                }
+            }));
+        }
+    }
 
-        sender.input(Input::Address(infinitime.device().address().to_string()));
+    fn start_weather_task(&mut self) {
+        if let (Some(infinitime), Some((provider, location))) = (self.infinitime.clone(), self.weather.clone()) {
+            self.stop_weather_task();
+            log::info!("Weather session started");
+            self.weather_task = Some(relm4::spawn(async move {
+                forecast::run_weather_session(&infinitime, provider, &location).await;
+            }));
+        }
+    }
 
-        send_checked(infinitime.device().alias().await
-            .map(Input::Alias)
-            .context("Failed to read alias"));
+    fn stop_weather_task(&mut self) {
+        if self.weather_task.take().map(|h| h.abort()).is_some() {
+            log::info!("Weather session stopped");
+        }
+    }
 
-        send_checked(infinitime.read_firmware_version().await
-            .map(Input::FirmwareVersion)
-            .context("Failed to read firmware version"));
+    fn display_name(&self) -> Option<&String> {
+        self.nickname.as_ref().or(self.alias.as_ref())
+    }
 
-        send_checked(infinitime.read_battery_level().await
-            .map(Input::BatteryLevel)
-            .context("Failed to read battery level"));
+    fn update_nickname(&mut self) {
+        self.nickname = self.address.as_ref()
+            .and_then(|address| ui::device_nickname(&self.settings, address));
+    }
 
-        send_checked(infinitime.read_heart_rate().await
-            .map(Input::HeartRate)
-            .context("Failed to read heart rate"));
+    fn update_resources_version(&mut self) {
+        self.resources_version = self.address.as_ref()
+            .and_then(|address| ui::resources_version(&self.settings, address));
+        self.check_resources_outdated();
+    }
 
-        send_checked(infinitime.read_step_count().await
-            .map(Input::StepCount)
-            .context("Failed to read step count"));
+    fn show_nickname_dialog(&self, sender: &ComponentSender<Self>) {
+        let entry = gtk::Entry::builder()
+            .text(self.nickname.as_deref().unwrap_or_default())
+            .placeholder_text(self.alias.as_deref().unwrap_or_default())
+            .activates_default(true)
+            .build();
+        let dialog = adw::MessageDialog::builder()
+            .heading(gettext("Device Nickname"))
+            .body(gettext("Shown in WatchMate instead of the Bluetooth name. Leave empty to use the Bluetooth name"))
+            .extra_child(&entry)
+            .modal(true)
+            .build();
+        dialog.set_transient_for(relm4::main_application().active_window().as_ref());
+        dialog.add_responses(&[("cancel", gettext("Cancel").as_str()), ("save", gettext("Save").as_str())]);
+        dialog.set_response_appearance("save", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("save"));
+        dialog.set_close_response("cancel");
+        dialog.connect_response(Some("save"), glib::clone!(#[strong] sender, move |_, _| {
+            sender.input(Input::SetNickname(entry.text().to_string()));
+        }));
+        dialog.present();
     }
 
-    async fn run_info_listener(infinitime: Arc<bt::InfiniTime>, sender: ComponentSender<Self>) {
-        let log_error = |err| {
-            log::error!("Failed to create data stream: {}", &err);
-            err
+    /// Weather provider and location, `None` if the weather is disabled
+    fn weather_settings(settings: &gio::Settings) -> Option<(forecast::Provider, forecast::Location)> {
+        let provider = forecast::Provider::from_name(&settings.string(ui::SETTING_WEATHER_PROVIDER))?;
+        let location = forecast::Location {
+            name: settings.string(ui::SETTING_WEATHER_LOCATION).to_string(),
+            latitude: settings.double(ui::SETTING_WEATHER_LATITUDE),
+            longitude: settings.double(ui::SETTING_WEATHER_LONGITUDE),
         };
+        Some((provider, location))
+    }
 
-        let mut bl_stream = infinitime.get_battery_level_stream().await
-            .map_err(log_error)
-            .map(StreamExt::boxed)
-            .unwrap_or(stream::empty().boxed());
+    /// Summary of the latest readings for the background status
+    fn background_status_text(&self) -> String {
+        if self.infinitime.is_none() {
+            return gettext("Watch is not connected");
+        }
+        let mut parts = Vec::new();
+        if let Some(soc) = self.battery_level {
+            parts.push(gettext_f("Battery {level}%", &[("level", &soc.to_string())]));
+        }
+        if let Some(bt::HeartRate::Bpm(rate)) = self.heart_rate {
+            parts.push(gettext_f("{rate} BPM", &[("rate", &rate.to_string())]));
+        }
+        if let Some(count) = self.step_count {
+            parts.push(gettext_f("{count} steps", &[("count", &count.to_string())]));
+        }
+        match parts.is_empty() {
+            true => gettext("Watch is connected"),
+            false => parts.join(" · "),
+        }
+    }
+
+    /// Show the latest readings in the system list of background apps
+    /// (e.g. GNOME quick settings) while running in background
+    fn update_background_status(&mut self, force: bool) {
+        if !self.run_in_background {
+            return;
+        }
+        let throttled = self.background_status_updated
+            .is_some_and(|t| t.elapsed() < BACKGROUND_STATUS_INTERVAL);
+        if throttled && !force {
+            return;
+        }
+        self.background_status_updated = Some(Instant::now());
+        let status = self.background_status_text();
+        relm4::spawn(async move {
+            let result = async {
+                BackgroundProxy::new().await?.set_status(&status).await
+            }.await;
+            if let Err(error) = result {
+                // Not supported by older portal versions
+                log::debug!("Failed to set background status: {error}");
+            }
+        });
+    }
+
+    /// Periodically time a cheap characteristic read to indicate the link quality
+    fn start_latency_task(&mut self, sender: &ComponentSender<Self>) {
+        if let Some(infinitime) = self.infinitime.clone() {
+            let sender = sender.clone();
+            self.latency_task.take().map(|h| h.abort());
+            self.latency_task = Some(relm4::spawn(async move {
+                loop {
+                    let start = Instant::now();
+                    match infinitime.read_battery_level().await {
+                        Ok(_) => sender.input(Input::LinkLatency(start.elapsed())),
+                        Err(error) => log::debug!("Failed to measure link latency: {error}"),
+                    }
+                    tokio::time::sleep(LATENCY_INTERVAL).await;
+                }
+            }));
+        }
+    }
 
-        let mut hr_stream = infinitime.get_heart_rate_stream().await
-            .map_err(log_error)
-            .map(StreamExt::boxed)
-            .unwrap_or(stream::empty().boxed());
+    fn stop_host_battery_task(&mut self) {
+        if self.host_battery_task.take().map(|h| h.abort()).is_some() {
+            log::info!("Host battery session stopped");
+        }
+    }
 
-        let mut sc_stream = infinitime.get_step_count_stream().await
-            .map_err(log_error)
-            .map(StreamExt::boxed)
-            .unwrap_or(stream::empty().boxed());
+    /// Device Information fields are optional, so their absence is only logged
+    fn optional_info(result: Result<String, bt::InfiniTimeError>, name: &str) -> Option<String> {
+        match result {
+            Ok(value) => Some(value),
+            Err(bt::InfiniTimeError::CharacteristicNotFound(_)) => {
+                log::info!("Device doesn't provide {name}");
+                None
+            }
+            Err(error) => {
+                log::warn!("Failed to read {name}: {error}");
+                None
+            }
+        }
+    }
 
+    /// Label for an optional Device Information field
+    fn info_label(value: &Option<Option<String>>) -> String {
+        match value {
+            Some(Some(value)) => value.clone(),
+            Some(None) => gettext("Unavailable"),
+            None => gettext("Loading..."),
+        }
+    }
+
+    fn time_offset_label(&self) -> String {
+        match self.time_offset {
+            // The watch clock has 1s resolution
+            Some(Some(offset)) if offset.abs() <= 1 => gettext("In sync"),
+            Some(Some(offset)) if offset < 0 => {
+                gettext_f("Watch is {seconds}s behind", &[("seconds", &(-offset).to_string())])
+            }
+            Some(Some(offset)) => {
+                gettext_f("Watch is {seconds}s ahead", &[("seconds", &offset.to_string())])
+            }
+            Some(None) => gettext("Unavailable"),
+            None => gettext("Loading..."),
+        }
+    }
+
+    async fn read_time_offset(infinitime: &bt::InfiniTime) -> Option<i64> {
+        match infinitime.read_current_time().await {
+            Ok(watch_time) => {
+                let offset = watch_time - chrono::Local::now().naive_local();
+                Some((offset.num_milliseconds() as f64 / 1000.0).round() as i64)
+            }
+            Err(error) => {
+                log::warn!("Failed to read watch time: {error}");
+                None
+            }
+        }
+    }
+
+    async fn read_info(infinitime: Arc<bt::InfiniTime>, sender: ComponentSender<Self>) {
+        let send_checked = |res: Result<Input>| match res {
+            Ok(msg) => {
+                sender.input(msg);
+            }
+            Err(error) => match error.downcast_ref::<bt::InfiniTimeError>() {
+                // Older firmware might lack some services, that's not worth a toast
+                Some(bt::InfiniTimeError::CharacteristicNotFound(_)) => {
+                    log::warn!("{}: {}", &error, error.root_cause());
+                }
+                _ => {
+                    log::error!("{}: {}", &error, error.root_cause());
+                    ui::BROKER.send(ui::Input::Toast(format!("{}", error)));
+                }
+            }
+        };
+
+        sender.input(Input::Address(infinitime.device().address().to_string()));
+
+        // Reads are independent, so issue them concurrently and
+        // send each value as soon as it arrives
+        let start = Instant::now();
+        futures::join!(
+            async {
+                send_checked(infinitime.device().alias().await
+                    .map(Input::Alias)
+                    .context("Failed to read alias"));
+            },
+            async {
+                send_checked(bt::retry_with_timeout(|| infinitime.firmware_version()).await
+                    .map(Input::FirmwareVersion)
+                    .context("Failed to read firmware version"));
+            },
+            async {
+                sender.input(Input::TimeOffset(Self::read_time_offset(&infinitime).await));
+            },
+            async {
+                let result = bt::retry_with_timeout(|| infinitime.read_hardware_revision()).await;
+                sender.input(Input::HardwareRevision(Self::optional_info(result, "hardware revision")));
+            },
+            async {
+                let result = bt::retry_with_timeout(|| infinitime.read_model_number()).await;
+                sender.input(Input::ModelNumber(Self::optional_info(result, "model number")));
+            },
+            async {
+                let result = bt::retry_with_timeout(|| infinitime.read_manufacturer()).await;
+                sender.input(Input::Manufacturer(Self::optional_info(result, "manufacturer")));
+            },
+            async {
+                send_checked(bt::retry_with_timeout(|| infinitime.read_battery_level()).await
+                    .map(Input::BatteryLevel)
+                    .context("Failed to read battery level"));
+            },
+            async {
+                send_checked(bt::retry_with_timeout(|| infinitime.read_heart_rate()).await
+                    .map(Input::HeartRate)
+                    .context("Failed to read heart rate"));
+            },
+            async {
+                send_checked(bt::retry_with_timeout(|| infinitime.read_step_count()).await
+                    .map(Input::StepCount)
+                    .context("Failed to read step count"));
+            },
+        );
+        log::debug!("Device info read in {:?}", start.elapsed());
+    }
+
+    async fn run_info_listener(infinitime: Arc<bt::InfiniTime>, sender: ComponentSender<Self>) {
+        let mut events = infinitime
+            .subscribe(&[bt::Sensor::BatteryLevel, bt::Sensor::HeartRate, bt::Sensor::StepCount])
+            .await;
+        let mut battery = SensorStats::new("battery level");
+        let mut heart = SensorStats::new("heart rate");
+        let mut steps = SensorStats::new("step count");
+        let mut stats_interval = tokio::time::interval(SENSOR_STATS_INTERVAL);
+        // The first tick completes immediately
+        stats_interval.tick().await;
         loop {
             tokio::select! {
-                Some(bl) = bl_stream.next() => sender.input(Input::BatteryLevel(bl)),
-                Some(hr) = hr_stream.next() => sender.input(Input::HeartRate(hr)),
-                Some(sc) = sc_stream.next() => sender.input(Input::StepCount(sc)),
-                else => break
+                event = events.next() => match event {
+                    Some(bt::SensorEvent::BatteryLevel(bl)) => {
+                        battery.record();
+                        sender.input(Input::BatteryLevel(bl));
+                    }
+                    Some(bt::SensorEvent::HeartRate(hr)) => {
+                        heart.record();
+                        sender.input(Input::HeartRate(hr));
+                    }
+                    Some(bt::SensorEvent::StepCount(sc)) => {
+                        steps.record();
+                        sender.input(Input::StepCount(sc));
+                    }
+                    // Not subscribed to
+                    Some(bt::SensorEvent::Motion(_)) => {}
+                    None => break,
+                },
+                _ = stats_interval.tick() => {
+                    log::debug!(
+                        "Sensor notifications in the last {}s: battery {}, heart rate {}, steps {}",
+                        SENSOR_STATS_INTERVAL.as_secs(), battery.count, heart.count, steps.count,
+                    );
+                    for stats in [&mut battery, &mut heart, &mut steps] {
+                        stats.check_silence();
+                    }
+                }
             }
         }
     }
 
+    fn connection_status(&self) -> (String, &'static str) {
+        if self.infinitime.is_some() {
+            let status = match self.link_latency {
+                Some(latency) => gettext_f("Connected · link: {latency} ms", &[
+                    ("latency", &latency.as_millis().to_string())
+                ]),
+                None => gettext("Connected"),
+            };
+            (status, "bluetooth-active-symbolic")
+        } else if self.is_connecting {
+            (gettext("Connecting…"), "bluetooth-acquiring-symbolic")
+        } else {
+            (gettext("Disconnected"), "bluetooth-disconnected-symbolic")
+        }
+    }
+
+    fn update_step_progress(&mut self) {
+        let steps = self.step_count.unwrap_or(0);
+        self.step_progress.set(steps as f64 / self.step_goal.max(1) as f64);
+        self.step_ring.queue_draw();
+    }
+
+    fn draw_step_ring(area: &gtk::DrawingArea, cr: &gtk::cairo::Context, width: i32, height: i32, progress: f64) {
+        let line_width = 4.0;
+        let radius = (width.min(height) as f64 - line_width) / 2.0;
+        let (cx, cy) = (width as f64 / 2.0, height as f64 / 2.0);
+        let color = area.color();
+        let (r, g, b) = (color.red() as f64, color.green() as f64, color.blue() as f64);
+        cr.set_line_width(line_width);
+        // Track
+        cr.set_source_rgba(r, g, b, 0.2);
+        cr.arc(cx, cy, radius, 0.0, 2.0 * PI);
+        _ = cr.stroke();
+        // Progress, clockwise from the top
+        if progress > 0.0 {
+            let start = -PI / 2.0;
+            cr.set_source_rgba(r, g, b, 1.0);
+            cr.arc(cx, cy, radius, start, start + 2.0 * PI * progress.min(1.0));
+            _ = cr.stroke();
+        }
+    }
+
     fn check_fw_update_available(&mut self) {
-        let latest = self.fw_latest.as_ref()
-            .and_then(|v| Version::from(v));
-        let current = self.fw_version.as_ref()
-            .and_then(|v| Version::from(v));
-        if let (Some(latest), Some(current)) = (latest, current) {
-            self.fw_update_available = latest > current;
+        if let (Some(latest), Some(current)) = (&self.fw_latest, &self.fw_version) {
+            self.fw_update_available = infinitime::is_update_available(current, latest);
         }
     }
+
+    /// Resources older than the firmware can lack its new watch faces and icons
+    fn check_resources_outdated(&mut self) {
+        self.resources_outdated = match (&self.resources_version, &self.fw_version) {
+            (Some(resources), Some(firmware)) => {
+                fwupd::compare_versions(resources, firmware) == Some(Ordering::Less)
+            }
+            _ => false,
+        };
+    }
 }
 
 #[relm4::component(pub)]
@@ -142,30 +559,46 @@ impl Component for Model {
 
     menu! {
         main_menu: {
-            "Devices" => super::DevicesViewAction,
-            "Settings" => super::SettingsViewAction,
+            &gettext("Devices") => super::DevicesViewAction,
+            &gettext("Settings") => super::SettingsViewAction,
             section! {
-                "About" => super::AboutAction,
+                &gettext("Keyboard Shortcuts") => super::ShortcutsAction,
+                &gettext("About") => super::AboutAction,
             },
             section! {
-                "Quit" => super::QuitAction,
+                &gettext("Quit") => super::QuitAction,
             }
         }
     }
 
     view! {
+        #[name = "root"]
         gtk::Box {
             set_hexpand: true,
             set_orientation: gtk::Orientation::Vertical,
 
             adw::HeaderBar {
                 #[wrap(Some)]
-                set_title_widget = &gtk::Label {
-                    set_label: "Watchmate",
+                set_title_widget = &gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_spacing: 6,
+
+                    gtk::Image {
+                        #[watch]
+                        set_icon_name: Some(model.connection_status().1),
+                        #[watch]
+                        set_tooltip_text: Some(&model.connection_status().0),
+                    },
+
+                    adw::WindowTitle {
+                        set_title: "Watchmate",
+                        #[watch]
+                        set_subtitle: &model.connection_status().0,
+                    },
                 },
 
                 pack_start = &gtk::Button {
-                    set_tooltip_text: Some("Devices"),
+                    set_tooltip_text: Some(&gettext("Devices")),
                     set_icon_name: "bluetooth-symbolic",
                     connect_clicked => |_| {
                         ui::BROKER.send(ui::Input::SetView(ui::View::Devices));
@@ -175,7 +608,24 @@ impl Component for Model {
                     set_icon_name: "open-menu-symbolic",
                     #[wrap(Some)]
                     set_popover = &gtk::PopoverMenu::from_model(Some(&main_menu)) {}
-                }
+                },
+                pack_end = &gtk::Button {
+                    #[watch]
+                    set_visible: model.infinitime.is_some(),
+                    #[watch]
+                    set_icon_name: if model.find_watch_task.is_some() {
+                        "media-playback-stop-symbolic"
+                    } else {
+                        "audio-volume-high-symbolic"
+                    },
+                    #[watch]
+                    set_tooltip_text: Some(&if model.find_watch_task.is_some() {
+                        gettext("Stop ringing")
+                    } else {
+                        gettext("Ring watch")
+                    }),
+                    connect_clicked => Input::FindWatch,
+                },
             },
 
             gtk::ScrolledWindow {
@@ -207,12 +657,16 @@ impl Component for Model {
                                         set_spacing: 10,
 
                                         gtk::Label {
-                                            set_label: "Battery",
+                                            set_label: &gettext("Battery"),
                                         },
 
                                         gtk::LevelBar {
                                             set_min_value: 0.0,
                                             set_max_value: 100.0,
+                                            // Default offsets assume 0..1 range
+                                            add_offset_value: (gtk::LEVEL_BAR_OFFSET_LOW, BATTERY_LOW as f64),
+                                            add_offset_value: (gtk::LEVEL_BAR_OFFSET_HIGH, BATTERY_HIGH as f64),
+                                            add_offset_value: (gtk::LEVEL_BAR_OFFSET_FULL, 100.0),
                                             #[watch]
                                             set_value: model.battery_level.unwrap_or(0) as f64,
                                             #[watch]
@@ -225,7 +679,7 @@ impl Component for Model {
                                             #[watch]
                                             set_label: match model.battery_level {
                                                 Some(soc) => format!("{}%", soc),
-                                                None => String::from("Loading..."),
+                                                None => gettext("Loading..."),
                                             }.as_str(),
                                             add_css_class: "dim-label",
                                         },
@@ -243,17 +697,19 @@ impl Component for Model {
                                         set_spacing: 10,
 
                                         gtk::Label {
-                                            set_label: "Heart Rate",
+                                            set_label: &gettext("Heart Rate"),
                                             set_hexpand: true,
                                             set_halign: gtk::Align::Start,
                                         },
 
                                         gtk::Label {
                                             #[watch]
-                                            set_label: match model.heart_rate {
-                                                Some(rate) => format!("{} BPM", rate),
-                                                None => String::from("Loading..."),
-                                            }.as_str(),
+                                            set_label: &match model.heart_rate {
+                                                Some(bt::HeartRate::Bpm(rate)) => format!("{} BPM", rate),
+                                                Some(bt::HeartRate::Measuring) => gettext("Measuring…"),
+                                                Some(bt::HeartRate::NotWorn) => gettext("Watch is not worn"),
+                                                None => gettext("Loading..."),
+                                            },
                                             add_css_class: "dim-label",
                                             set_hexpand: true,
                                             set_halign: gtk::Align::End,
@@ -272,7 +728,7 @@ impl Component for Model {
                                         set_spacing: 10,
 
                                         gtk::Label {
-                                            set_label: "Step Count",
+                                            set_label: &gettext("Step Count"),
                                             set_hexpand: true,
                                             set_halign: gtk::Align::Start,
                                         },
@@ -280,19 +736,32 @@ impl Component for Model {
                                         gtk::Label {
                                             #[watch]
                                             set_label: match model.step_count {
-                                                Some(rate) => format!("{}", rate),
-                                                None => String::from("Loading..."),
+                                                // Translators: step count out of the daily goal, e.g. "3,240 / 10,000"
+                                                Some(steps) => gettext_f("{steps} / {goal}", &[
+                                                    ("steps", &format_number(steps)),
+                                                    ("goal", &format_number(model.step_goal)),
+                                                ]),
+                                                None => gettext("Loading..."),
                                             }.as_str(),
                                             add_css_class: "dim-label",
                                             set_hexpand: true,
                                             set_halign: gtk::Align::End,
                                         },
+
+                                        #[local_ref]
+                                        step_ring -> gtk::DrawingArea {
+                                            set_content_width: 24,
+                                            set_content_height: 24,
+                                            set_valign: gtk::Align::Center,
+                                            #[watch]
+                                            set_visible: model.step_count.is_some(),
+                                        },
                                     },
                                 },
                             },
 
                             gtk::Label {
-                                set_label: "Host Integration",
+                                set_label: &gettext("Host Integration"),
                                 set_halign: gtk::Align::Start,
                                 set_margin_top: 20,
                             },
@@ -317,7 +786,7 @@ impl Component for Model {
                             },
 
                             gtk::Label {
-                                set_label: "System Info",
+                                set_label: &gettext("System Info"),
                                 set_halign: gtk::Align::Start,
                                 set_margin_top: 20,
                             },
@@ -337,21 +806,43 @@ impl Component for Model {
                                         set_spacing: 10,
 
                                         gtk::Label {
-                                            set_label: "Name",
+                                            set_label: &gettext("Name"),
                                             set_hexpand: true,
                                             set_halign: gtk::Align::Start,
                                         },
 
                                         gtk::Label {
                                             #[watch]
-                                            set_label: match &model.alias {
-                                                Some(alias) => alias,
-                                                None => "Loading...",
+                                            set_label: &match model.display_name() {
+                                                Some(name) => name.clone(),
+                                                None => gettext("Loading..."),
                                             },
+                                            #[watch]
+                                            set_tooltip_text: model.nickname.as_ref().and(model.alias.as_deref()),
                                             add_css_class: "dim-label",
                                             set_hexpand: true,
                                             set_halign: gtk::Align::End,
                                         },
+
+                                        gtk::Button {
+                                            set_tooltip_text: Some(&gettext("Copy")),
+                                            set_icon_name: "edit-copy-symbolic",
+                                            set_valign: gtk::Align::Center,
+                                            add_css_class: "flat",
+                                            #[watch]
+                                            set_visible: model.alias.is_some(),
+                                            connect_clicked => Input::CopyInfo(InfoField::Name),
+                                        },
+
+                                        gtk::Button {
+                                            set_tooltip_text: Some(&gettext("Set nickname")),
+                                            set_icon_name: "document-edit-symbolic",
+                                            set_valign: gtk::Align::Center,
+                                            add_css_class: "flat",
+                                            #[watch]
+                                            set_visible: model.address.is_some(),
+                                            connect_clicked => Input::EditNickname,
+                                        },
                                     },
                                 },
 
@@ -366,26 +857,148 @@ impl Component for Model {
                                         set_spacing: 10,
 
                                         gtk::Label {
-                                            set_label: "Address",
+                                            set_label: &gettext("Address"),
                                             set_hexpand: true,
                                             set_halign: gtk::Align::Start,
                                         },
 
                                         gtk::Label {
                                             #[watch]
-                                            set_label: match &model.address {
-                                                Some(address) => address,
-                                                None => "Loading...",
+                                            set_label: &match &model.address {
+                                                Some(address) => address.clone(),
+                                                None => gettext("Loading..."),
                                             },
                                             add_css_class: "dim-label",
                                             set_hexpand: true,
                                             set_halign: gtk::Align::End,
                                         },
+
+                                        gtk::Button {
+                                            set_tooltip_text: Some(&gettext("Copy")),
+                                            set_icon_name: "edit-copy-symbolic",
+                                            set_valign: gtk::Align::Center,
+                                            add_css_class: "flat",
+                                            #[watch]
+                                            set_visible: model.address.is_some(),
+                                            connect_clicked => Input::CopyInfo(InfoField::Address),
+                                        },
+                                    },
+                                },
+
+                                gtk::ListBoxRow {
+                                    set_selectable: false,
+                                    #[watch]
+                                    set_sensitive: model.model_number.is_some(),
+
+                                    gtk::Box {
+                                        set_orientation: gtk::Orientation::Horizontal,
+                                        set_margin_all: 12,
+                                        set_spacing: 10,
+
+                                        gtk::Label {
+                                            set_label: &gettext("Model"),
+                                            set_hexpand: true,
+                                            set_halign: gtk::Align::Start,
+                                        },
+
+                                        gtk::Label {
+                                            #[watch]
+                                            set_label: &Self::info_label(&model.model_number),
+                                            add_css_class: "dim-label",
+                                            set_hexpand: true,
+                                            set_halign: gtk::Align::End,
+                                        },
+                                    },
+                                },
+
+                                gtk::ListBoxRow {
+                                    set_selectable: false,
+                                    #[watch]
+                                    set_sensitive: model.hw_revision.is_some(),
+
+                                    gtk::Box {
+                                        set_orientation: gtk::Orientation::Horizontal,
+                                        set_margin_all: 12,
+                                        set_spacing: 10,
+
+                                        gtk::Label {
+                                            set_label: &gettext("Hardware Revision"),
+                                            set_hexpand: true,
+                                            set_halign: gtk::Align::Start,
+                                        },
+
+                                        gtk::Label {
+                                            #[watch]
+                                            set_label: &Self::info_label(&model.hw_revision),
+                                            add_css_class: "dim-label",
+                                            set_hexpand: true,
+                                            set_halign: gtk::Align::End,
+                                        },
+                                    },
+                                },
+
+                                gtk::ListBoxRow {
+                                    set_selectable: false,
+                                    #[watch]
+                                    set_sensitive: model.manufacturer.is_some(),
+
+                                    gtk::Box {
+                                        set_orientation: gtk::Orientation::Horizontal,
+                                        set_margin_all: 12,
+                                        set_spacing: 10,
+
+                                        gtk::Label {
+                                            set_label: &gettext("Manufacturer"),
+                                            set_hexpand: true,
+                                            set_halign: gtk::Align::Start,
+                                        },
+
+                                        gtk::Label {
+                                            #[watch]
+                                            set_label: &Self::info_label(&model.manufacturer),
+                                            add_css_class: "dim-label",
+                                            set_hexpand: true,
+                                            set_halign: gtk::Align::End,
+                                        },
+                                    },
+                                },
+
+                                gtk::ListBoxRow {
+                                    set_selectable: false,
+                                    #[watch]
+                                    set_sensitive: model.time_offset.is_some(),
+
+                                    gtk::Box {
+                                        set_orientation: gtk::Orientation::Horizontal,
+                                        set_margin_all: 12,
+                                        set_spacing: 10,
+
+                                        gtk::Label {
+                                            set_label: &gettext("Time"),
+                                            set_hexpand: true,
+                                            set_halign: gtk::Align::Start,
+                                        },
+
+                                        gtk::Label {
+                                            #[watch]
+                                            set_label: &model.time_offset_label(),
+                                            add_css_class: "dim-label",
+                                            set_hexpand: true,
+                                            set_halign: gtk::Align::End,
+                                        },
+
+                                        gtk::Button {
+                                            set_tooltip_text: Some(&gettext("Sync time now")),
+                                            set_icon_name: "view-refresh-symbolic",
+                                            set_valign: gtk::Align::Center,
+                                            add_css_class: "flat",
+                                            connect_clicked => Input::SyncTime,
+                                        },
                                     },
                                 },
 
                                 adw::ExpanderRow {
-                                    set_title: "Firmware Version",
+                                    set_title: &gettext("Firmware Version"),
                                     #[watch]
                                     set_sensitive: model.fw_version.is_some(),
 
@@ -394,18 +1007,34 @@ impl Component for Model {
 
                                         gtk::Label {
                                             #[watch]
-                                            set_label: match &model.fw_version {
-                                                Some(version) => version,
-                                                None => "Loading...",
+                                            set_label: &match &model.fw_version {
+                                                Some(version) => version.clone(),
+                                                None => gettext("Loading..."),
                                             },
                                             add_css_class: "dim-label",
                                         },
 
-                                        gtk::Image {
+                                        gtk::Button {
                                             #[watch]
                                             set_visible: model.fw_update_available,
-                                            set_tooltip_text: Some("Firmware update available"),
-                                            set_icon_name: Some("arrow3-up-symbolic"),
+                                            #[watch]
+                                            set_label: &gettext_f("Update to {version}", &[
+                                                ("version", model.fw_latest.as_deref().unwrap_or_default())
+                                            ]),
+                                            set_tooltip_text: Some(&gettext("Firmware update available")),
+                                            set_valign: gtk::Align::Center,
+                                            add_css_class: "suggested-action",
+                                            connect_clicked => Input::UpdateToLatest,
+                                        },
+
+                                        gtk::Button {
+                                            set_tooltip_text: Some(&gettext("Copy")),
+                                            set_icon_name: "edit-copy-symbolic",
+                                            set_valign: gtk::Align::Center,
+                                            add_css_class: "flat",
+                                            #[watch]
+                                            set_visible: model.fw_version.is_some(),
+                                            connect_clicked => Input::CopyInfo(InfoField::FirmwareVersion),
                                         },
                                     },
 
@@ -415,6 +1044,37 @@ impl Component for Model {
                                         set_child: Some(model.firmware_panel.widget()),
                                     },
                                 },
+
+                                adw::ActionRow {
+                                    set_title: &gettext("Resources update recommended"),
+                                    #[watch]
+                                    set_subtitle: &gettext_f("Installed resources {resources} are older than the firmware", &[
+                                        ("resources", model.resources_version.as_deref().unwrap_or_default())
+                                    ]),
+                                    #[watch]
+                                    set_visible: model.resources_outdated,
+                                    add_prefix = &gtk::Image {
+                                        set_icon_name: Some("dialog-warning-symbolic"),
+                                        add_css_class: "warning",
+                                    },
+                                    add_suffix = &gtk::Button {
+                                        set_label: &gettext("Update Resources"),
+                                        set_valign: gtk::Align::Center,
+                                        connect_clicked => Input::UpdateResources,
+                                    },
+                                },
+
+                                adw::ActionRow {
+                                    set_title: &gettext("GATT Services"),
+                                    set_subtitle: &gettext("Inspect raw services and characteristics"),
+                                    set_activatable: true,
+                                    #[watch]
+                                    set_visible: model.developer_mode,
+                                    add_suffix = &gtk::Image {
+                                        set_icon_name: Some("go-next-symbolic"),
+                                    },
+                                    connect_activated => Input::InspectGatt,
+                                },
                             },
                         }
                     } else {
@@ -425,11 +1085,11 @@ impl Component for Model {
                             set_valign: gtk::Align::Center,
 
                             gtk::Label {
-                                set_label: "InfiniTime watch is not connected",
+                                set_label: &gettext("InfiniTime watch is not connected"),
                             },
 
                             gtk::Button {
-                                set_label: "Devices",
+                                set_label: &gettext("Devices"),
                                 set_halign: gtk::Align::Center,
 
                                 connect_clicked => |_| {
@@ -446,18 +1106,107 @@ impl Component for Model {
     fn init((window, settings): Self::Init, root: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
 
         let player_panel = media_player::Model::builder()
-            .launch(())
+            .launch(settings.clone())
             .detach();
 
+        let step_goal = settings.uint(ui::SETTING_STEP_GOAL);
+        settings.connect_changed(
+            Some(ui::SETTING_STEP_GOAL),
+            glib::clone!(#[strong] sender, move |settings, key| {
+                sender.input(Input::StepGoal(settings.uint(key)));
+            })
+        );
+        let run_in_background = settings.boolean(ui::SETTING_BACKGROUND);
+        settings.connect_changed(
+            Some(ui::SETTING_BACKGROUND),
+            glib::clone!(#[strong] sender, move |settings, key| {
+                sender.input(Input::RunInBackground(settings.boolean(key)));
+            })
+        );
+        let forward_host_battery = settings.boolean(ui::SETTING_HOST_BATTERY);
+        settings.connect_changed(
+            Some(ui::SETTING_HOST_BATTERY),
+            glib::clone!(#[strong] sender, move |settings, key| {
+                sender.input(Input::ForwardHostBattery(settings.boolean(key)));
+            })
+        );
+        let weather = Self::weather_settings(&settings);
+        for key in [
+            ui::SETTING_WEATHER_PROVIDER,
+            ui::SETTING_WEATHER_LOCATION,
+            ui::SETTING_WEATHER_LATITUDE,
+            ui::SETTING_WEATHER_LONGITUDE,
+        ] {
+            settings.connect_changed(
+                Some(key),
+                glib::clone!(#[strong] sender, move |settings, _| {
+                    sender.input(Input::Weather(Self::weather_settings(settings)));
+                })
+            );
+        }
+
+        let step_progress = Rc::new(Cell::new(0.0));
+        let step_ring = gtk::DrawingArea::new();
+        step_ring.set_draw_func(glib::clone!(#[strong] step_progress, move |area, cr, width, height| {
+            Self::draw_step_ring(area, cr, width, height, step_progress.get());
+        }));
+
         let notifications_panel = notifications::Model::builder()
-            .launch(settings)
+            .launch(settings.clone())
             .detach();
 
+        let dropped_file_prompt = Alert::builder()
+            .transient_for(&window)
+            .launch(AlertSettings {
+                text: Some(gettext("Flash dropped file?")),
+                secondary_text: Some(gettext(
+                    "Could not tell from the file name whether it is a firmware or resources archive.",
+                )),
+                confirm_label: Some(gettext("Firmware")),
+                cancel_label: Some(gettext("Cancel")),
+                option_label: Some(gettext("Resources")),
+                is_modal: true,
+                destructive_accept: false,
+                extra_child: None,
+            })
+            .forward(sender.input_sender(), |message| match message {
+                AlertResponse::Confirm => Input::DroppedFileTypeSelected(AssetType::Firmware),
+                AlertResponse::Cancel => Input::None,
+                AlertResponse::Option => Input::DroppedFileTypeSelected(AssetType::Resources),
+            });
+
+        let gatt_inspector = gatt_inspector::Model::builder()
+            .launch(window.clone())
+            .detach();
+
+        let developer_mode = settings.boolean(ui::SETTING_DEVELOPER_MODE);
+        settings.connect_changed(
+            Some(ui::SETTING_DEVELOPER_MODE),
+            glib::clone!(#[strong] sender, move |settings, key| {
+                sender.input(Input::DeveloperMode(settings.boolean(key)));
+            })
+        );
+
+        settings.connect_changed(
+            Some(ui::SETTING_DEVICE_NICKNAMES),
+            glib::clone!(#[strong] sender, move |_, _| {
+                sender.input(Input::NicknamesChanged);
+            })
+        );
+
+        settings.connect_changed(
+            Some(ui::SETTING_RESOURCES_VERSIONS),
+            glib::clone!(#[strong] sender, move |_, _| {
+                sender.input(Input::ResourcesVersionsChanged);
+            })
+        );
+
         let firmware_panel = fwupd::Model::builder()
-            .launch(window)
+            .launch((window, settings.clone()))
             .forward(&sender.input_sender(), |message| match message {
                 fwupd::Output::LatestFirmwareVersion(f) => Input::LatestFirmwareVersion(f),
                 fwupd::Output::FlashAssetFromFile(f, t) => Input::FlashAssetFromFile(f, t),
+                fwupd::Output::FlashAssetFromFiles(f, t) => Input::FlashAssetFromFiles(f, t),
                 fwupd::Output::FlashAssetFromUrl(u, t) => Input::FlashAssetFromUrl(u, t),
             });
 
@@ -465,26 +1214,72 @@ impl Component for Model {
             battery_level: None,
             heart_rate: None,
             step_count: None,
+            step_goal,
+            step_goal_reached: false,
             alias: None,
+            nickname: None,
             address: None,
             fw_version: None,
             fw_latest: None,
             fw_update_available: false,
+            resources_version: None,
+            resources_outdated: false,
+            link_latency: None,
+            hw_revision: None,
+            model_number: None,
+            manufacturer: None,
+            time_offset: None,
+            is_connecting: false,
             player_panel,
+            gatt_inspector,
+            developer_mode,
+            run_in_background,
+            background_status_updated: None,
             notifications_panel,
             firmware_panel,
+            dropped_file_prompt,
+            step_ring: step_ring.clone(),
+            step_progress,
             infinitime: None,
             data_task: None,
+            latency_task: None,
+            dropped_file: None,
+            forward_host_battery,
+            host_battery_task: None,
+            weather,
+            weather_task: None,
+            find_watch_task: None,
+            settings,
         };
 
+        let step_ring = &model.step_ring;
         let widgets = view_output!();
 
+        let drop_target = gtk::DropTarget::new(gio::File::static_type(), gtk::gdk::DragAction::COPY);
+        drop_target.connect_drop(glib::clone!(#[strong] sender, move |_, value, _, _| {
+            match value.get::<gio::File>().ok().and_then(|file| file.path()) {
+                Some(path) => {
+                    sender.input(Input::FileDropped(path));
+                    true
+                }
+                None => false,
+            }
+        }));
+        widgets.root.add_controller(drop_target);
+
         ComponentParts { model, widgets }
     }
 
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
         match msg {
+            Input::Connecting => {
+                self.is_connecting = true;
+            }
+            Input::ConnectionFailed => {
+                self.is_connecting = false;
+            }
             Input::Connected(infinitime) => {
+                self.is_connecting = false;
                 self.infinitime = Some(infinitime.clone());
                 // Propagate to components
                 self.player_panel.emit(
@@ -493,6 +1288,11 @@ impl Component for Model {
                 self.notifications_panel.emit(
                     notifications::Input::Device(Some(infinitime.clone()))
                 );
+                if self.forward_host_battery {
+                    self.start_host_battery_task();
+                }
+                self.start_weather_task();
+                self.start_latency_task(&sender);
                 // Read data from the watch
                 self.data_task = Some(relm4::spawn(async move {
                     // Read initial values
@@ -503,18 +1303,34 @@ impl Component for Model {
                 }));
             }
             Input::Disconnected => {
+                self.is_connecting = false;
                 self.battery_level = None;
                 self.heart_rate = None;
+                self.step_count = None;
                 self.alias = None;
+                self.nickname = None;
                 self.address = None;
                 self.fw_version = None;
                 self.fw_update_available = false;
+                self.resources_version = None;
+                self.resources_outdated = false;
+                self.hw_revision = None;
+                self.model_number = None;
+                self.manufacturer = None;
+                self.time_offset = None;
                 self.infinitime = None;
-                // Abort data update task
+                self.link_latency = None;
+                // Abort data update tasks
                 self.data_task.take().map(|h| h.abort());
+                self.latency_task.take().map(|h| h.abort());
+                self.stop_host_battery_task();
+                self.stop_weather_task();
+                self.find_watch_task.take().map(|h| h.abort());
                 // Propagate to components
                 self.player_panel.emit(media_player::Input::Device(None));
+                self.gatt_inspector.emit(gatt_inspector::Input::Disconnected);
                 self.notifications_panel.emit(notifications::Input::Device(None));
+                self.update_background_status(true);
             }
             Input::LatestFirmwareVersion(latest) => {
                 self.fw_latest = latest;
@@ -523,24 +1339,113 @@ impl Component for Model {
             Input::FlashAssetFromFile(f, t) => {
                 sender.output(Output::FlashAssetFromFile(f, t)).unwrap();
             }
+            Input::FlashAssetFromFiles(f, t) => {
+                sender.output(Output::FlashAssetFromFiles(f, t)).unwrap();
+            }
             Input::FlashAssetFromUrl(u, t) => {
                 sender.output(Output::FlashAssetFromUrl(u, t)).unwrap();
             }
+            Input::FileDropped(path) => {
+                let is_zip = path.extension()
+                    .map_or(false, |ext| ext.eq_ignore_ascii_case("zip"));
+                if !is_zip {
+                    ui::BROKER.send(ui::Input::Toast(gettext("Only .zip files can be flashed")));
+                } else if self.infinitime.is_none() {
+                    ui::BROKER.send(ui::Input::Toast(gettext("InfiniTime watch is not connected")));
+                } else {
+                    let name = path.file_name()
+                        .map(|n| n.to_string_lossy().to_lowercase())
+                        .unwrap_or_default();
+                    if name.contains("resources") {
+                        sender.output(Output::FlashAssetFromFile(path, AssetType::Resources)).unwrap();
+                    } else if name.contains("dfu") {
+                        sender.output(Output::FlashAssetFromFile(path, AssetType::Firmware)).unwrap();
+                    } else {
+                        self.dropped_file = Some(path);
+                        self.dropped_file_prompt.emit(AlertMsg::Show);
+                    }
+                }
+            }
+            Input::DroppedFileTypeSelected(asset_type) => {
+                if let Some(path) = self.dropped_file.take() {
+                    sender.output(Output::FlashAssetFromFile(path, asset_type)).unwrap();
+                }
+            }
             // -- Watch data --
             Input::BatteryLevel(soc) => {
                 self.battery_level = Some(soc);
+                self.update_background_status(false);
             }
             Input::HeartRate(rate) => {
                 self.heart_rate = Some(rate);
+                self.update_background_status(false);
             }
             Input::StepCount(count) => {
+                match self.step_count {
+                    // The watch resets step count at midnight. InfiniTime doesn't notify
+                    // about the reset or the goal (its motion service only provides step
+                    // count and raw motion values), so the reset is inferred from the drop
+                    Some(previous) if count < previous / 2 => {
+                        self.step_goal_reached = count >= self.step_goal;
+                    }
+                    Some(_) => {
+                        if count >= self.step_goal && !self.step_goal_reached {
+                            ui::BROKER.send(ui::Input::Toast(gettext("Daily step goal reached!")));
+                        }
+                        self.step_goal_reached = count >= self.step_goal;
+                    }
+                    // Initial reading, don't congratulate on the goal reached earlier
+                    None => {
+                        self.step_goal_reached = count >= self.step_goal;
+                    }
+                }
                 self.step_count = Some(count);
+                self.update_step_progress();
+                self.update_background_status(false);
+            }
+            Input::StepGoal(goal) => {
+                self.step_goal = goal;
+                self.step_goal_reached = self.step_count.unwrap_or(0) >= goal;
+                self.update_step_progress();
+            }
+            Input::RunInBackground(enabled) => {
+                self.run_in_background = enabled;
+                self.update_background_status(true);
+            }
+            Input::ForwardHostBattery(enabled) => {
+                self.forward_host_battery = enabled;
+                match enabled {
+                    true => self.start_host_battery_task(),
+                    false => self.stop_host_battery_task(),
+                }
+            }
+            Input::Weather(weather) => {
+                if self.weather != weather {
+                    self.weather = weather;
+                    match self.weather {
+                        Some(_) => self.start_weather_task(),
+                        None => self.stop_weather_task(),
+                    }
+                }
             }
             Input::Alias(alias) => {
                 self.alias = Some(alias);
             }
             Input::Address(address) => {
                 self.address = Some(address);
+                self.update_nickname();
+                self.update_resources_version();
+            }
+            Input::NicknamesChanged => {
+                self.update_nickname();
+            }
+            Input::EditNickname => {
+                self.show_nickname_dialog(&sender);
+            }
+            Input::SetNickname(nickname) => {
+                if let Some(address) = &self.address {
+                    ui::set_device_nickname(&self.settings, address, &nickname);
+                }
             }
             Input::FirmwareVersion(version) => {
                 self.firmware_panel.emit(
@@ -548,8 +1453,106 @@ impl Component for Model {
                 );
                 self.fw_version = Some(version);
                 self.check_fw_update_available();
+                self.check_resources_outdated();
+            }
+            Input::TimeOffset(offset) => {
+                if let Some(offset) = offset {
+                    log::info!("Watch time offset: {offset}s");
+                }
+                self.time_offset = Some(offset);
+            }
+            Input::SyncTime => {
+                if let Some(infinitime) = self.infinitime.clone() {
+                    relm4::spawn(async move {
+                        match infinitime.sync_current_time().await {
+                            Ok(()) => {
+                                ui::BROKER.send(ui::Input::Toast(gettext("Watch time is synchronized")));
+                                sender.input(Input::TimeOffset(Self::read_time_offset(&infinitime).await));
+                            }
+                            Err(error) => {
+                                log::error!("Failed to sync watch time: {error}");
+                                ui::BROKER.send(ui::Input::Toast(gettext("Failed to sync watch time")));
+                            }
+                        }
+                    });
+                }
             }
+            Input::FindWatch => {
+                if let Some(infinitime) = self.infinitime.clone() {
+                    if let Some(handle) = self.find_watch_task.take() {
+                        handle.abort();
+                        relm4::spawn(async move {
+                            if let Err(error) = infinitime.stop_find_watch().await {
+                                log::error!("Failed to stop ringing the watch: {error}");
+                            }
+                        });
+                    } else {
+                        self.find_watch_task = Some(relm4::spawn(async move {
+                            match infinitime.trigger_find_watch().await {
+                                Ok(()) => {
+                                    tokio::time::sleep(FIND_WATCH_TIMEOUT).await;
+                                    if let Err(error) = infinitime.stop_find_watch().await {
+                                        log::error!("Failed to stop ringing the watch: {error}");
+                                    }
+                                }
+                                Err(error) => {
+                                    log::error!("Failed to ring the watch: {error}");
+                                    ui::BROKER.send(ui::Input::Toast(gettext("Failed to ring the watch")));
+                                }
+                            }
+                            sender.input(Input::FindWatchStopped);
+                        }));
+                    }
+                }
+            }
+            Input::FindWatchStopped => {
+                self.find_watch_task = None;
+            }
+            Input::ResourcesVersionsChanged => {
+                self.update_resources_version();
+            }
+            Input::HardwareRevision(revision) => {
+                self.hw_revision = Some(revision);
+            }
+            Input::ModelNumber(model) => {
+                self.model_number = Some(model);
+            }
+            Input::Manufacturer(manufacturer) => {
+                self.manufacturer = Some(manufacturer);
+            }
+            Input::LinkLatency(latency) => {
+                self.link_latency = Some(latency);
+            }
+            Input::DeveloperMode(enabled) => {
+                self.developer_mode = enabled;
+            }
+            Input::UpdateToLatest => {
+                self.firmware_panel.emit(fwupd::Input::FlashLatest);
+            }
+            Input::UpdateResources => {
+                self.firmware_panel.emit(fwupd::Input::FlashMatchingResources);
+            }
+            Input::InspectGatt => {
+                if let Some(infinitime) = self.infinitime.clone() {
+                    self.gatt_inspector.emit(gatt_inspector::Input::Show(infinitime));
+                }
+            }
+            Input::CopyInfo(field) => {
+                let value = match field {
+                    InfoField::Name => self.display_name(),
+                    InfoField::Address => self.address.as_ref(),
+                    InfoField::FirmwareVersion => self.fw_version.as_ref(),
+                };
+                match (value, gtk::gdk::Display::default()) {
+                    (Some(value), Some(display)) => {
+                        display.clipboard().set_text(value);
+                        ui::BROKER.send(ui::Input::Toast(gettext("Copied")));
+                    }
+                    (Some(_), None) => log::error!("Failed to copy: no display"),
+                    (None, _) => {}
+                }
+            }
+            Input::None => {}
         }
     }
 }
-
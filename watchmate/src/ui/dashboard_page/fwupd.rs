@@ -1,6 +1,6 @@
 use super::AssetType;
-use crate::ui;
-use infinitime::gh;
+use crate::{i18n::{gettext, gettext_f}, ui};
+use infinitime::{bt, gh, tokio};
 
 use anyhow::Result;
 use relm4::{
@@ -10,8 +10,9 @@ use relm4::{
     Component, ComponentController, ComponentParts, ComponentSender, Controller, JoinHandle,
     RelmWidgetExt,
 };
+use adw::prelude::AdwWindowExt;
 use relm4_components::{alert::*, open_dialog::*, save_dialog::*};
-use std::path::PathBuf;
+use std::{cell::RefCell, cmp::Ordering, path::{Path, PathBuf}, rc::Rc};
 use version_compare as vercomp;
 
 #[derive(Debug)]
@@ -21,11 +22,13 @@ pub enum Input {
     RequestReleases,
     SelectedRelease(u32),
     ReleaseNotes,
+    OpenReleasePage,
 
-    // Firmware & Resources Download
-    DownloadFirmware,
-    DownloadResources,
-    DownloadAsset(gh::Asset),
+    // Firmware & Resources Download. The flag forces asking
+    // for the location, even if auto-saving is enabled
+    DownloadFirmware(bool),
+    DownloadResources(bool),
+    DownloadAsset(gh::Asset, bool),
     CancelDownloading,
     FinishedDownloading(Result<Vec<u8>>),
     SaveFile(PathBuf),
@@ -33,17 +36,37 @@ pub enum Input {
     // Firmware & Resources Update
     OpenFirmwareFileDialog,
     FlashFirmwareFromReleaseClicked,
+    FlashSummaryConfirmed,
     FlashFirmwareFromRelease,
+    UnsafeDowngradeConfirmed,
     FlashFirmwareFromFile(PathBuf),
     OpenResourcesFileDialog,
     FlashResourcesFromReleaseClicked,
     FlashResourcesFromRelease,
-    FlashResourcesFromFile(PathBuf),
+    FlashResourcesFromFiles(Vec<PathBuf>),
+    FlashResourcesConfirmed,
+    FlashResourcesCancelled,
+    FlashFromUrl(String),
+    /// Select the latest release and flash its firmware
+    FlashLatest,
+    /// Select the release of the installed firmware and flash its resources
+    FlashMatchingResources,
 }
 
+/// Firmware versions at which bootloader requirements changed. Downgrading
+/// from one of them (or later) to an earlier version can brick the watch
+const BOOTLOADER_BOUNDARIES: &[&str] = &[
+    // The first release for bootloader 1.0.0, earlier ones expect the old one
+    "1.0.0",
+];
+
+/// Word to type to confirm a downgrade across a bootloader boundary
+const UNSAFE_DOWNGRADE_CONFIRMATION: &str = "confirm";
+
 #[derive(Debug)]
 pub enum Output {
     FlashAssetFromFile(PathBuf, AssetType),
+    FlashAssetFromFiles(Vec<PathBuf>, AssetType),
     FlashAssetFromUrl(String, AssetType),
     LatestFirmwareVersion(Option<String>),
 }
@@ -51,7 +74,8 @@ pub enum Output {
 #[derive(Debug)]
 pub enum CommandOutput {
     FirmwareReleasesResponse(Result<Vec<gh::ReleaseInfo>>),
-    SaveFileResponse(Result<()>),
+    SaveFileResponse(PathBuf, Result<()>),
+    ResourcesFilesVersions(Vec<(PathBuf, Option<String>)>),
 }
 
 #[derive(Debug, Default, PartialEq)]
@@ -60,7 +84,8 @@ pub enum FirmwareReleasesState {
     None,
     Requested,
     Some(Vec<gh::ReleaseInfo>),
-    Error,
+    /// Error message for the user
+    Error(String),
 }
 
 impl FirmwareReleasesState {
@@ -84,7 +109,7 @@ impl FirmwareReleasesState {
     }
 
     pub fn _is_error(&self) -> bool {
-        self == &FirmwareReleasesState::Error
+        matches!(self, FirmwareReleasesState::Error(_))
     }
 }
 
@@ -94,17 +119,29 @@ pub struct Model {
     tags: Option<gtk::StringList>,
     selected_index: u32,
     resources_available: bool,
-    current_version: String,
+    // Shared with the release list rows to mark them relative to it
+    current_version: Rc<RefCell<String>>,
+    settings: gio::Settings,
     // Firmware download state
     download_task: Option<JoinHandle<()>>,
     download_content: Option<Vec<u8>>,
     download_filepath: Option<PathBuf>,
+    // Resources files waiting for version mismatch confirmation
+    pending_resources_files: Vec<PathBuf>,
     // Components
     dfu_open_dialog: Controller<OpenDialog>,
-    res_open_dialog: Controller<OpenDialog>,
+    res_open_dialog: Controller<OpenDialogMulti>,
     save_dialog: Controller<SaveDialog>,
+    flash_summary: Controller<Alert>,
+    flash_summary_label: gtk::Label,
     firmware_downgrade_warning: Controller<Alert>,
+    unsafe_downgrade_warning: Controller<Alert>,
+    unsafe_downgrade_entry: gtk::Entry,
     resource_mismatch_warning: Controller<Alert>,
+    // Release notes preview
+    release_notes_window: adw::Window,
+    release_notes_title: adw::WindowTitle,
+    release_notes_label: gtk::Label,
 }
 
 impl Model {
@@ -113,11 +150,54 @@ impl Model {
             let content = self.download_content.take().unwrap();
             let filepath = self.download_filepath.take().unwrap();
             sender.oneshot_command(async move {
-                CommandOutput::SaveFileResponse(gh::save_file(&content, filepath).await)
+                let result: Result<()> = match filepath.parent() {
+                    Some(dir) => tokio::fs::create_dir_all(dir).await.map_err(Into::into),
+                    None => Ok(()),
+                };
+                let result = match result {
+                    Ok(()) => gh::save_file(&content, &filepath).await,
+                    Err(error) => Err(error),
+                };
+                CommandOutput::SaveFileResponse(filepath, result)
             });
         }
     }
 
+    fn compare_with_current(&self, version: &str) -> Option<Ordering> {
+        compare_versions(version, &self.current_version.borrow())
+    }
+
+    /// Whether flashing `version` would downgrade across a bootloader boundary
+    fn crosses_bootloader_boundary(&self, version: &str) -> bool {
+        BOOTLOADER_BOUNDARIES.iter().any(|boundary| {
+            let target = compare_versions(version, boundary);
+            let current = self.compare_with_current(boundary);
+            target == Some(Ordering::Less) && matches!(current, Some(Ordering::Less | Ordering::Equal))
+        })
+    }
+
+    fn resources_file_version(filepath: &Path) -> Option<String> {
+        filepath.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(bt::resources_version_from_filename)
+            .map(String::from)
+    }
+
+    /// Release, file, size and version change, for confirmation before flashing
+    fn flash_summary_text(&self, release: &gh::ReleaseInfo, asset: &gh::Asset) -> String {
+        let current = match self.current_version.borrow().as_str() {
+            "" => gettext("Unknown"),
+            version => version.to_string(),
+        };
+        let target = release.tag.trim_start_matches('v');
+        [
+            gettext_f("Release: {tag}", &[("tag", &release.tag)]),
+            gettext_f("File: {name}", &[("name", &asset.name)]),
+            gettext_f("Download size: {size}", &[("size", glib::format_size(asset.size as u64).as_str())]),
+            gettext_f("Version: {current} → {target}", &[("current", &current), ("target", target)]),
+        ].join("\n")
+    }
+
     fn selected_release_info(&self) -> Option<&gh::ReleaseInfo> {
         if let FirmwareReleasesState::Some(releases) = &self.releases {
             releases.get(self.selected_index as usize)
@@ -130,20 +210,22 @@ impl Model {
 #[relm4::component(pub)]
 impl Component for Model {
     type CommandOutput = CommandOutput;
-    type Init = adw::ApplicationWindow;
+    type Init = (adw::ApplicationWindow, gio::Settings);
     type Input = Input;
     type Output = Output;
     type Widgets = Widgets;
 
     menu! {
         extra_menu: {
-            "Flash Resources" => FlashResourcesAction,
+            &gettext("Flash Resources") => FlashResourcesAction,
             section! {
-                "Download Firmware" => DownloadFirmwareAction,
-                "Download Resources" => DownloadResourcesAction,
+                &gettext("Download Firmware") => DownloadFirmwareAction,
+                &gettext("Download Firmware As…") => DownloadFirmwareAsAction,
+                &gettext("Download Resources") => DownloadResourcesAction,
+                &gettext("Download Resources As…") => DownloadResourcesAsAction,
             },
             section! {
-                "Release Notes" => ReleaseNotesAction,
+                &gettext("Release Notes") => ReleaseNotesAction,
             },
         }
     }
@@ -156,7 +238,7 @@ impl Component for Model {
             set_spacing: 10,
 
             gtk::Label {
-                set_label: "Update from GitHub release",
+                set_label: &gettext("Update from GitHub release"),
                 set_halign: gtk::Align::Start,
             },
 
@@ -171,19 +253,35 @@ impl Component for Model {
                     set_model: model.tags.as_ref(),
                     #[wrap(Some)]
                     set_factory = &gtk::SignalListItemFactory {
-                        connect_setup => |_, item| {
+                        connect_setup[current_version = model.current_version.clone()] => move |_, item| {
                             let item = item.downcast_ref::<gtk::ListItem>().unwrap();
                             let label = gtk::Label::new(None);
+                            let badge = gtk::Label::new(None);
+                            badge.add_css_class("caption");
+                            let row = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+                            row.append(&label);
+                            row.append(&badge);
                             let scroll_view = gtk::ScrolledWindow::builder()
                                 .vscrollbar_policy(gtk::PolicyType::Never)
-                                .child(&label)
+                                .child(&row)
                                 .build();
                             item.property_expression("item")
                                 .chain_property::<gtk::StringObject>("string")
                                 .bind(&label, "label", gtk::Widget::NONE);
+                            let current_version = current_version.clone();
+                            item.connect_item_notify(move |item| {
+                                let tag = item.item()
+                                    .and_downcast::<gtk::StringObject>()
+                                    .map(|s| s.string());
+                                let current = current_version.borrow();
+                                let ordering = tag.and_then(|tag| compare_versions(&tag, &current));
+                                update_version_badge(&badge, ordering);
+                            });
                             item.set_child(Some(&scroll_view));
                         }
                     },
+                    #[watch]
+                    set_selected: model.selected_index,
                     connect_selected_notify[sender] => move |wgt| {
                         sender.input(Input::SelectedRelease(wgt.selected()));
                     }
@@ -194,7 +292,7 @@ impl Component for Model {
                     set_visible: model.releases.is_some(),
                     #[watch]
                     set_sensitive: !model.download_task.is_some(),
-                    set_label: "Flash",
+                    set_label: &gettext("Flash"),
                     connect_clicked => Input::FlashFirmwareFromReleaseClicked,
                     #[wrap(Some)]
                     set_popover = &gtk::PopoverMenu::from_model(Some(&extra_menu)) {}
@@ -205,11 +303,11 @@ impl Component for Model {
                     #[watch]
                     set_visible: !model.releases.is_some(),
                     #[watch]
-                    set_label: match &model.releases {
-                        FirmwareReleasesState::None => "Firmware releases are not loaded",
-                        FirmwareReleasesState::Requested => "Getting firmware releases...",
-                        FirmwareReleasesState::Error => "Failed to get firmware releases",
-                        _ => "",
+                    set_label: &match &model.releases {
+                        FirmwareReleasesState::None => gettext("Firmware releases are not loaded"),
+                        FirmwareReleasesState::Requested => gettext("Getting firmware releases..."),
+                        FirmwareReleasesState::Error(message) => message.clone(),
+                        _ => String::new(),
                     },
                 },
 
@@ -219,7 +317,7 @@ impl Component for Model {
                     }
                 } else {
                     gtk::Button {
-                        set_tooltip_text: Some("Refresh releases list"),
+                        set_tooltip_text: Some(&gettext("Refresh releases list")),
                         set_icon_name: "refresh-symbolic",
                         connect_clicked => Input::RequestReleases,
                     }
@@ -231,7 +329,7 @@ impl Component for Model {
             },
 
             gtk::Label {
-                set_label: "Update from file",
+                set_label: &gettext("Update from file"),
                 set_halign: gtk::Align::Start,
             },
 
@@ -240,22 +338,49 @@ impl Component for Model {
                 set_spacing: 10,
 
                 gtk::Button {
-                    set_label: "Firmware",
+                    set_label: &gettext("Firmware"),
                     set_hexpand: true,
                     connect_clicked => Input::OpenFirmwareFileDialog,
                 },
 
                 gtk::Button {
-                    set_label: "Resources",
+                    set_label: &gettext("Resources"),
                     set_hexpand: true,
                     connect_clicked => Input::OpenResourcesFileDialog,
                 },
+            },
+
+            gtk::Label {
+                set_label: &gettext("Update from URL"),
+                set_halign: gtk::Align::Start,
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                add_css_class: "linked",
+
+                #[name = "url_entry"]
+                gtk::Entry {
+                    set_hexpand: true,
+                    set_input_purpose: gtk::InputPurpose::Url,
+                    set_placeholder_text: Some("https://example.com/pinetime-mcuboot-app-dfu.zip"),
+                    connect_activate[sender] => move |entry| {
+                        sender.input(Input::FlashFromUrl(entry.text().to_string()));
+                    },
+                },
+
+                gtk::Button {
+                    set_label: &gettext("Flash"),
+                    connect_clicked[sender, url_entry] => move |_| {
+                        sender.input(Input::FlashFromUrl(url_entry.text().to_string()));
+                    },
+                },
             }
         }
     }
 
     fn init(
-        main_window: Self::Init,
+        (main_window, settings): Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
@@ -274,7 +399,7 @@ impl Component for Model {
                 OpenDialogResponse::Cancel => Input::None,
             });
 
-        let res_open_dialog = OpenDialog::builder()
+        let res_open_dialog = OpenDialogMulti::builder()
             .transient_for_native(&main_window)
             .launch(OpenDialogSettings {
                 create_folders: false,
@@ -282,7 +407,7 @@ impl Component for Model {
                 ..Default::default()
             })
             .forward(&sender.input_sender(), |message| match message {
-                OpenDialogResponse::Accept(path) => Input::FlashResourcesFromFile(path),
+                OpenDialogResponse::Accept(paths) => Input::FlashResourcesFromFiles(paths),
                 OpenDialogResponse::Cancel => Input::None,
             });
 
@@ -294,15 +419,41 @@ impl Component for Model {
                 SaveDialogResponse::Cancel => Input::CancelDownloading,
             });
 
+        let flash_summary_label = gtk::Label::builder()
+            .halign(gtk::Align::Center)
+            .justify(gtk::Justification::Center)
+            .selectable(true)
+            .wrap(true)
+            .build();
+        let flash_summary = Alert::builder()
+            .transient_for(&main_window)
+            .launch(AlertSettings {
+                text: Some(gettext("Flash firmware?")),
+                secondary_text: Some(gettext(
+                    "The firmware will be downloaded and flashed to the watch",
+                )),
+                confirm_label: Some(gettext("Flash")),
+                cancel_label: Some(gettext("Cancel")),
+                option_label: None,
+                is_modal: true,
+                destructive_accept: false,
+                extra_child: Some(flash_summary_label.clone().upcast()),
+            })
+            .forward(sender.input_sender(), |message| match message {
+                AlertResponse::Confirm => Input::FlashSummaryConfirmed,
+                AlertResponse::Cancel => Input::None,
+                AlertResponse::Option => Input::None,
+            });
+
         let firmware_downgrade_warning = Alert::builder()
             .transient_for(&main_window)
             .launch(AlertSettings {
-                text: Some(String::from("Warning: downgrading!")),
-                secondary_text: Some(String::from(
+                text: Some(gettext("Warning: downgrading!")),
+                secondary_text: Some(gettext(
                     "Are you sure you want to downgrade the firmware?",
                 )),
-                confirm_label: Some(String::from("Proceed")),
-                cancel_label: Some(String::from("Cancel")),
+                confirm_label: Some(gettext("Proceed")),
+                cancel_label: Some(gettext("Cancel")),
                 option_label: None,
                 is_modal: true,
                 destructive_accept: true,
@@ -314,40 +465,121 @@ impl Component for Model {
                 AlertResponse::Option => Input::None,
             });
 
+        let unsafe_downgrade_entry = gtk::Entry::builder()
+            .placeholder_text(UNSAFE_DOWNGRADE_CONFIRMATION)
+            .build();
+        let unsafe_downgrade_warning = Alert::builder()
+            .transient_for(&main_window)
+            .launch(AlertSettings {
+                text: Some(gettext("Warning: unsafe downgrade!")),
+                secondary_text: Some(gettext_f(
+                    "The selected version requires an older bootloader than the one \
+                    on the watch. Flashing it may leave the watch unbootable, with \
+                    recovery possible only by hardware means. Type \"{word}\" to proceed.",
+                    &[("word", UNSAFE_DOWNGRADE_CONFIRMATION)],
+                )),
+                confirm_label: Some(gettext("Downgrade Anyway")),
+                cancel_label: Some(gettext("Cancel")),
+                option_label: None,
+                is_modal: true,
+                destructive_accept: true,
+                extra_child: Some(unsafe_downgrade_entry.clone().upcast()),
+            })
+            .forward(sender.input_sender(), |message| match message {
+                AlertResponse::Confirm => Input::UnsafeDowngradeConfirmed,
+                AlertResponse::Cancel => Input::None,
+                AlertResponse::Option => Input::None,
+            });
+
         let resource_mismatch_warning = Alert::builder()
             .transient_for(&main_window)
             .launch(AlertSettings {
-                text: Some(String::from("Warning: version mismatch!")),
-                secondary_text: Some(String::from(
+                text: Some(gettext("Warning: version mismatch!")),
+                secondary_text: Some(gettext(
                     "Selected resources do not match the current firmware version",
                 )),
-                confirm_label: Some(String::from("Proceed")),
-                cancel_label: Some(String::from("Cancel")),
+                confirm_label: Some(gettext("Proceed")),
+                cancel_label: Some(gettext("Cancel")),
                 option_label: None,
                 is_modal: true,
                 destructive_accept: true,
                 extra_child: None,
             })
             .forward(sender.input_sender(), |message| match message {
-                AlertResponse::Confirm => Input::FlashResourcesFromRelease,
-                AlertResponse::Cancel => Input::None,
-                AlertResponse::Option => Input::None,
+                AlertResponse::Confirm => Input::FlashResourcesConfirmed,
+                AlertResponse::Cancel => Input::FlashResourcesCancelled,
+                AlertResponse::Option => Input::FlashResourcesCancelled,
             });
 
+        // Labels are translated outside of the macro, so that xgettext can find them
+        let release_notes_text = gettext("Release Notes");
+        let open_in_browser_text = gettext("Open in Browser");
+        relm4::view! {
+            release_notes_window = adw::Window {
+                set_modal: true,
+                set_hide_on_close: true,
+                set_transient_for: Some(&main_window),
+                set_default_size: (480, 600),
+
+                #[wrap(Some)]
+                set_content = &adw::ToolbarView {
+                    add_top_bar = &adw::HeaderBar {
+                        #[wrap(Some)]
+                        #[name = "release_notes_title"]
+                        set_title_widget = &adw::WindowTitle {
+                            set_title: &release_notes_text,
+                        },
+
+                        pack_end = &gtk::Button {
+                            set_label: &open_in_browser_text,
+                            connect_clicked[sender] => move |_| {
+                                sender.input(Input::OpenReleasePage);
+                            },
+                        },
+                    },
+
+                    #[wrap(Some)]
+                    set_content = &gtk::ScrolledWindow {
+                        set_hscrollbar_policy: gtk::PolicyType::Never,
+                        set_vexpand: true,
+
+                        #[name = "release_notes_label"]
+                        gtk::Label {
+                            set_margin_all: 12,
+                            set_wrap: true,
+                            set_selectable: true,
+                            set_xalign: 0.0,
+                            set_yalign: 0.0,
+                            set_valign: gtk::Align::Start,
+                        },
+                    },
+                },
+            }
+        }
+
         let model = Model {
             releases: FirmwareReleasesState::default(),
             tags: None,
             selected_index: 0,
             resources_available: false,
-            current_version: String::new(),
+            current_version: Rc::default(),
+            settings,
             download_task: None,
             download_content: None,
             download_filepath: None,
+            pending_resources_files: Vec::new(),
             dfu_open_dialog,
             res_open_dialog,
             save_dialog,
+            flash_summary,
+            flash_summary_label,
             firmware_downgrade_warning,
+            unsafe_downgrade_warning,
+            unsafe_downgrade_entry,
             resource_mismatch_warning,
+            release_notes_window,
+            release_notes_title,
+            release_notes_label,
         };
 
         let widgets = view_output!();
@@ -367,13 +599,25 @@ impl Component for Model {
         ));
         group.add_action(RelmAction::<DownloadFirmwareAction>::new_stateless(
             glib::clone!(#[strong] sender, move |_| {
-                    sender.input(Input::DownloadFirmware);
+                    sender.input(Input::DownloadFirmware(false));
+                }
+            ),
+        ));
+        group.add_action(RelmAction::<DownloadFirmwareAsAction>::new_stateless(
+            glib::clone!(#[strong] sender, move |_| {
+                    sender.input(Input::DownloadFirmware(true));
                 }
             ),
         ));
         group.add_action(RelmAction::<DownloadResourcesAction>::new_stateless(
             glib::clone!(#[strong] sender, move |_| {
-                    sender.input(Input::DownloadResources);
+                    sender.input(Input::DownloadResources(false));
+                }
+            ),
+        ));
+        group.add_action(RelmAction::<DownloadResourcesAsAction>::new_stateless(
+            glib::clone!(#[strong] sender, move |_| {
+                    sender.input(Input::DownloadResources(true));
                 }
             ),
         ));
@@ -385,6 +629,13 @@ impl Component for Model {
         ));
         group.register_for_widget(&widgets.root);
 
+        model.settings.connect_changed(
+            Some(ui::SETTING_FIRMWARE_REPO),
+            glib::clone!(#[strong] sender, move |_, _| {
+                sender.input(Input::RequestReleases);
+            })
+        );
+
         sender.input(Input::RequestReleases);
         ComponentParts { model, widgets }
     }
@@ -393,12 +644,20 @@ impl Component for Model {
         match msg {
             Input::None => {}
             Input::CurrentFirmwareVersion(version) => {
-                self.current_version = version;
+                if *self.current_version.borrow() != version {
+                    *self.current_version.borrow_mut() = version;
+                    // Recreate the list, so that its rows are marked anew
+                    if let Some(releases) = self.releases.as_option() {
+                        let tags = releases.iter().map(|r| r.tag.as_str()).collect::<Vec<&str>>();
+                        self.tags = Some(gtk::StringList::new(&tags));
+                    }
+                }
             }
             Input::RequestReleases => {
                 self.releases = FirmwareReleasesState::Requested;
+                let repo = self.settings.string(ui::SETTING_FIRMWARE_REPO);
                 sender.oneshot_command(async move {
-                    CommandOutput::FirmwareReleasesResponse(gh::list_releases().await)
+                    CommandOutput::FirmwareReleasesResponse(gh::list_releases(&repo).await)
                 });
             }
             Input::SelectedRelease(index) => {
@@ -408,6 +667,18 @@ impl Component for Model {
                 }
             }
             Input::ReleaseNotes => {
+                if let Some(release) = self.selected_release_info() {
+                    let markup = match release.body.as_deref() {
+                        Some(body) if !body.trim().is_empty() => markdown_to_pango(body),
+                        _ => format!("<i>{}</i>", glib::markup_escape_text(&gettext("No release notes"))),
+                    };
+                    self.release_notes_title.set_title(&release.name);
+                    self.release_notes_title.set_subtitle(&release.tag);
+                    self.release_notes_label.set_markup(&markup);
+                    self.release_notes_window.present();
+                }
+            }
+            Input::OpenReleasePage => {
                 if let Some(release) = self.selected_release_info() {
                     gtk::UriLauncher::new(&release.url).launch(
                         adw::ApplicationWindow::NONE,
@@ -416,31 +687,31 @@ impl Component for Model {
                     );
                 }
             }
-            Input::DownloadFirmware => {
+            Input::DownloadFirmware(ask_location) => {
                 if let Some(release) = self.selected_release_info() {
                     match release.get_dfu_asset() {
                         Some(asset) => {
-                            sender.input(Input::DownloadAsset(asset.clone()));
+                            sender.input(Input::DownloadAsset(asset.clone(), ask_location));
                         }
                         None => {
-                            ui::BROKER.send(ui::Input::ToastStatic("DFU file not found"));
+                            ui::BROKER.send(ui::Input::Toast(gettext("DFU file not found")));
                         }
                     }
                 }
             }
-            Input::DownloadResources => {
+            Input::DownloadResources(ask_location) => {
                 if let Some(release) = self.selected_release_info() {
                     match release.get_resources_asset() {
                         Some(asset) => {
-                            sender.input(Input::DownloadAsset(asset.clone()));
+                            sender.input(Input::DownloadAsset(asset.clone(), ask_location));
                         }
                         None => {
-                            ui::BROKER.send(ui::Input::ToastStatic("Resources file not found"));
+                            ui::BROKER.send(ui::Input::Toast(gettext("Resources file not found")));
                         }
                     }
                 }
             }
-            Input::DownloadAsset(asset) => {
+            Input::DownloadAsset(asset, ask_location) => {
                 let url = asset.url;
                 let filename = asset.name;
                 let task = relm4::spawn(async move {
@@ -449,7 +720,11 @@ impl Component for Model {
                     ))
                 });
                 self.download_task = Some(task);
-                self.save_dialog.emit(SaveDialogMsg::SaveAs(filename));
+                if ask_location || !self.settings.boolean(ui::SETTING_DOWNLOAD_AUTO_SAVE) {
+                    self.save_dialog.emit(SaveDialogMsg::SaveAs(filename));
+                } else {
+                    self.download_filepath = Some(ui::download_dir(&self.settings).join(filename));
+                }
             }
             Input::CancelDownloading => {
                 self.download_task.take().map(|h| h.abort());
@@ -466,7 +741,9 @@ impl Component for Model {
                     Err(error) => {
                         self.download_content = None;
                         log::error!("Failed to download DFU file: {}", error);
-                        ui::BROKER.send(ui::Input::ToastStatic("Failed to download DFU file"));
+                        ui::BROKER.send(ui::Input::Toast(ui::github_error_message(
+                            &error, gettext("Failed to download DFU file"),
+                        )));
                     }
                 }
             }
@@ -482,53 +759,99 @@ impl Component for Model {
             }
             Input::FlashFirmwareFromReleaseClicked => {
                 if let Some(release) = self.selected_release_info() {
-                    let mut manifest = vercomp::Manifest::default();
-                    manifest.ignore_text = true;
-                    let selected = vercomp::Version::from_manifest(&release.tag, &manifest);
-                    let current = vercomp::Version::from_manifest(&self.current_version, &manifest);
-                    if let (Some(selected), Some(current)) = (selected, current) {
-                        if selected < current {
-                            self.firmware_downgrade_warning.emit(AlertMsg::Show);
-                        } else {
-                            sender.input(Input::FlashFirmwareFromRelease);
+                    match release.get_dfu_asset() {
+                        Some(asset) => {
+                            self.flash_summary_label.set_label(&self.flash_summary_text(release, asset));
+                            self.flash_summary.emit(AlertMsg::Show);
                         }
+                        None => {
+                            ui::BROKER.send(ui::Input::Toast(gettext("DFU file not found")));
+                        }
+                    }
+                }
+            }
+            Input::FlashSummaryConfirmed => {
+                if let Some(release) = self.selected_release_info() {
+                    if self.crosses_bootloader_boundary(&release.tag) {
+                        self.unsafe_downgrade_entry.set_text("");
+                        self.unsafe_downgrade_warning.emit(AlertMsg::Show);
+                    } else if self.compare_with_current(&release.tag) == Some(Ordering::Less) {
+                        self.firmware_downgrade_warning.emit(AlertMsg::Show);
                     } else {
                         sender.input(Input::FlashFirmwareFromRelease);
                     }
                 }
             }
+            Input::UnsafeDowngradeConfirmed => {
+                let text = self.unsafe_downgrade_entry.text();
+                if text.trim().eq_ignore_ascii_case(UNSAFE_DOWNGRADE_CONFIRMATION) {
+                    log::warn!("Downgrading across bootloader boundary, confirmed by user");
+                    sender.input(Input::FlashFirmwareFromRelease);
+                } else {
+                    ui::BROKER.send(ui::Input::Toast(gettext("Downgrade cancelled: confirmation didn't match")));
+                }
+            }
             Input::FlashFirmwareFromRelease => {
                 if let Some(release) = self.selected_release_info() {
                     match release.get_dfu_asset() {
                         Some(asset) => {
                             let url = asset.url.clone();
                             let atype = AssetType::Firmware;
+                            ui::BROKER.send(ui::Input::FirmwareVersionExpected(release.tag.clone()));
                             sender.output(Output::FlashAssetFromUrl(url, atype)).unwrap();
                         }
                         None => {
-                            ui::BROKER.send(ui::Input::ToastStatic("DFU file not found"));
+                            ui::BROKER.send(ui::Input::Toast(gettext("DFU file not found")));
                         }
                     }
                 }
             }
+            Input::FlashLatest => {
+                // Releases are listed newest first
+                if self.releases.is_some() {
+                    sender.input(Input::SelectedRelease(0));
+                    sender.input(Input::FlashFirmwareFromReleaseClicked);
+                }
+            }
+            Input::FlashMatchingResources => {
+                let index = self.releases.as_option().and_then(|releases| {
+                    releases.iter().position(|r| self.compare_with_current(&r.tag) == Some(Ordering::Equal))
+                });
+                match index {
+                    Some(index) => {
+                        sender.input(Input::SelectedRelease(index as u32));
+                        sender.input(Input::FlashResourcesFromRelease);
+                    }
+                    None => {
+                        ui::BROKER.send(ui::Input::Toast(gettext("No release matches the installed firmware")));
+                    }
+                }
+            }
+            Input::FlashFromUrl(url) => {
+                match validate_asset_url(&url) {
+                    Ok(atype) => {
+                        log::info!("Flashing {} from URL: {}", atype.name().to_lowercase(), url.trim());
+                        sender.output(Output::FlashAssetFromUrl(url.trim().to_string(), atype)).unwrap();
+                    }
+                    Err(message) => {
+                        ui::BROKER.send(ui::Input::Toast(message));
+                    }
+                }
+            }
             Input::FlashFirmwareFromFile(filepath) => {
                 let atype = AssetType::Firmware;
                 sender.output(Output::FlashAssetFromFile(filepath, atype)).unwrap();
             }
             Input::FlashResourcesFromReleaseClicked => {
+                self.pending_resources_files.clear();
                 if let Some(release) = self.selected_release_info() {
-                    let mut manifest = vercomp::Manifest::default();
-                    manifest.ignore_text = true;
-                    let selected = vercomp::Version::from_manifest(&release.tag, &manifest);
-                    let current = vercomp::Version::from_manifest(&self.current_version, &manifest);
-                    if let (Some(selected), Some(current)) = (selected, current) {
-                        if selected != current {
-                            self.resource_mismatch_warning.emit(AlertMsg::Show);
-                        } else {
+                    match self.compare_with_current(&release.tag) {
+                        Some(Ordering::Equal) | None => {
                             sender.input(Input::FlashResourcesFromRelease);
                         }
-                    } else {
-                        sender.input(Input::FlashResourcesFromRelease);
+                        Some(_) => {
+                            self.resource_mismatch_warning.emit(AlertMsg::Show);
+                        }
                     }
                 }
             }
@@ -541,14 +864,33 @@ impl Component for Model {
                             sender.output(Output::FlashAssetFromUrl(url, atype)).unwrap();
                         }
                         None => {
-                            ui::BROKER.send(ui::Input::ToastStatic("Resources asset not found"));
+                            ui::BROKER.send(ui::Input::Toast(gettext("Resources asset not found")));
                         }
                     }
                 }
             }
-            Input::FlashResourcesFromFile(filepath) => {
-                let atype = AssetType::Resources;
-                sender.output(Output::FlashAssetFromFile(filepath, atype)).unwrap();
+            Input::FlashResourcesFromFiles(filepaths) => {
+                if !filepaths.is_empty() {
+                    let versions = filepaths.into_iter()
+                        .map(|filepath| {
+                            let version = Self::resources_file_version(&filepath);
+                            (filepath, version)
+                        })
+                        .collect();
+                    sender.command_sender().emit(CommandOutput::ResourcesFilesVersions(versions));
+                }
+            }
+            Input::FlashResourcesConfirmed => {
+                if self.pending_resources_files.is_empty() {
+                    sender.input(Input::FlashResourcesFromRelease);
+                } else {
+                    let filepaths = std::mem::take(&mut self.pending_resources_files);
+                    let atype = AssetType::Resources;
+                    sender.output(Output::FlashAssetFromFiles(filepaths, atype)).unwrap();
+                }
+            }
+            Input::FlashResourcesCancelled => {
+                self.pending_resources_files.clear();
             }
         }
     }
@@ -573,24 +915,165 @@ impl Component for Model {
                 }
                 Err(error) => {
                     self.tags = None;
-                    self.releases = FirmwareReleasesState::Error;
+                    self.releases = FirmwareReleasesState::Error(ui::github_error_message(
+                        &error, gettext("Failed to get firmware releases"),
+                    ));
                     sender.output(Output::LatestFirmwareVersion(None)).unwrap();
                     log::error!("Failed to fetch firmware releases: {error}");
                 }
             },
-            CommandOutput::SaveFileResponse(response) => match response {
+            CommandOutput::ResourcesFilesVersions(versions) => {
+                // Warn once if any of the selected files doesn't match the firmware
+                let mut mismatch = false;
+                for (filepath, version) in &versions {
+                    match version {
+                        Some(version) => mismatch |= matches!(
+                            self.compare_with_current(version),
+                            Some(Ordering::Less | Ordering::Greater)
+                        ),
+                        None => log::warn!("Unknown resources version: {}", filepath.display()),
+                    }
+                }
+                let filepaths = versions.into_iter().map(|(filepath, _)| filepath).collect();
+                if mismatch {
+                    self.pending_resources_files = filepaths;
+                    self.resource_mismatch_warning.emit(AlertMsg::Show);
+                } else {
+                    let atype = AssetType::Resources;
+                    sender.output(Output::FlashAssetFromFiles(filepaths, atype)).unwrap();
+                }
+            }
+            CommandOutput::SaveFileResponse(filepath, response) => match response {
                 Ok(()) => {
-                    ui::BROKER.send(ui::Input::ToastStatic("Firmware downloaded"));
+                    log::info!("Downloaded file saved to {}", filepath.display());
+                    ui::BROKER.send(ui::Input::Toast(gettext_f("Downloaded to {path}", &[
+                        ("path", &filepath.to_string_lossy())
+                    ])));
                 }
                 Err(error) => {
                     log::error!("Failed to save firmware file: {error}");
-                    ui::BROKER.send(ui::Input::ToastStatic("Failed to save DFU file"));
+                    ui::BROKER.send(ui::Input::Toast(gettext("Failed to save DFU file")));
                 }
             },
         }
     }
 }
 
+/// Check that the pasted URL points to a zip archive over HTTP(S), and tell
+/// the asset type from the file name, like for dropped files
+fn validate_asset_url(url: &str) -> Result<AssetType, String> {
+    let url = url.trim();
+    let lowercase = url.to_lowercase();
+    if !lowercase.starts_with("https://") && !lowercase.starts_with("http://") {
+        return Err(gettext("Only HTTP and HTTPS links can be flashed"));
+    }
+    let path = lowercase.split(['?', '#']).next().unwrap_or_default();
+    let name = path.rsplit('/').next().unwrap_or_default();
+    if !name.ends_with(".zip") {
+        return Err(gettext("The link must point to a .zip file"));
+    }
+    match name.contains("resources") {
+        true => Ok(AssetType::Resources),
+        false => Ok(AssetType::Firmware),
+    }
+}
+
+pub(super) fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    let mut manifest = vercomp::Manifest::default();
+    manifest.ignore_text = true;
+    let a = vercomp::Version::from_manifest(a, &manifest)?;
+    let b = vercomp::Version::from_manifest(b, &manifest)?;
+    a.partial_cmp(&b)
+}
+
+/// Convert the subset of markdown used in GitHub release notes (headings,
+/// list items, bold, inline code and links) to Pango markup.
+/// Falls back to escaped plain text if the result is not valid markup
+fn markdown_to_pango(markdown: &str) -> String {
+    let markup = markdown.lines()
+        .map(|line| {
+            let line = line.trim_end();
+            let content = line.trim_start();
+            let indent = &line[..line.len() - content.len()];
+            if content.starts_with('#') {
+                format!("<b>{}</b>", inline_markdown_to_pango(content.trim_start_matches('#').trim()))
+            } else if let Some(item) = content.strip_prefix("- ").or(content.strip_prefix("* ")) {
+                format!("{indent}• {}", inline_markdown_to_pango(item))
+            } else {
+                inline_markdown_to_pango(line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    if gtk::pango::parse_markup(&markup, '\0').is_ok() {
+        markup
+    } else {
+        glib::markup_escape_text(markdown).to_string()
+    }
+}
+
+fn inline_markdown_to_pango(text: &str) -> String {
+    let escaped = glib::markup_escape_text(text);
+    let mut result = String::new();
+    let mut open_tags = Vec::new();
+    let mut rest = escaped.as_str();
+    while let Some(c) = rest.chars().next() {
+        let tag = if rest.starts_with("**") {
+            Some(("b", 2))
+        } else if c == '`' {
+            Some(("tt", 1))
+        } else {
+            None
+        };
+        if let Some((tag, len)) = tag {
+            if open_tags.last() == Some(&tag) {
+                open_tags.pop();
+                result.push_str(&format!("</{tag}>"));
+            } else {
+                open_tags.push(tag);
+                result.push_str(&format!("<{tag}>"));
+            }
+            rest = &rest[len..];
+            continue;
+        }
+        if c == '[' {
+            let link = rest.find("](").and_then(|mid| {
+                rest[mid..].find(')').map(|end| (mid, mid + end))
+            });
+            if let Some((mid, end)) = link {
+                result.push_str(&format!("<a href=\"{}\">{}</a>", &rest[mid + 2..end], &rest[1..mid]));
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+        result.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    for tag in open_tags.iter().rev() {
+        result.push_str(&format!("</{tag}>"));
+    }
+    result
+}
+
+/// Mark a release relative to the installed firmware version
+fn update_version_badge(badge: &gtk::Label, ordering: Option<Ordering>) {
+    for class in ["accent", "success", "dim-label"] {
+        badge.remove_css_class(class);
+    }
+    let (text, class) = match ordering {
+        Some(Ordering::Equal) => (gettext("Installed"), "accent"),
+        Some(Ordering::Greater) => (gettext("Newer"), "success"),
+        Some(Ordering::Less) => (gettext("Older"), "dim-label"),
+        None => (String::new(), ""),
+    };
+    badge.set_label(&format!("● {text}"));
+    badge.set_visible(ordering.is_some());
+    if !class.is_empty() {
+        badge.add_css_class(class);
+    }
+}
+
 relm4::new_action_group!(FirmwareUpdateGroup, "fwupd");
 relm4::new_stateless_action!(
     FlashFirmwareAction,
@@ -607,11 +1090,21 @@ relm4::new_stateless_action!(
     FirmwareUpdateGroup,
     "download-firmware"
 );
+relm4::new_stateless_action!(
+    DownloadFirmwareAsAction,
+    FirmwareUpdateGroup,
+    "download-firmware-as"
+);
 relm4::new_stateless_action!(
     DownloadResourcesAction,
     FirmwareUpdateGroup,
     "download-resouces"
 );
+relm4::new_stateless_action!(
+    DownloadResourcesAsAction,
+    FirmwareUpdateGroup,
+    "download-resources-as"
+);
 relm4::new_stateless_action!(
     ReleaseNotesAction,
     FirmwareUpdateGroup,
@@ -0,0 +1,209 @@
+use crate::i18n::gettext;
+use infinitime::{bluer::Uuid, bt};
+use std::{collections::HashMap, sync::Arc};
+use adw::prelude::{ActionRowExt, PreferencesGroupExt, PreferencesPageExt, PreferencesRowExt};
+use gtk::prelude::{BoxExt, ButtonExt, GtkWindowExt, OrientableExt, WidgetExt};
+use relm4::{adw, gtk, ComponentParts, ComponentSender, Component};
+
+
+#[derive(Debug)]
+pub enum Input {
+    Show(Arc<bt::InfiniTime>),
+    Disconnected,
+    Reload,
+    Read(Uuid),
+}
+
+#[derive(Debug)]
+pub enum CommandOutput {
+    Services(Result<Vec<bt::GattService>, bt::InfiniTimeError>),
+    Value(Uuid, Result<Vec<u8>, bt::InfiniTimeError>),
+}
+
+/// Developer view listing all GATT services and characteristics of the watch
+pub struct Model {
+    infinitime: Option<Arc<bt::InfiniTime>>,
+    is_loading: bool,
+    page: adw::PreferencesPage,
+    groups: Vec<adw::PreferencesGroup>,
+    value_labels: HashMap<Uuid, gtk::Label>,
+}
+
+impl Model {
+    fn load(&mut self, sender: &ComponentSender<Self>) {
+        if let Some(infinitime) = self.infinitime.clone() {
+            self.is_loading = true;
+            sender.oneshot_command(async move {
+                CommandOutput::Services(infinitime.gatt_services().await)
+            });
+        }
+    }
+
+    fn clear(&mut self) {
+        for group in self.groups.drain(..) {
+            self.page.remove(&group);
+        }
+        self.value_labels.clear();
+    }
+
+    fn show_services(&mut self, services: Vec<bt::GattService>, sender: &ComponentSender<Self>) {
+        self.clear();
+        for service in services {
+            let group = adw::PreferencesGroup::new();
+            group.set_title(bt::gatt_name(&service.uuid).unwrap_or("Unknown Service"));
+            group.set_description(Some(&service.uuid.to_string()));
+            for characteristic in service.characteristics {
+                let row = adw::ActionRow::new();
+                row.set_title(bt::gatt_name(&characteristic.uuid).unwrap_or("Unknown Characteristic"));
+                row.set_subtitle(&characteristic.uuid.to_string());
+                row.set_subtitle_selectable(true);
+
+                let value = gtk::Label::builder()
+                    .selectable(true)
+                    .wrap(true)
+                    .xalign(1.0)
+                    .css_classes(["dim-label", "monospace"])
+                    .build();
+                row.add_suffix(&value);
+                if characteristic.readable {
+                    let button = gtk::Button::builder()
+                        .icon_name("view-refresh-symbolic")
+                        .tooltip_text(gettext("Read"))
+                        .valign(gtk::Align::Center)
+                        .css_classes(["flat"])
+                        .build();
+                    let uuid = characteristic.uuid;
+                    let sender = sender.clone();
+                    button.connect_clicked(move |_| sender.input(Input::Read(uuid)));
+                    row.add_suffix(&button);
+                }
+                self.value_labels.insert(characteristic.uuid, value);
+                group.add(&row);
+            }
+            self.page.add(&group);
+            self.groups.push(group);
+        }
+    }
+
+    /// Hex bytes, followed by the decoded value if the characteristic is known
+    fn format_value(uuid: &Uuid, value: &[u8]) -> String {
+        let hex = match value {
+            [] => gettext("(empty)"),
+            _ => bt::to_hex(value),
+        };
+        match bt::decode_value(uuid, value) {
+            Some(decoded) => format!("{hex}\n{decoded}"),
+            None => hex,
+        }
+    }
+}
+
+
+#[relm4::component(pub)]
+impl Component for Model {
+    type CommandOutput = CommandOutput;
+    type Init = adw::ApplicationWindow;
+    type Input = Input;
+    type Output = ();
+    type Widgets = Widgets;
+
+    view! {
+        adw::Window {
+            set_hide_on_close: true,
+            set_transient_for: Some(&main_window),
+            set_default_size: (560, 640),
+            set_title: Some(&title_text),
+
+            #[wrap(Some)]
+            set_content = &adw::ToolbarView {
+                add_top_bar = &adw::HeaderBar {
+                    pack_start = &gtk::Button {
+                        set_icon_name: "view-refresh-symbolic",
+                        set_tooltip_text: Some(&gettext("Reload")),
+                        #[watch]
+                        set_sensitive: model.infinitime.is_some() && !model.is_loading,
+                        connect_clicked => Input::Reload,
+                    },
+
+                    pack_end = &gtk::Spinner {
+                        #[watch]
+                        set_spinning: model.is_loading,
+                    },
+                },
+
+                #[wrap(Some)]
+                set_content = &gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+
+                    #[local]
+                    page -> adw::PreferencesPage {
+                        set_vexpand: true,
+                    },
+                },
+            },
+        }
+    }
+
+    fn init(main_window: Self::Init, root: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
+        let page = adw::PreferencesPage::new();
+        let model = Self {
+            infinitime: None,
+            is_loading: false,
+            page: page.clone(),
+            groups: Vec::new(),
+            value_labels: HashMap::new(),
+        };
+        let title_text = gettext("GATT Services");
+        let widgets = view_output!();
+        ComponentParts { model, widgets }
+    }
+
+    fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>, root: &Self::Root) {
+        match msg {
+            Input::Show(infinitime) => {
+                self.infinitime = Some(infinitime);
+                self.load(&sender);
+                root.present();
+            }
+            Input::Disconnected => {
+                self.infinitime = None;
+                self.clear();
+                root.close();
+            }
+            Input::Reload => {
+                self.load(&sender);
+            }
+            Input::Read(uuid) => {
+                if let Some(infinitime) = self.infinitime.clone() {
+                    sender.oneshot_command(async move {
+                        CommandOutput::Value(uuid, infinitime.read_raw(&uuid).await)
+                    });
+                }
+            }
+        }
+    }
+
+    fn update_cmd(&mut self, msg: Self::CommandOutput, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match msg {
+            CommandOutput::Services(result) => {
+                self.is_loading = false;
+                match result {
+                    Ok(services) => self.show_services(services, &sender),
+                    Err(error) => log::error!("Failed to list GATT services: {error}"),
+                }
+            }
+            CommandOutput::Value(uuid, result) => {
+                let text = match result {
+                    Ok(value) => Self::format_value(&uuid, &value),
+                    Err(error) => {
+                        log::warn!("Failed to read characteristic {uuid}: {error}");
+                        gettext("Read failed")
+                    }
+                };
+                if let Some(label) = self.value_labels.get(&uuid) {
+                    label.set_label(&text);
+                }
+            }
+        }
+    }
+}
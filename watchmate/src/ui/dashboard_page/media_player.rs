@@ -1,26 +1,32 @@
+use crate::{i18n::{gettext, gettext_f}, ui};
 use futures::StreamExt;
-use gtk::prelude::{BoxExt, OrientableExt, WidgetExt};
-use infinitime::{bt, fdo::mpris, zbus};
+use gtk::{gio, prelude::{BoxExt, OrientableExt, SettingsExt, WidgetExt}};
+use infinitime::{bt, fdo::mpris, tokio, zbus};
 use relm4::{gtk, Component, ComponentParts, ComponentSender, JoinHandle, RelmWidgetExt};
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 #[derive(Debug)]
 pub enum Input {
     Device(Option<Arc<bt::InfiniTime>>),
     PlayerControlSessionStart,
     PlayerControlSessionEnded,
+    VolumeStep(f64),
     PlayerUpdateSessionStart,
     PlayerUpdateSessionEnded,
     PlayerAdded(mpris::MediaPlayer),
     PlayerRemoved(zbus::names::OwnedBusName),
+    ControlSessionEvent(mpris::ControlSessionEvent),
 }
 
 #[derive(Debug)]
 pub enum CommandOutput {
-    None,
     DBusConnection(zbus::Connection),
+    DBusConnectionFailed(zbus::Error),
 }
 
+/// Delay between attempts to connect to the D-Bus session bus
+const DBUS_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
 #[derive(Default)]
 pub struct Model {
     player_handles: Vec<Arc<mpris::MediaPlayer>>,
@@ -28,82 +34,163 @@ pub struct Model {
     infinitime: Option<Arc<bt::InfiniTime>>,
     control_task: Option<JoinHandle<()>>,
     update_task: Option<JoinHandle<()>>,
+    active_player: Option<String>,
+    playback_status: Option<mpris::PlaybackStatus>,
+    track_title: Option<String>,
+    track_artist: Option<String>,
     dbus_session: Option<Arc<zbus::Connection>>,
+    // Session bus is not reachable (e.g. headless system), retrying
+    dbus_unavailable: bool,
     dropdown: gtk::DropDown,
+    volume_step: f64,
 }
 
 impl Model {
     fn stop_control_task(&mut self) {
+        self.clear_control_state();
         if self.control_task.take().map(|h| h.abort()).is_some() {
             log::info!("Media Player Control session stopped");
         }
     }
 
+    fn clear_control_state(&mut self) {
+        self.active_player = None;
+        self.playback_status = None;
+        self.track_title = None;
+        self.track_artist = None;
+    }
+
+    fn now_playing(&self) -> Option<String> {
+        self.active_player.as_ref()?;
+        let title = self.track_title.as_ref()?;
+        Some(match &self.track_artist {
+            Some(artist) => format!("{artist} — {title}"),
+            None => title.clone(),
+        })
+    }
+
+    fn control_status(&self) -> Option<String> {
+        let player = self.active_player.as_ref()?;
+        let status = match self.playback_status {
+            Some(mpris::PlaybackStatus::Playing) => gettext("Playing"),
+            Some(mpris::PlaybackStatus::Paused) => gettext("Paused"),
+            Some(mpris::PlaybackStatus::Stopped) => gettext("Stopped"),
+            None => return Some(gettext_f("Controlling: {player}", &[("player", player)])),
+        };
+        Some(gettext_f("Controlling: {player} — {status}", &[("player", player), ("status", &status)]))
+    }
+
     fn stop_update_task(&mut self) {
         if self.update_task.take().map(|h| h.abort()).is_some() {
             log::info!("Media Player List Update session stopped");
         }
     }
+
+    fn clear_players(&mut self) {
+        self.player_handles.clear();
+        self.player_names = gtk::StringList::new(&[]);
+    }
+
+    fn connect_dbus(sender: &ComponentSender<Self>, delay: Duration) {
+        sender.oneshot_command(async move {
+            tokio::time::sleep(delay).await;
+            match zbus::Connection::session().await {
+                Ok(connection) => CommandOutput::DBusConnection(connection),
+                Err(error) => CommandOutput::DBusConnectionFailed(error),
+            }
+        });
+    }
 }
 
 #[relm4::component(pub)]
 impl Component for Model {
     type CommandOutput = CommandOutput;
-    type Init = ();
+    type Init = gio::Settings;
     type Input = Input;
     type Output = ();
     type Widgets = Widgets;
 
     view! {
         gtk::Box {
-            set_orientation: gtk::Orientation::Horizontal,
+            set_orientation: gtk::Orientation::Vertical,
             set_margin_all: 12,
-            set_spacing: 10,
+            set_spacing: 6,
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 10,
+
+                gtk::Label {
+                    set_label: &gettext("Media Player"),
+                    set_halign: gtk::Align::Start,
+                },
+
+                if model.dbus_unavailable {
+                    gtk::Label {
+                        set_label: &gettext("Media control unavailable (no session bus)"),
+                        set_hexpand: true,
+                        set_halign: gtk::Align::End,
+                        set_wrap: true,
+                        set_justify: gtk::Justification::Right,
+                        add_css_class: "dim-label",
+                    }
+                } else if model.player_handles.is_empty() {
+                    gtk::Label {
+                        set_label: &gettext("Not running"),
+                        set_hexpand: true,
+                        set_halign: gtk::Align::End,
+                        add_css_class: "dim-label",
+                    }
+                } else {
+                    #[local]
+                    dropdown -> gtk::DropDown {
+                        set_hexpand: true,
+                        #[watch]
+                        set_model: Some(&model.player_names),
+                        connect_selected_notify => Input::PlayerControlSessionStart,
+                    }
+                }
+            },
 
             gtk::Label {
-                set_label: "Media Player",
                 set_halign: gtk::Align::Start,
+                set_ellipsize: gtk::pango::EllipsizeMode::End,
+                add_css_class: "heading",
+                #[watch]
+                set_visible: model.now_playing().is_some(),
+                #[watch]
+                set_label: &model.now_playing().unwrap_or_default(),
             },
 
-            if model.player_handles.is_empty() {
-                gtk::Label {
-                    set_label: "Not running",
-                    set_hexpand: true,
-                    set_halign: gtk::Align::End,
-                    add_css_class: "dim-label",
-                }
-            } else {
-                #[local]
-                dropdown -> gtk::DropDown {
-                    set_hexpand: true,
-                    #[watch]
-                    set_model: Some(&model.player_names),
-                    connect_selected_notify => Input::PlayerControlSessionStart,
-                }
+            gtk::Label {
+                set_halign: gtk::Align::Start,
+                add_css_class: "caption",
+                add_css_class: "dim-label",
+                #[watch]
+                set_visible: model.active_player.is_some(),
+                #[watch]
+                set_label: &model.control_status().unwrap_or_default(),
             }
         }
     }
 
     fn init(
-        _: Self::Init,
+        settings: Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
         let dropdown = gtk::DropDown::default();
         let model = Self {
             dropdown: dropdown.clone(),
+            volume_step: settings.double(ui::SETTING_MEDIA_VOLUME_STEP),
             ..Default::default()
         };
-        let widgets = view_output!();
-        sender.oneshot_command(async move {
-            match zbus::Connection::session().await {
-                Ok(connection) => CommandOutput::DBusConnection(connection),
-                Err(error) => {
-                    log::error!("Failed to establish D-Bus session connection: {error}");
-                    CommandOutput::None
-                }
-            }
+        let input_sender = sender.input_sender().clone();
+        settings.connect_changed(Some(ui::SETTING_MEDIA_VOLUME_STEP), move |settings, key| {
+            input_sender.emit(Input::VolumeStep(settings.double(key)));
         });
+        let widgets = view_output!();
+        Self::connect_dbus(&sender, Duration::ZERO);
         ComponentParts { model, widgets }
     }
 
@@ -112,7 +199,14 @@ impl Component for Model {
             Input::Device(infinitime) => {
                 self.infinitime = infinitime;
                 match self.infinitime {
-                    Some(_) => sender.input(Input::PlayerControlSessionStart),
+                    Some(_) => {
+                        // Player list is tracked regardless of the watch connection,
+                        // make sure it's up to date before starting the control session
+                        if self.update_task.is_none() {
+                            sender.input(Input::PlayerUpdateSessionStart);
+                        }
+                        sender.input(Input::PlayerControlSessionStart);
+                    }
                     None => self.stop_control_task(),
                 }
             }
@@ -124,8 +218,17 @@ impl Component for Model {
                         self.stop_control_task();
                         // Start new media player control sesssion
                         let player = self.player_handles[index].clone();
-                        let task_handle = relm4::spawn(async move {
-                            match mpris::run_control_session(&player, &infinitime).await {
+                        let volume_step = self.volume_step;
+                        self.active_player = self.player_names.string(index as u32).map(String::from);
+                        let (events_tx, mut events_rx) = mpris::control_session_channel();
+                        let events_sender = sender.clone();
+                        let events_forwarder = async move {
+                            while let Some(event) = events_rx.recv().await {
+                                events_sender.input(Input::ControlSessionEvent(event));
+                            }
+                        };
+                        let session = async move {
+                            match mpris::run_control_session(&player, &infinitime, volume_step, Some(events_tx)).await {
                                 Ok(()) => {
                                     log::warn!("Media player control session ended unexpectedly")
                                 }
@@ -134,19 +237,39 @@ impl Component for Model {
                                 }
                             }
                             sender.input(Input::PlayerControlSessionEnded);
+                        };
+                        let task_handle = relm4::spawn(async move {
+                            tokio::join!(events_forwarder, session);
                         });
                         self.control_task = Some(task_handle);
                     }
                 }
             }
             Input::PlayerControlSessionEnded => {
-                self.player_handles.clear();
-                self.player_names = gtk::StringList::new(&[]);
                 self.control_task = None;
+                self.clear_control_state();
             }
+            Input::VolumeStep(step) => {
+                self.volume_step = step;
+                // Apply to the running session
+                if self.control_task.is_some() {
+                    sender.input(Input::PlayerControlSessionStart);
+                }
+            }
+            Input::ControlSessionEvent(event) => match event {
+                mpris::ControlSessionEvent::TrackChanged { title, artist } => {
+                    self.track_title = title.filter(|t| !t.is_empty());
+                    self.track_artist = artist.filter(|a| !a.is_empty());
+                }
+                mpris::ControlSessionEvent::PlaybackChanged(status) => {
+                    self.playback_status = Some(status);
+                }
+            },
             Input::PlayerUpdateSessionStart => {
                 if let Some(dbus_session) = self.dbus_session.clone() {
                     self.stop_update_task();
+                    // The update stream starts with currently running players
+                    self.clear_players();
                     let task_handle = relm4::spawn(async move {
                         match mpris::get_players_update_stream(&dbus_session).await {
                             Ok(stream) => stream.for_each(|event| {
@@ -176,6 +299,7 @@ impl Component for Model {
                 }
             }
             Input::PlayerUpdateSessionEnded => {
+                self.update_task = None;
                 log::info!("Restarting player list update session");
                 sender.input(Input::PlayerUpdateSessionStart);
             }
@@ -213,11 +337,24 @@ impl Component for Model {
         _root: &Self::Root,
     ) {
         match msg {
-            CommandOutput::None => {}
             CommandOutput::DBusConnection(connection) => {
+                if self.dbus_unavailable {
+                    log::info!("D-Bus session connection established");
+                }
+                self.dbus_unavailable = false;
                 self.dbus_session = Some(Arc::new(connection));
                 sender.input(Input::PlayerUpdateSessionStart);
             }
+            CommandOutput::DBusConnectionFailed(error) => {
+                // Report once, not on every retry
+                if self.dbus_unavailable {
+                    log::debug!("Failed to establish D-Bus session connection: {error}");
+                } else {
+                    log::error!("Failed to establish D-Bus session connection: {error}");
+                }
+                self.dbus_unavailable = true;
+                Self::connect_dbus(&sender, DBUS_RETRY_INTERVAL);
+            }
         }
     }
 }
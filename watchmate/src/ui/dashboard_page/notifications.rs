@@ -1,21 +1,37 @@
-use crate::ui;
-use infinitime::{zbus, bt, fdo::notifications};
+use crate::{i18n::gettext, ui};
+use infinitime::{bt, fdo::notifications};
 use std::sync::Arc;
-use gtk::{gio, prelude::{BoxExt, OrientableExt, WidgetExt, SettingsExt, SettingsExtManual}};
+use gtk::{gio, glib, prelude::{BoxExt, ButtonExt, OrientableExt, WidgetExt, SettingsExt, SettingsExtManual}};
 use relm4::{gtk, ComponentParts, ComponentSender, Component, JoinHandle, RelmWidgetExt};
 
 
+/// Known issue with the instructions on granting session bus access
+const PERMISSION_ISSUE_URL: &str = "https://github.com/azymohliad/watchmate/issues/6";
+
 #[derive(Debug)]
 pub enum Input {
     Device(Option<Arc<bt::InfiniTime>>),
     SetNotificationSession(bool),
+    SetForwardLowUrgency(bool),
+    SetLockCondition(notifications::LockCondition),
+    SetFormat(String),
     NotificationSessionEnded,
+    PermissionMissing,
+    SendTestNotification,
+}
+
+#[derive(Debug)]
+pub enum CommandOutput {
+    PermissionChecked(bool),
+    TestNotificationSent(Result<(), bt::InfiniTimeError>),
 }
 
 #[derive(Default)]
 pub struct Model {
     infinitime: Option<Arc<bt::InfiniTime>>,
     is_enabled: bool,
+    config: notifications::SessionConfig,
+    permission_missing: bool,
     task: Option<JoinHandle<()>>,
 }
 
@@ -25,23 +41,20 @@ impl Model {
             self.stop_notifications_task();
             log::info!("Notification session started");
             let infinitime = infinitime.clone();
+            let config = self.config.clone();
             self.task = Some(relm4::spawn(async move {
-                if let Err(error) = notifications::run_notification_session(&infinitime).await {
-                    if let Some(zbus::fdo::Error::AccessDenied(_)) = error.downcast_ref() {
-                        log::warn!(
-                            "Notification session failed: the app doesn't have permissions to monitor \
-                            D-Bus session bus. If you're running it from flatpak, you can grant access with \
-                            command: `flatpak override --socket=session-bus io.gitlab.azymohliad.WatchMate`, \
-                            or via Flatseal"
-                        );
+                if let Err(error) = notifications::run_notification_session(&infinitime, &config).await {
+                    if notifications::is_access_denied(&error) {
+                        Self::log_permission_missing();
                         ui::BROKER.send(ui::Input::ToastWithLink {
-                            message: "Session bus permission is needed here",
-                            label: "Details",
-                            url: "https://github.com/azymohliad/watchmate/issues/6",
+                            message: gettext("Session bus permission is needed here"),
+                            label: gettext("Details"),
+                            url: PERMISSION_ISSUE_URL,
                         });
+                        sender.input(Input::PermissionMissing);
                     } else {
                         log::warn!("Notifications session failed: {error}");
-                        ui::BROKER.send(ui::Input::ToastStatic("Notification session failed"));
+                        ui::BROKER.send(ui::Input::Toast(gettext("Notification session failed")));
                     }
                 }
                 sender.input(Input::NotificationSessionEnded);
@@ -49,6 +62,47 @@ impl Model {
         }
     }
 
+    fn check_permission(sender: &ComponentSender<Self>) {
+        sender.oneshot_command(async move {
+            match notifications::check_monitor_permission().await {
+                Ok(()) => CommandOutput::PermissionChecked(true),
+                Err(error) if notifications::is_access_denied(&error) => {
+                    Self::log_permission_missing();
+                    CommandOutput::PermissionChecked(false)
+                }
+                Err(error) => {
+                    // Not a permission issue, let the session report it
+                    log::warn!("Failed to check notifications permission: {error}");
+                    CommandOutput::PermissionChecked(true)
+                }
+            }
+        });
+    }
+
+    fn log_permission_missing() {
+        log::warn!(
+            "Notification session failed: the app doesn't have permissions to monitor \
+            D-Bus session bus. If you're running it from flatpak, you can grant access with \
+            command: `flatpak override --user --socket=session-bus {}`, or via Flatseal",
+            ui::APP_ID
+        );
+    }
+
+    fn update_config(&mut self, config: notifications::SessionConfig, sender: ComponentSender<Self>) {
+        if self.config != config {
+            self.config = config;
+            // Restart the running session with the new config
+            if self.task.is_some() {
+                self.start_notifications_task(sender);
+            }
+        }
+    }
+
+    fn lock_condition(settings: &gio::Settings) -> notifications::LockCondition {
+        let name = settings.string(ui::SETTING_NOTIFICATIONS_LOCK_CONDITION);
+        notifications::LockCondition::from_name(&name).unwrap_or_default()
+    }
+
     fn stop_notifications_task(&mut self) {
         // TODO: Is it safe to abort, or does it makes sense to
         // hook up a message channel to finish gracefully?
@@ -61,7 +115,7 @@ impl Model {
 
 #[relm4::component(pub)]
 impl Component for Model {
-    type CommandOutput = ();
+    type CommandOutput = CommandOutput;
     type Init = gio::Settings;
     type Input = Input;
     type Output = ();
@@ -69,33 +123,161 @@ impl Component for Model {
 
     view! {
         gtk::Box {
-            set_orientation: gtk::Orientation::Horizontal,
+            set_orientation: gtk::Orientation::Vertical,
             set_margin_all: 12,
-            set_spacing: 10,
+            set_spacing: 6,
 
-            gtk::Label {
-                set_label: "Notifications",
-                set_halign: gtk::Align::Start,
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 10,
+
+                gtk::Label {
+                    set_label: &gettext("Notifications"),
+                    set_halign: gtk::Align::Start,
+                },
+
+                #[name = "switch"]
+                gtk::Switch {
+                    #[watch]
+                    set_state: model.is_enabled && model.task.is_some(),
+                    set_halign: gtk::Align::End,
+                    set_hexpand: true,
+                    connect_active_notify[sender] => move |switch| {
+                        sender.input(Input::SetNotificationSession(switch.is_active()));
+                    }
+                }
             },
 
-            #[name = "switch"]
-            gtk::Switch {
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 10,
                 #[watch]
-                set_state: model.is_enabled && model.task.is_some(),
-                set_halign: gtk::Align::End,
-                set_hexpand: true,
-                connect_active_notify[sender] => move |switch| {
-                    sender.input(Input::SetNotificationSession(switch.is_active()));
+                set_visible: model.is_enabled,
+
+                gtk::Label {
+                    set_label: &gettext("Include low urgency"),
+                    set_halign: gtk::Align::Start,
+                    add_css_class: "dim-label",
+                },
+
+                #[name = "low_urgency_switch"]
+                gtk::Switch {
+                    set_halign: gtk::Align::End,
+                    set_hexpand: true,
+                    connect_active_notify[sender] => move |switch| {
+                        sender.input(Input::SetForwardLowUrgency(switch.is_active()));
+                    }
                 }
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 10,
+                #[watch]
+                set_visible: model.is_enabled,
+
+                gtk::Label {
+                    set_label: &gettext("Forward"),
+                    set_halign: gtk::Align::Start,
+                    add_css_class: "dim-label",
+                },
+
+                gtk::DropDown::from_strings(&[
+                    gettext("Always").as_str(),
+                    gettext("When screen is locked").as_str(),
+                    gettext("When screen is unlocked").as_str(),
+                ]) {
+                    set_halign: gtk::Align::End,
+                    set_hexpand: true,
+                    set_selected: notifications::LockCondition::ALL.iter()
+                        .position(|c| *c == model.config.lock_condition)
+                        .unwrap_or(0) as u32,
+                    connect_selected_notify[settings = settings.clone()] => move |dropdown| {
+                        if let Some(condition) = notifications::LockCondition::ALL.get(dropdown.selected() as usize) {
+                            _ = settings.set_string(ui::SETTING_NOTIFICATIONS_LOCK_CONDITION, condition.name());
+                        }
+                    },
+                },
+            },
+
+            // Sent directly, bypassing the session bus monitor. Helps to tell
+            // missing permission apart from connection issues
+            gtk::Button {
+                set_label: &gettext("Send Test Notification"),
+                set_halign: gtk::Align::Start,
+                #[watch]
+                set_sensitive: model.infinitime.is_some(),
+                connect_clicked => Input::SendTestNotification,
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 6,
+                #[watch]
+                set_visible: model.is_enabled && model.permission_missing,
+
+                gtk::Label {
+                    set_label: &permission_text,
+                    set_halign: gtk::Align::Start,
+                    set_xalign: 0.0,
+                    set_wrap: true,
+                    add_css_class: "caption",
+                    add_css_class: "dim-label",
+                },
+
+                gtk::Label {
+                    set_label: &override_command,
+                    set_halign: gtk::Align::Start,
+                    set_xalign: 0.0,
+                    set_wrap: true,
+                    set_selectable: true,
+                    add_css_class: "caption",
+                    add_css_class: "monospace",
+                },
+
+                gtk::LinkButton {
+                    set_label: &details_text,
+                    set_uri: PERMISSION_ISSUE_URL,
+                    set_halign: gtk::Align::Start,
+                },
             }
         }
     }
 
     fn init(settings: Self::Init, root: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
         let is_enabled = settings.boolean(ui::SETTING_NOTIFICATIONS);
-        let model = Self { is_enabled, ..Default::default() };
+        if is_enabled {
+            // Find out early, so that the guidance is shown before the watch is connected
+            Self::check_permission(&sender);
+        }
+        let config = notifications::SessionConfig {
+            forward_low_urgency: settings.boolean(ui::SETTING_LOW_URGENCY_NOTIFICATIONS),
+            lock_condition: Self::lock_condition(&settings),
+            format: settings.string(ui::SETTING_NOTIFICATION_FORMAT).to_string(),
+        };
+        settings.connect_changed(
+            Some(ui::SETTING_NOTIFICATIONS_LOCK_CONDITION),
+            glib::clone!(#[strong] sender, move |settings, _| {
+                sender.input(Input::SetLockCondition(Self::lock_condition(settings)));
+            })
+        );
+        settings.connect_changed(
+            Some(ui::SETTING_NOTIFICATION_FORMAT),
+            glib::clone!(#[strong] sender, move |settings, key| {
+                sender.input(Input::SetFormat(settings.string(key).to_string()));
+            })
+        );
+        let model = Self { is_enabled, config, ..Default::default() };
+        let permission_text = gettext(
+            "Watchmate has no permission to monitor notifications on the session bus. \
+            If it's installed from Flatpak, grant the access with the following command \
+            (or via Flatseal), and restart the app:"
+        );
+        let override_command = format!("flatpak override --user --socket=session-bus {}", ui::APP_ID);
+        let details_text = gettext("Details");
         let widgets = view_output!();
         settings.bind(ui::SETTING_NOTIFICATIONS, &widgets.switch, "active").build();
+        settings.bind(ui::SETTING_LOW_URGENCY_NOTIFICATIONS, &widgets.low_urgency_switch, "active").build();
         ComponentParts { model, widgets }
     }
 
@@ -104,22 +286,69 @@ impl Component for Model {
             Input::Device(infinitime) => {
                 self.infinitime = infinitime;
                 match self.infinitime {
-                    Some(_) if self.is_enabled => self.start_notifications_task(sender),
+                    Some(_) if self.is_enabled && !self.permission_missing => {
+                        self.start_notifications_task(sender)
+                    }
                     Some(_) => {},
                     None => self.stop_notifications_task(),
                 }
             }
             Input::SetNotificationSession(state) => {
+                if state && !self.is_enabled {
+                    // Give it another try, the permission might have been granted since
+                    self.permission_missing = false;
+                }
                 self.is_enabled = state;
                 match state {
-                    true => self.start_notifications_task(sender),
+                    true if !self.permission_missing => self.start_notifications_task(sender),
+                    true => {}
                     false => self.stop_notifications_task(),
                 }
             }
+            Input::SetForwardLowUrgency(state) => {
+                let config = notifications::SessionConfig { forward_low_urgency: state, ..self.config.clone() };
+                self.update_config(config, sender);
+            }
+            Input::SetLockCondition(condition) => {
+                let config = notifications::SessionConfig { lock_condition: condition, ..self.config.clone() };
+                self.update_config(config, sender);
+            }
+            Input::SetFormat(format) => {
+                let config = notifications::SessionConfig { format, ..self.config.clone() };
+                self.update_config(config, sender);
+            }
             Input::NotificationSessionEnded => {
                 self.task = None;
             }
+            Input::PermissionMissing => {
+                self.permission_missing = true;
+            }
+            Input::SendTestNotification => {
+                if let Some(infinitime) = self.infinitime.clone() {
+                    let content = gettext("Test notification");
+                    sender.oneshot_command(async move {
+                        CommandOutput::TestNotificationSent(infinitime.send_notification("Watchmate", &content).await)
+                    });
+                }
+            }
         }
     }
-}
 
+    fn update_cmd(&mut self, msg: Self::CommandOutput, sender: ComponentSender<Self>, _root: &Self::Root) {
+        match msg {
+            CommandOutput::PermissionChecked(granted) => {
+                self.permission_missing = !granted;
+                if granted && self.is_enabled && self.task.is_none() {
+                    self.start_notifications_task(sender);
+                }
+            }
+            CommandOutput::TestNotificationSent(Ok(())) => {
+                ui::BROKER.send(ui::Input::Toast(gettext("Test notification sent")));
+            }
+            CommandOutput::TestNotificationSent(Err(error)) => {
+                log::error!("Failed to send test notification: {error}");
+                ui::BROKER.send(ui::Input::Toast(gettext("Failed to send test notification")));
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::ui::{self, fwupd_page::AssetType};
+use infinitime::bt;
+use std::{path::PathBuf, sync::Arc};
+use zbus::{fdo, interface, Connection, SignalContext};
+
+// GApplication already owns the bus name equal to the app ID,
+// so the control service uses a sub-name of it
+pub static BUS_NAME: &'static str = "io.gitlab.azymohliad.WatchMate.Control";
+pub static OBJECT_PATH: &'static str = "/io/gitlab/azymohliad/WatchMate/Control";
+
+
+#[derive(Default)]
+pub struct ControlService {
+    infinitime: Option<Arc<bt::InfiniTime>>,
+}
+
+impl ControlService {
+    fn infinitime(&self) -> fdo::Result<&bt::InfiniTime> {
+        self.infinitime.as_deref()
+            .ok_or(fdo::Error::Failed(String::from("InfiniTime is not connected")))
+    }
+}
+
+#[interface(name = "io.gitlab.azymohliad.WatchMate.Control")]
+impl ControlService {
+    async fn connect(&self) {
+        ui::BROKER.send(ui::Input::Connect);
+    }
+
+    async fn disconnect(&self) {
+        ui::BROKER.send(ui::Input::Disconnect);
+    }
+
+    async fn get_battery_level(&self) -> fdo::Result<u8> {
+        self.infinitime()?.read_battery_level().await
+            .map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    async fn flash_firmware(&self, path: String) -> fdo::Result<()> {
+        self.infinitime()?;
+        ui::BROKER.send(ui::Input::FlashAssetFromFile(PathBuf::from(path), AssetType::Firmware));
+        Ok(())
+    }
+
+    async fn flash_resources(&self, path: String) -> fdo::Result<()> {
+        self.infinitime()?;
+        ui::BROKER.send(ui::Input::FlashAssetFromFile(PathBuf::from(path), AssetType::Resources));
+        Ok(())
+    }
+
+    #[zbus(signal)]
+    async fn update_finished(
+        ctxt: &SignalContext<'_>, asset: &str, success: bool, message: &str
+    ) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    async fn connected(&self) -> bool {
+        self.infinitime.is_some()
+    }
+
+    #[zbus(property)]
+    async fn address(&self) -> String {
+        self.infinitime.as_ref()
+            .map(|i| i.device().address().to_string())
+            .unwrap_or_default()
+    }
+}
+
+
+pub async fn start_control_service() -> zbus::Result<Connection> {
+    zbus::connection::Builder::session()?
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, ControlService::default())?
+        .build()
+        .await
+}
+
+pub async fn set_device(connection: &Connection, infinitime: Option<Arc<bt::InfiniTime>>) -> zbus::Result<()> {
+    let iface_ref = connection.object_server()
+        .interface::<_, ControlService>(OBJECT_PATH).await?;
+    let mut iface = iface_ref.get_mut().await;
+    iface.infinitime = infinitime;
+    iface.connected_changed(iface_ref.signal_context()).await?;
+    iface.address_changed(iface_ref.signal_context()).await?;
+    Ok(())
+}
+
+pub async fn emit_update_finished(connection: &Connection, asset: &str, success: bool, message: &str) -> zbus::Result<()> {
+    let iface_ref = connection.object_server()
+        .interface::<_, ControlService>(OBJECT_PATH).await?;
+    ControlService::update_finished(iface_ref.signal_context(), asset, success, message).await
+}
@@ -1,9 +1,10 @@
-use crate::ui;
+use crate::{i18n::{gettext, gettext_f, ngettext_f}, ui};
 use std::str::FromStr;
-use infinitime::{ bluer, bt };
-use std::sync::Arc;
-use futures::{pin_mut, StreamExt};
-use gtk::{gio, prelude::{BoxExt, ButtonExt, OrientableExt, ListBoxRowExt, WidgetExt, SettingsExt}};
+use infinitime::{ bluer, bt, tokio::{self, sync::mpsc} };
+use std::{cell::Cell, cmp::Reverse, sync::Arc, time::Duration};
+use futures::{channel::oneshot, pin_mut, StreamExt};
+use gtk::{gio, glib, prelude::{BoxExt, ButtonExt, EditableExt, GtkApplicationExt, GtkWindowExt, OrientableExt, ListBoxRowExt, WidgetExt, SettingsExt}};
+use adw::prelude::{EntryRowExt, MessageDialogExt, PreferencesRowExt};
 use relm4::{
     adw, gtk,
     factory::{FactoryComponent, FactorySender, FactoryVecDeque, DynamicIndex},
@@ -17,56 +18,279 @@ pub enum Input {
     InitAdapter,
     AdapterAdded(String),
     AdapterRemoved(String),
+    AdapterSelected,
+    AdapterPowered(bool),
+    PowerOnAdapter,
     StartDiscovery,
     StopDiscovery,
+    AutoStartDiscovery,
+    ToggleDiscovery,
     DiscoveryFailed,
+    DiscoveryTimedOut,
     DeviceInfoReady(DeviceInfo),
     DeviceAdded(bluer::Address),
     DeviceRemoved(bluer::Address),
+    DeviceAddedManually(String),
     DeviceSelected(i32),
+    DeviceConnecting,
+    ConnectSaved,
+    Disconnect(bluer::Address),
     DeviceConnected(Arc<bluer::Device>),
     DeviceDisconnected(Arc<bluer::Device>),
     DeviceDisconnecting(Arc<bluer::Device>),
     DeviceConnectionFailed,
-    DeviceConnectionLost(bluer::Address),
+    DeviceConnectionEnded(bluer::Address),
     SaveAddress(Option<bluer::Address>),
+    ToggleSaved(bluer::Address),
+    NicknamesChanged,
+    GattServerToggled(bool),
+    NameFilterChanged(String),
+    DiscoveryFilterChanged,
+    ForgetAll,
+    StopReconnecting,
+    RetryReconnecting,
+    CancelKnownDevices,
+    PasskeyRequested(bluer::Address, oneshot::Sender<Option<u32>>),
+    RemoveBond(bluer::Address),
+    /// Stop background tasks and services before quitting, optionally
+    /// disconnecting connected devices
+    Shutdown(bool),
+}
+
+/// BlueZ doesn't tell why the connection ended, so the disconnection
+/// is considered requested only if it was initiated from here
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisconnectReason {
+    Requested,
+    /// Disconnected by the watch, or out of range
+    Lost,
 }
 
 #[derive(Debug)]
 pub enum Output {
+    DeviceConnecting,
     DeviceConnected(Arc<bluer::Device>),
+    DeviceConnectionFailed,
+    DeviceDisconnected(DisconnectReason),
+    Reconnecting(bool),
+    AdapterNames(Vec<String>),
+    ShutdownComplete,
 }
 
 #[derive(Debug)]
 pub enum CommandOutput {
     InitSessionResult(bluer::Result<bluer::Session>),
     InitAdapterResult(bluer::Result<bluer::Adapter>),
+    AdapterPoweredResult(bluer::Result<bool>),
+    AdapterNames(bluer::Result<Vec<String>>),
     GattServicesResult(bluer::Result<bluer::gatt::local::ApplicationHandle>),
+    AgentResult(bluer::Result<bluer::agent::AgentHandle>),
     KnownDevices(Vec<DeviceInfo>),
+    ShutdownComplete,
 }
 
+/// How long to wait for the devices to disconnect on quit
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+/// GATT server registration often fails transiently right after the adapter
+/// appears, so it's retried with the delay doubled after each attempt
+const GATT_ATTEMPTS: u32 = 4;
+const GATT_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// How long each reconnection attempt scans for the lost device
+const RECONNECT_SCAN_WINDOW: Duration = Duration::from_secs(30);
+
 pub struct Model {
     settings: gio::Settings,
     devices: FactoryVecDeque<DeviceInfo>,
     session: Option<Arc<bluer::Session>>,
     adapter: Option<Arc<bluer::Adapter>>,
+    // None until the power state of the adapter is known
+    adapter_powered: Option<bool>,
+    adapter_task: Option<JoinHandle<()>>,
+    known_devices_task: Option<JoinHandle<()>>,
+    gatt_task: Option<JoinHandle<()>>,
+    find_host_task: Option<JoinHandle<()>>,
+    // Reading info of the discovered or manually added devices
+    device_info_tasks: Vec<JoinHandle<()>>,
     gatt_server: Option<bluer::gatt::local::ApplicationHandle>,
+    // Pairing agent, unregistered when dropped
+    agent: Option<bluer::agent::AgentHandle>,
     discovery_task: Option<JoinHandle<()>>,
+    discovery_timeout_task: Option<JoinHandle<()>>,
+    discovery_timed_out: bool,
+    name_filter: String,
 
     saved_address: Option<bluer::Address>,
     autoconnect_address: Option<bluer::Address>,
     disconnecting_address: Option<bluer::Address>,
+    // Trying to restore lost connection, until the device is connected
+    // or auto-connection is cancelled
+    reconnecting: bool,
+    reconnect_attempts: u32,
+    reconnect_task: Option<JoinHandle<()>>,
 }
 
 impl Model {
-    async fn init_adapter(session: Arc<bluer::Session>) -> bluer::Result<bluer::Adapter> {
-        let adapter = session.default_adapter().await?;
-        adapter.set_discovery_filter(bluer::DiscoveryFilter {
+    fn update_saved_address(&self, event: SavedAddressEvent, sender: &ComponentSender<Self>) {
+        let address = saved_address_after(self.saved_address, event);
+        if address != self.saved_address {
+            sender.input(Input::SaveAddress(address));
+        }
+    }
+
+    /// Initialize the adapter selected in the settings, or the default one
+    /// if none is selected or the selected one is not found
+    async fn init_adapter(session: Arc<bluer::Session>, adapter_name: String, filter: bluer::DiscoveryFilter) -> bluer::Result<bluer::Adapter> {
+        let adapter = match adapter_name.as_str() {
+            "" => session.default_adapter().await?,
+            name if session.adapter_names().await?.iter().any(|n| n == name) => session.adapter(name)?,
+            name => {
+                log::warn!("Bluetooth adapter {name} is not found, using the default one");
+                session.default_adapter().await?
+            }
+        };
+        adapter.set_discovery_filter(filter).await?;
+        Ok(adapter)
+    }
+
+    fn refresh_adapter_names(&self, sender: &ComponentSender<Self>) {
+        if let Some(session) = self.session.clone() {
+            sender.oneshot_command(async move {
+                CommandOutput::AdapterNames(session.adapter_names().await)
+            });
+        }
+    }
+
+    /// Drop the current adapter along with its devices and initialize it again
+    fn reset_adapter(&mut self, sender: &ComponentSender<Self>) {
+        self.abort_adapter_tasks();
+        self.adapter = None;
+        self.adapter_powered = None;
+        sender.input(Input::InitAdapter);
+    }
+
+    /// Abort everything awaiting the current adapter, which would otherwise
+    /// only fail after D-Bus timeouts if the adapter is gone
+    fn abort_adapter_tasks(&mut self) {
+        self.adapter_task.take().map(|h| h.abort());
+        self.known_devices_task.take().map(|h| h.abort());
+        self.gatt_task.take().map(|h| h.abort());
+        self.find_host_task.take().map(|h| h.abort());
+        self.discovery_timeout_task.take().map(|h| h.abort());
+        if let Some(handle) = self.discovery_task.take() {
+            handle.abort();
+            log::info!("Device discovery stopped");
+        }
+        for handle in self.device_info_tasks.drain(..) {
+            handle.abort();
+        }
+        for device in self.devices.iter() {
+            if let Some(task) = &device.connect_task {
+                task.abort();
+            }
+        }
+        self.devices.guard().clear();
+        self.gatt_server = None;
+    }
+
+    /// Insert the device at its place in the list order, unless it is
+    /// already listed. Returns its index
+    fn insert_device(&mut self, info: DeviceInfo) -> usize {
+        let mut devices = self.devices.guard();
+        if let Some(index) = devices.iter().position(|d| d.address == info.address) {
+            log::debug!("Device {} is already listed", info.address);
+            return index;
+        }
+        let index = insert_position(devices.iter().map(DeviceInfo::list_order), info.list_order());
+        devices.insert(index, info);
+        index
+    }
+
+    fn track_device_info_task(&mut self, handle: JoinHandle<()>) {
+        self.device_info_tasks.retain(|h| !h.is_finished());
+        self.device_info_tasks.push(handle);
+    }
+
+    /// Pairing agent, which asks the user for the passkey shown on the watch
+    fn pairing_agent(sender: ComponentSender<Self>) -> bluer::agent::Agent {
+        bluer::agent::Agent {
+            request_default: true,
+            request_passkey: Some(Box::new(move |request| {
+                let sender = sender.clone();
+                Box::pin(async move {
+                    log::info!("Passkey requested for {}", request.device);
+                    let (reply_tx, reply_rx) = oneshot::channel();
+                    sender.input(Input::PasskeyRequested(request.device, reply_tx));
+                    match reply_rx.await {
+                        Ok(Some(passkey)) => Ok(passkey),
+                        _ => Err(bluer::agent::ReqError::Rejected),
+                    }
+                })
+            })),
+            ..Default::default()
+        }
+    }
+
+    fn show_passkey_dialog(address: bluer::Address, reply: oneshot::Sender<Option<u32>>) {
+        let entry = gtk::Entry::builder()
+            .input_purpose(gtk::InputPurpose::Digits)
+            .max_length(6)
+            .activates_default(true)
+            .build();
+        let dialog = adw::MessageDialog::builder()
+            .heading(gettext("Pairing Request"))
+            .body(gettext_f("Enter the passkey shown on the watch ({address})", &[("address", &address.to_string())]))
+            .extra_child(&entry)
+            .modal(true)
+            .build();
+        dialog.set_transient_for(relm4::main_application().active_window().as_ref());
+        dialog.add_responses(&[("cancel", gettext("Cancel").as_str()), ("pair", gettext("Pair").as_str())]);
+        dialog.set_response_appearance("pair", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("pair"));
+        dialog.set_close_response("cancel");
+        // The agent waits for exactly one reply
+        let reply = Cell::new(Some(reply));
+        dialog.connect_response(None, move |_, response| {
+            let passkey = match response {
+                "pair" => entry.text().trim().parse::<u32>().ok(),
+                _ => None,
+            };
+            if let Some(reply) = reply.take() {
+                _ = reply.send(passkey);
+            }
+        });
+        dialog.present();
+    }
+
+    fn discovery_filter(&self) -> bluer::DiscoveryFilter {
+        bluer::DiscoveryFilter {
             transport: bluer::DiscoveryTransport::Le,
-            pattern: Some(String::from("InfiniTime")),
+            pattern: Some(self.name_filter.clone()).filter(|f| !f.is_empty()),
+            rssi: self.rssi_threshold(),
+            duplicate_data: self.settings.boolean(super::SETTING_DUPLICATE_DATA),
             ..Default::default()
-        }).await?;
-        Ok(adapter)
+        }
+    }
+
+    fn rssi_threshold(&self) -> Option<i16> {
+        rssi_threshold(self.settings.int(super::SETTING_RSSI_THRESHOLD))
+    }
+
+    /// Apply the discovery filter, restarting the discovery if it's running
+    fn update_discovery_filter(&self, sender: &ComponentSender<Self>) {
+        if let Some(adapter) = self.adapter.clone() {
+            let filter = self.discovery_filter();
+            let is_discovering = self.discovery_task.is_some();
+            let sender = sender.clone();
+            relm4::spawn(async move {
+                if let Err(error) = adapter.set_discovery_filter(filter).await {
+                    log::error!("Failed to set discovery filter: {error}");
+                }
+                if is_discovering {
+                    sender.input(Input::StopDiscovery);
+                    sender.input(Input::StartDiscovery);
+                }
+            });
+        }
     }
 
     async fn run_session_stream(session: Arc<bluer::Session>, sender: ComponentSender<Self>) {
@@ -90,6 +314,201 @@ impl Model {
         }
     }
 
+    /// Report the end of reconnection once auto-connection is over,
+    /// either succeeded or cancelled
+    fn update_reconnecting(&mut self, sender: &ComponentSender<Self>) {
+        if self.reconnecting && self.autoconnect_address.is_none() {
+            self.reconnecting = false;
+            self.reconnect_attempts = 0;
+            self.reconnect_task.take().map(|h| h.abort());
+            sender.output(Output::Reconnecting(false)).unwrap();
+        }
+    }
+
+    /// Start the next reconnection attempt after the configured delay,
+    /// or give up if the maximum number of attempts is reached
+    fn schedule_reconnect(&mut self, sender: &ComponentSender<Self>) {
+        let max_attempts = self.settings.uint(super::SETTING_RECONNECT_MAX_ATTEMPTS);
+        if max_attempts > 0 && self.reconnect_attempts >= max_attempts {
+            log::info!("Giving up reconnecting after {} attempts", self.reconnect_attempts);
+            self.autoconnect_address = None;
+            sender.input(Input::StopDiscovery);
+            Self::show_reconnect_failed_dialog(self.reconnect_attempts, sender.clone());
+            return;
+        }
+        self.reconnect_attempts += 1;
+        let delay = Duration::from_secs(self.settings.uint(super::SETTING_RECONNECT_DELAY) as u64);
+        log::debug!("Reconnection attempt {} in {:?}", self.reconnect_attempts, delay);
+        let sender = sender.clone();
+        self.reconnect_task.take().map(|h| h.abort());
+        self.reconnect_task = Some(relm4::spawn(async move {
+            tokio::time::sleep(delay).await;
+            sender.input(Input::StartDiscovery);
+        }));
+    }
+
+    fn show_reconnect_failed_dialog(attempts: u32, sender: ComponentSender<Self>) {
+        let dialog = adw::MessageDialog::builder()
+            .heading(gettext("Watch Is Unreachable"))
+            .body(ngettext_f(
+                "Could not restore connection after {attempts} attempt. Make sure the watch is on and nearby",
+                "Could not restore connection after {attempts} attempts. Make sure the watch is on and nearby",
+                attempts,
+                &[("attempts", &attempts.to_string())],
+            ))
+            .modal(true)
+            .build();
+        dialog.set_transient_for(relm4::main_application().active_window().as_ref());
+        dialog.add_responses(&[("close", gettext("Close").as_str()), ("retry", gettext("Retry").as_str())]);
+        dialog.set_response_appearance("retry", adw::ResponseAppearance::Suggested);
+        dialog.set_default_response(Some("retry"));
+        dialog.set_close_response("close");
+        dialog.connect_response(Some("retry"), move |_, _| {
+            sender.input(Input::RetryReconnecting);
+        });
+        dialog.present();
+    }
+
+    fn auto_connect_enabled(&self) -> bool {
+        self.settings.boolean(super::SETTING_AUTO_CONNECT)
+    }
+
+    /// (Re)start discovery timeout, unless it's disabled or discovery
+    /// is needed to auto-connect to the saved device. While reconnecting,
+    /// each attempt scans for a limited time instead
+    fn restart_discovery_timeout(&mut self, sender: ComponentSender<Self>) {
+        self.discovery_timeout_task.take().map(|h| h.abort());
+        let timeout = if self.reconnecting && self.autoconnect_address.is_some() {
+            RECONNECT_SCAN_WINDOW
+        } else {
+            Duration::from_secs(self.settings.uint(super::SETTING_SCAN_TIMEOUT) as u64)
+        };
+        let autoconnecting = self.autoconnect_address.is_some() && !self.reconnecting;
+        if !timeout.is_zero() && !autoconnecting && self.discovery_task.is_some() {
+            self.discovery_timeout_task = Some(relm4::spawn(async move {
+                tokio::time::sleep(timeout).await;
+                sender.input(Input::DiscoveryTimedOut);
+            }));
+        }
+    }
+
+    async fn run_adapter_stream(adapter: Arc<bluer::Adapter>, sender: ComponentSender<Self>) {
+        match adapter.events().await {
+            Ok(stream) => {
+                pin_mut!(stream);
+                while let Some(event) = stream.next().await {
+                    if let bluer::AdapterEvent::PropertyChanged(bluer::AdapterProperty::Powered(powered)) = event {
+                        sender.input(Input::AdapterPowered(powered));
+                    }
+                }
+            }
+            Err(error) => {
+                log::error!("Failed to monitor bluetooth adapter events: {}", error)
+            }
+        }
+    }
+
+    fn load_adapter(&mut self, sender: ComponentSender<Self>) {
+        if let Some(adapter) = self.adapter.clone() {
+            if self.settings.boolean(super::SETTING_GATT_SERVER) {
+                self.start_gatt_server(&sender);
+            }
+
+            // Read known devices list
+            let saved_address = self.saved_address.clone();
+            let name_filter = self.name_filter.clone();
+            self.known_devices_task.take().map(|h| h.abort());
+            self.known_devices_task = Some(relm4::spawn(async move {
+                let devices = Self::read_known_devices(&adapter, &name_filter, saved_address).await;
+                sender.command_sender().emit(CommandOutput::KnownDevices(devices));
+            }));
+        }
+    }
+
+    fn start_gatt_server(&mut self, sender: &ComponentSender<Self>) {
+        if let Some(adapter) = self.adapter.clone() {
+            let sender = sender.clone();
+            let (find_host_tx, mut find_host_rx) = mpsc::unbounded_channel();
+            self.gatt_task.take().map(|h| h.abort());
+            self.gatt_task = Some(relm4::spawn(async move {
+                let result = Self::start_gatt_services(&adapter, find_host_tx).await;
+                sender.command_sender().emit(CommandOutput::GattServicesResult(result));
+            }));
+            // Ends when the server is stopped
+            self.find_host_task.take().map(|h| h.abort());
+            self.find_host_task = Some(relm4::spawn(async move {
+                while let Some(level) = find_host_rx.recv().await {
+                    ui::BROKER.send(ui::Input::FindHost(level));
+                }
+            }));
+        }
+    }
+
+    fn stop_gatt_server(&mut self) {
+        self.gatt_task.take().map(|h| h.abort());
+        self.find_host_task.take().map(|h| h.abort());
+        // Dropping the handle unregisters the application
+        if self.gatt_server.take().is_some() {
+            log::info!("GATT server stopped");
+        }
+    }
+
+    async fn start_gatt_services(
+        adapter: &bluer::Adapter, find_host: mpsc::UnboundedSender<bt::AlertLevel>
+    ) -> bluer::Result<bluer::gatt::local::ApplicationHandle> {
+        let mut delay = GATT_RETRY_DELAY;
+        let mut attempt = 1;
+        loop {
+            match bt::start_gatt_services(adapter, find_host.clone()).await {
+                Err(error) if attempt < GATT_ATTEMPTS && !Self::is_gatt_error_permanent(&error) => {
+                    log::warn!("Failed to start GATT server (attempt {attempt} of {GATT_ATTEMPTS}): {error}");
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Errors which retrying won't fix
+    fn is_gatt_error_permanent(error: &bluer::Error) -> bool {
+        matches!(
+            error.kind,
+            bluer::ErrorKind::NotPermitted
+                | bluer::ErrorKind::NotAuthorized
+                | bluer::ErrorKind::NotSupported
+                | bluer::ErrorKind::AlreadyExists
+        )
+    }
+
+    /// Read info of the known devices concurrently, skipping unreadable ones
+    async fn read_known_devices(
+        adapter: &bluer::Adapter,
+        name_filter: &str,
+        saved_address: Option<bluer::Address>,
+    ) -> Vec<DeviceInfo> {
+        let devices = match bt::InfiniTime::list_known_devices(adapter, name_filter).await {
+            Ok(devices) => devices,
+            Err(error) => {
+                log::error!("Failed to list known devices: {error}");
+                return Vec::new();
+            }
+        };
+        let infos = devices.into_iter().map(|device| async move {
+            let address = device.address();
+            let saved = Some(address) == saved_address;
+            match DeviceInfo::new(Arc::new(device), saved).await {
+                Ok(info) => Some(info),
+                Err(error) => {
+                    log::warn!("Failed to read info of known device {address}: {error}");
+                    None
+                }
+            }
+        });
+        futures::future::join_all(infos).await.into_iter().flatten().collect()
+    }
+
     async fn run_discovery(adapter: Arc<bluer::Adapter>, sender: ComponentSender<Self>) {
         match adapter.discover_devices().await {
             Ok(stream) => {
@@ -124,13 +543,17 @@ impl Component for Model {
 
     menu! {
         main_menu: {
-            "Back to Dashboard" => super::DashboardViewAction,
-            "Settings" => super::SettingsViewAction,
+            &gettext("Back to Dashboard") => super::DashboardViewAction,
+            &gettext("Settings") => super::SettingsViewAction,
             section! {
-                "About" => super::AboutAction,
+                &gettext("Forget All Devices") => super::ForgetDevicesAction,
             },
             section! {
-                "Quit" => super::QuitAction,
+                &gettext("Keyboard Shortcuts") => super::ShortcutsAction,
+                &gettext("About") => super::AboutAction,
+            },
+            section! {
+                &gettext("Quit") => super::QuitAction,
             }
         }
     }
@@ -147,7 +570,7 @@ impl Component for Model {
                     set_spacing: 10,
 
                     gtk::Label {
-                        set_label: "Devices",
+                        set_label: &gettext("Devices"),
                     },
 
                     gtk::Spinner {
@@ -158,7 +581,7 @@ impl Component for Model {
                 },
 
                 pack_start = &gtk::Button {
-                    set_tooltip_text: Some("Back"),
+                    set_tooltip_text: Some(&gettext("Back")),
                     set_icon_name: "go-previous-symbolic",
                     connect_clicked => |_| {
                         ui::BROKER.send(ui::Input::SetView(super::View::Dashboard));
@@ -168,7 +591,24 @@ impl Component for Model {
                     set_icon_name: "open-menu-symbolic",
                     #[wrap(Some)]
                     set_popover = &gtk::PopoverMenu::from_model(Some(&main_menu)) {}
-                }
+                },
+                pack_end = &gtk::Button {
+                    #[watch]
+                    set_visible: model.adapter.is_some() && model.adapter_powered == Some(true),
+                    #[watch]
+                    set_icon_name: if model.discovery_task.is_some() {
+                        "media-playback-pause-symbolic"
+                    } else {
+                        "refresh-symbolic"
+                    },
+                    #[watch]
+                    set_tooltip_text: Some(&if model.discovery_task.is_some() {
+                        gettext("Pause discovery")
+                    } else {
+                        gettext("Resume discovery")
+                    }),
+                    connect_clicked => Input::ToggleDiscovery,
+                },
             },
 
             adw::Clamp {
@@ -181,27 +621,104 @@ impl Component for Model {
                     set_spacing: 10,
 
                     if model.session.is_none() {
-                        gtk::Label {
-                            set_label: "Unable to start bluetooth session!",
+                        adw::StatusPage {
+                            set_vexpand: true,
+                            set_icon_name: Some("dialog-error-symbolic"),
+                            set_title: &gettext("Unable to start bluetooth session!"),
+                            set_description: Some(&gettext("Make sure that BlueZ service is installed and running")),
                         }
                     } else if model.adapter.is_none() {
-                        gtk::Label {
-                            set_label: "Bluetooth adapter not found!",
+                        adw::StatusPage {
+                            set_vexpand: true,
+                            set_icon_name: Some("bluetooth-disabled-symbolic"),
+                            set_title: &gettext("Bluetooth adapter not found!"),
+                            set_description: Some(&gettext("Plug in a bluetooth adapter, it will be picked up automatically")),
                         }
-                    } else {
-                        gtk::ScrolledWindow {
-                            set_hscrollbar_policy: gtk::PolicyType::Never,
+                    } else if model.adapter_powered == Some(false) {
+                        adw::StatusPage {
                             set_vexpand: true,
+                            set_icon_name: Some("bluetooth-disabled-symbolic"),
+                            set_title: &gettext("Bluetooth is turned off"),
+                            set_description: Some(&gettext("Turn it on to discover and connect to the watch")),
+
+                            #[wrap(Some)]
+                            set_child = &gtk::Button {
+                                set_label: &gettext("Turn on Bluetooth"),
+                                set_halign: gtk::Align::Center,
+                                add_css_class: "pill",
+                                add_css_class: "suggested-action",
+                                connect_clicked => Input::PowerOnAdapter,
+                            },
+                        }
+                    } else {
+                        gtk::Box {
+                            set_orientation: gtk::Orientation::Vertical,
+                            set_spacing: 10,
+
+                            gtk::Box {
+                                set_orientation: gtk::Orientation::Horizontal,
+                                set_spacing: 10,
+                                set_halign: gtk::Align::Center,
+                                #[watch]
+                                set_visible: model.known_devices_task.is_some(),
+
+                                gtk::Spinner {
+                                    set_spinning: true,
+                                },
+
+                                gtk::Label {
+                                    set_label: &gettext("Loading known devices…"),
+                                    add_css_class: "dim-label",
+                                },
+
+                                gtk::Button {
+                                    set_label: &gettext("Cancel"),
+                                    add_css_class: "flat",
+                                    connect_clicked => Input::CancelKnownDevices,
+                                },
+                            },
 
-                            #[local_ref]
-                            factory_widget -> gtk::ListBox {
-                                // set_margin_all: 5,
-                                set_valign: gtk::Align::Start,
-                                add_css_class: "boxed-list",
-                                connect_row_activated[sender] => move |_, row| {
-                                    sender.input(Input::DeviceSelected(row.index()))
-                                }
+                            gtk::ScrolledWindow {
+                                set_hscrollbar_policy: gtk::PolicyType::Never,
+                                set_vexpand: true,
+
+                                #[local_ref]
+                                factory_widget -> gtk::ListBox {
+                                    // set_margin_all: 5,
+                                    set_valign: gtk::Align::Start,
+                                    add_css_class: "boxed-list",
+                                    connect_row_activated[sender] => move |_, row| {
+                                        sender.input(Input::DeviceSelected(row.index()))
+                                    }
+                                },
                             },
+
+                            gtk::Button {
+                                add_css_class: "flat",
+                                #[watch]
+                                set_visible: model.discovery_timed_out && model.discovery_task.is_none(),
+                                #[watch]
+                                set_label: &if model.devices.is_empty() {
+                                    gettext("No device found, tap to rescan")
+                                } else {
+                                    gettext("Scan stopped, tap to rescan")
+                                },
+                                connect_clicked => Input::StartDiscovery,
+                            },
+
+                            gtk::ListBox {
+                                set_valign: gtk::Align::End,
+                                set_selection_mode: gtk::SelectionMode::None,
+                                add_css_class: "boxed-list",
+
+                                adw::EntryRow {
+                                    set_title: &gettext("Add device by address"),
+                                    set_show_apply_button: true,
+                                    connect_apply[sender] => move |row| {
+                                        sender.input(Input::DeviceAddedManually(row.text().to_string()));
+                                    },
+                                },
+                            }
                         }
                     }
                 }
@@ -214,15 +731,51 @@ impl Component for Model {
             "" => None,
             address => bluer::Address::from_str(address).ok()
         };
+        let autoconnect_address = saved_address.filter(|_| settings.boolean(super::SETTING_AUTO_CONNECT));
+        let name_filter = settings.string(super::SETTING_NAME_FILTER).to_string();
+        settings.connect_changed(
+            Some(super::SETTING_NAME_FILTER),
+            glib::clone!(#[strong] sender, move |settings, key| {
+                sender.input(Input::NameFilterChanged(settings.string(key).to_string()));
+            })
+        );
+        for key in [super::SETTING_RSSI_THRESHOLD, super::SETTING_DUPLICATE_DATA] {
+            settings.connect_changed(
+                Some(key),
+                glib::clone!(#[strong] sender, move |_, _| {
+                    sender.input(Input::DiscoveryFilterChanged);
+                })
+            );
+        }
+        settings.connect_changed(
+            Some(super::SETTING_ADAPTER),
+            glib::clone!(#[strong] sender, move |_, _| {
+                sender.input(Input::AdapterSelected);
+            })
+        );
+        settings.connect_changed(
+            Some(super::SETTING_DEVICE_NICKNAMES),
+            glib::clone!(#[strong] sender, move |_, _| {
+                sender.input(Input::NicknamesChanged);
+            })
+        );
+        settings.connect_changed(
+            Some(super::SETTING_GATT_SERVER),
+            glib::clone!(#[strong] sender, move |settings, key| {
+                sender.input(Input::GattServerToggled(settings.boolean(key)));
+            })
+        );
 
         let devices = FactoryVecDeque::builder()
             .launch(gtk::ListBox::new())
             .forward(sender.input_sender(), |output| match output {
+                DeviceOutput::Connecting => Input::DeviceConnecting,
                 DeviceOutput::Connected(device) => Input::DeviceConnected(device),
                 DeviceOutput::Disconnected(device) => Input::DeviceDisconnected(device),
                 DeviceOutput::Disconnecting(device) => Input::DeviceDisconnecting(device),
                 DeviceOutput::ConnectionFailed => Input::DeviceConnectionFailed,
-                DeviceOutput::SaveAddress(address) => Input::SaveAddress(address),
+                DeviceOutput::ToggleSaved(address) => Input::ToggleSaved(address),
+                DeviceOutput::RemoveBond(address) => Input::RemoveBond(address),
             });
 
         let model = Self {
@@ -230,11 +783,24 @@ impl Component for Model {
             devices,
             session: None,
             adapter: None,
+            adapter_powered: None,
+            adapter_task: None,
+            known_devices_task: None,
+            gatt_task: None,
+            find_host_task: None,
+            device_info_tasks: Vec::new(),
             gatt_server: None,
+            agent: None,
             discovery_task: None,
-            autoconnect_address: saved_address.clone(),
+            discovery_timeout_task: None,
+            discovery_timed_out: false,
+            name_filter,
+            autoconnect_address,
             saved_address,
             disconnecting_address: None,
+            reconnecting: false,
+            reconnect_attempts: 0,
+            reconnect_task: None,
         };
 
         let factory_widget = model.devices.widget();
@@ -255,58 +821,141 @@ impl Component for Model {
 
             Input::InitAdapter => {
                 if let Some(session) = self.session.clone() {
+                    let adapter_name = self.settings.string(super::SETTING_ADAPTER).to_string();
+                    let filter = self.discovery_filter();
                     sender.oneshot_command(async move {
-                        CommandOutput::InitAdapterResult(Self::init_adapter(session).await)
+                        CommandOutput::InitAdapterResult(Self::init_adapter(session, adapter_name, filter).await)
                     });
                 }
             }
 
-            Input::AdapterAdded(_name) => {
-                if self.adapter.is_none() {
-                    sender.input(Input::InitAdapter);
+            Input::AdapterAdded(name) => {
+                self.refresh_adapter_names(&sender);
+                // Switch to the selected adapter once it's back
+                let selected = self.settings.string(super::SETTING_ADAPTER);
+                match &self.adapter {
+                    None => sender.input(Input::InitAdapter),
+                    Some(adapter) if name == selected.as_str() && adapter.name() != name => {
+                        log::info!("Selected bluetooth adapter {name} is found");
+                        self.reset_adapter(&sender);
+                    }
+                    Some(_) => {}
                 }
             }
 
             Input::AdapterRemoved(name) => {
+                self.refresh_adapter_names(&sender);
                 if self.adapter.as_ref().map(|a| a.name()) == Some(&name) {
                     log::warn!("Bluetooth adapter is lost");
-                    self.adapter = None;
+                    // Same as switching the adapter, falls back to another one, if any
+                    self.reset_adapter(&sender);
+                }
+            }
+
+            Input::AdapterSelected => {
+                log::info!("Bluetooth adapter selection changed");
+                self.reset_adapter(&sender);
+            }
+
+            Input::AdapterPowered(powered) => {
+                if self.adapter_powered != Some(powered) {
+                    self.adapter_powered = Some(powered);
+                    if powered {
+                        log::info!("Bluetooth adapter is powered on");
+                        self.load_adapter(sender.clone());
+                    } else {
+                        log::warn!("Bluetooth adapter is powered off");
+                        self.known_devices_task.take().map(|h| h.abort());
+                        sender.input(Input::StopDiscovery);
+                        self.devices.guard().clear();
+                        self.stop_gatt_server();
+                    }
+                }
+            }
+
+            Input::PowerOnAdapter => {
+                if let Some(adapter) = self.adapter.clone() {
+                    // Power state change is picked up by the adapter event stream
+                    relm4::spawn(async move {
+                        if let Err(error) = adapter.set_powered(true).await {
+                            log::error!("Failed to turn on bluetooth adapter: {error}");
+                            ui::BROKER.send(ui::Input::Toast(gettext("Failed to turn on Bluetooth")));
+                        }
+                    });
                 }
             }
 
             Input::StartDiscovery => {
-                if self.discovery_task.is_none() {
+                if self.discovery_task.is_none() && self.adapter_powered == Some(true) {
                     if let Some(adapter) = self.adapter.clone() {
                         self.devices.guard().clear();
+                        let sender_ = sender.clone();
                         self.discovery_task = Some(relm4::spawn(async move {
-                            Self::run_discovery(adapter.clone(), sender.clone()).await;
-                            sender.input(Input::DiscoveryFailed);
+                            Self::run_discovery(adapter.clone(), sender_.clone()).await;
+                            sender_.input(Input::DiscoveryFailed);
                         }));
+                        self.discovery_timed_out = false;
+                        self.restart_discovery_timeout(sender.clone());
                         log::info!("Device discovery started");
                     }
                 }
             }
 
             Input::StopDiscovery => {
+                self.discovery_timeout_task.take().map(|h| h.abort());
                 if let Some(handle) = self.discovery_task.take() {
                     handle.abort();
                     log::info!("Device discovery stopped");
                 }
             }
 
+            Input::AutoStartDiscovery => {
+                // Discovery is still needed to find the device to auto-connect to
+                if self.autoconnect_address.is_some() || self.settings.boolean(super::SETTING_AUTO_DISCOVERY) {
+                    sender.input(Input::StartDiscovery);
+                }
+            }
+
+            Input::ToggleDiscovery => {
+                if self.discovery_task.is_some() {
+                    sender.input(Input::StopDiscovery);
+                } else {
+                    sender.input(Input::StartDiscovery);
+                }
+            }
+
             Input::DiscoveryFailed => {
                 log::error!("Device discovery failed");
                 self.discovery_task = None;
+                self.discovery_timeout_task.take().map(|h| h.abort());
             }
 
-            Input::DeviceInfoReady(info) => {
+            Input::DiscoveryTimedOut => {
+                self.discovery_timeout_task = None;
+                if self.autoconnect_address.is_none() && self.discovery_task.is_some() {
+                    log::info!("No device found in time, stopping discovery");
+                    self.discovery_timed_out = true;
+                    sender.input(Input::StopDiscovery);
+                } else if self.reconnecting && self.discovery_task.is_some() {
+                    log::debug!("Lost device is not found, retrying");
+                    sender.input(Input::StopDiscovery);
+                    self.schedule_reconnect(&sender);
+                }
+            }
+
+            Input::DeviceInfoReady(mut info) => {
                 let address = info.address;
-                let mut devices = self.devices.guard();
-                devices.push_front(info);
+                info.nickname = super::device_nickname(&self.settings, &address.to_string());
+                if self.discovery_task.is_some() {
+                    self.restart_discovery_timeout(sender.clone());
+                }
+                let index = self.insert_device(info);
                 if Some(address) == self.autoconnect_address {
                     log::debug!("Detected lost device: {}. Trying to reconnect...", address);
                     sender.input(Input::StopDiscovery);
-                    devices.send(0, DeviceInput::Connect);
+                    if self.devices.get(index).is_some_and(|d| d.state == DeviceState::Disconnected) {
+                        self.devices.send(index, DeviceInput::Connect);
+                    }
                 }
             }
 
@@ -315,15 +964,23 @@ impl Component for Model {
                     if let Ok(device) = adapter.device(address) {
                         let device = Arc::new(device);
                         let saved = Some(address) == self.saved_address;
-                        relm4::spawn(async move {
-                            if bt::InfiniTime::check_device(&device).await {
+                        let name_filter = self.name_filter.clone();
+                        let threshold = self.rssi_threshold().filter(|_| !saved);
+                        let sender = sender.clone();
+                        let task = relm4::spawn(async move {
+                            if bt::InfiniTime::check_device(&device, &name_filter).await {
                                 log::debug!("Device discovered: {}", address);
                                 match DeviceInfo::new(device, saved).await {
+                                    // BlueZ reports devices it already knows regardless of the signal
+                                    Ok(info) if is_too_far(info.rssi, threshold) => {
+                                        log::debug!("Device {} is too far: {:?} dBm", address, info.rssi);
+                                    }
                                     Ok(info) => sender.input(Input::DeviceInfoReady(info)),
                                     Err(error) => log::error!("Failed to read device info: {}", error),
                                 }
                             }
                         });
+                        self.track_device_info_task(task);
                     }
                 }
             }
@@ -340,6 +997,54 @@ impl Component for Model {
                 }
             }
 
+            Input::DeviceAddedManually(text) => {
+                let address = match bluer::Address::from_str(text.trim()) {
+                    Ok(address) => address,
+                    Err(_) => {
+                        ui::BROKER.send(ui::Input::Toast(gettext("Invalid device address")));
+                        return;
+                    }
+                };
+                if self.devices.iter().any(|d| d.address == address) {
+                    ui::BROKER.send(ui::Input::Toast(gettext("Device is already in the list")));
+                    return;
+                }
+                if let Some(adapter) = self.adapter.clone() {
+                    let saved = Some(address) == self.saved_address;
+                    let sender = sender.clone();
+                    let task = relm4::spawn(async move {
+                        // Device might be already known to BlueZ, otherwise connect to it directly
+                        let known = match adapter.device(address) {
+                            Ok(device) => DeviceInfo::new(Arc::new(device), saved).await.ok(),
+                            Err(_) => None,
+                        };
+                        if let Some(info) = known {
+                            sender.input(Input::DeviceInfoReady(info));
+                            return;
+                        }
+                        log::info!("Device {} is unknown, trying to connect directly", address);
+                        let result = adapter.connect_device(address, bluer::AddressType::LeRandom).await;
+                        match result {
+                            Ok(device) => {
+                                let device = Arc::new(device);
+                                match DeviceInfo::new(device.clone(), saved).await {
+                                    Ok(info) => {
+                                        sender.input(Input::DeviceInfoReady(info));
+                                        sender.input(Input::DeviceConnected(device));
+                                    }
+                                    Err(error) => log::error!("Failed to read device info: {}", error),
+                                }
+                            }
+                            Err(error) => {
+                                log::error!("Failed to connect to {}: {}", address, error);
+                                ui::BROKER.send(ui::Input::Toast(gettext("Device not found")));
+                            }
+                        }
+                    });
+                    self.track_device_info_task(task);
+                }
+            }
+
             Input::DeviceSelected(index) => {
                 log::debug!("Device selected: {}", index);
                 sender.input(Input::StopDiscovery);
@@ -351,11 +1056,40 @@ impl Component for Model {
                 }
             }
 
+            Input::DeviceConnecting => {
+                sender.output(Output::DeviceConnecting).unwrap();
+            }
+
+            Input::ConnectSaved => {
+                if let Some(address) = self.saved_address {
+                    let result = self.devices.iter().enumerate().find(|(_, d)| d.address == address);
+                    match result {
+                        Some((index, device)) => {
+                            if device.state == DeviceState::Disconnected {
+                                sender.input(Input::DeviceSelected(index as i32));
+                            }
+                        }
+                        None => {
+                            // Connect as soon as it is discovered
+                            self.autoconnect_address = Some(address);
+                            sender.input(Input::StartDiscovery);
+                        }
+                    }
+                }
+            }
+
+            Input::Disconnect(address) => {
+                let result = self.devices.iter().enumerate().find(|(_, d)| d.address == address);
+                if let Some((index, _)) = result {
+                    self.devices.send(index, DeviceInput::Disconnect);
+                }
+            }
+
             Input::DeviceConnected(device) => {
                 log::debug!("Device connected successfully: {}", device.address());
                 self.autoconnect_address = None;
-                _ = self.settings.set_string(super::SETTING_DEVICE_ADDRESS, &device.address().to_string());
-                sender.input(Input::SaveAddress(Some(device.address())));
+                self.disconnecting_address = None;
+                self.update_saved_address(SavedAddressEvent::Connected(device.address()), &sender);
                 sender.output(Output::DeviceConnected(device)).unwrap();
             }
 
@@ -364,7 +1098,6 @@ impl Component for Model {
                 if Some(device.address()) == self.autoconnect_address {
                     self.autoconnect_address = None;
                 }
-                self.disconnecting_address = None;
                 // Repopulate known devices
                 sender.input(Input::StopDiscovery);
                 sender.input(Input::StartDiscovery);
@@ -376,20 +1109,116 @@ impl Component for Model {
 
             Input::DeviceConnectionFailed => {
                 log::debug!("Device connection failed");
-                sender.input(Input::StartDiscovery);
+                sender.output(Output::DeviceConnectionFailed).unwrap();
+                if self.reconnecting && self.autoconnect_address.is_some() {
+                    self.schedule_reconnect(&sender);
+                } else {
+                    sender.input(Input::StartDiscovery);
+                }
             }
 
-            Input::DeviceConnectionLost(address) => {
-                log::debug!("Device connection lost: {}", address);
+            Input::DeviceConnectionEnded(address) => {
+                // Either this or DeviceDisconnected can come first,
+                // so the requested address is only reset here
+                let reason = if self.disconnecting_address == Some(address) {
+                    self.disconnecting_address = None;
+                    DisconnectReason::Requested
+                } else {
+                    DisconnectReason::Lost
+                };
+                log::debug!("Device connection ended: {} ({:?})", address, reason);
+                sender.output(Output::DeviceDisconnected(reason)).unwrap();
 
                 let devices = self.devices.guard();
                 let result = devices.iter().enumerate().find(|(_, d)| d.address == address);
                 if let Some((idx, _)) = result {
                     devices.send(idx, DeviceInput::StateUpdated(DeviceState::Disconnected));
                 }
-                if Some(address) != self.disconnecting_address && Some(address) == self.saved_address {
+                if reason == DisconnectReason::Lost
+                    && Some(address) == self.saved_address
+                    && self.auto_connect_enabled()
+                {
                     self.autoconnect_address = Some(address);
-                    sender.input(Input::StartDiscovery);
+                    self.reconnecting = true;
+                    self.reconnect_attempts = 0;
+                    sender.output(Output::Reconnecting(true)).unwrap();
+                    // Scanning is restarted by the attempt, limited in time
+                    sender.input(Input::StopDiscovery);
+                    self.schedule_reconnect(&sender);
+                }
+            }
+
+            Input::RetryReconnecting => {
+                if let Some(address) = self.saved_address.filter(|_| !self.reconnecting) {
+                    log::info!("Retrying to reconnect to {}", address);
+                    self.autoconnect_address = Some(address);
+                    self.reconnecting = true;
+                    sender.output(Output::Reconnecting(true)).unwrap();
+                    self.schedule_reconnect(&sender);
+                }
+            }
+
+            Input::StopReconnecting => {
+                if self.reconnecting {
+                    log::info!("Reconnection cancelled");
+                    self.autoconnect_address = None;
+                    sender.input(Input::StopDiscovery);
+                }
+            }
+
+            Input::CancelKnownDevices => {
+                if let Some(task) = self.known_devices_task.take() {
+                    task.abort();
+                    log::info!("Loading known devices is cancelled");
+                    sender.input(Input::AutoStartDiscovery);
+                }
+            }
+
+            Input::PasskeyRequested(address, reply) => {
+                Self::show_passkey_dialog(address, reply);
+            }
+
+            Input::Shutdown(disconnect) => {
+                log::info!("Shutting down Bluetooth services");
+                self.autoconnect_address = None;
+                self.reconnect_task.take().map(|h| h.abort());
+                self.discovery_timeout_task.take().map(|h| h.abort());
+                self.discovery_task.take().map(|h| h.abort());
+                self.known_devices_task.take().map(|h| h.abort());
+                self.adapter_task.take().map(|h| h.abort());
+                self.stop_gatt_server();
+                self.agent = None;
+                let connected = match disconnect {
+                    true => self.devices.iter()
+                        .filter(|d| d.state == DeviceState::Connected)
+                        .map(|d| d.device.clone())
+                        .collect(),
+                    false => Vec::new(),
+                };
+                sender.oneshot_command(async move {
+                    let disconnect_all = futures::future::join_all(connected.iter().map(|d| d.disconnect()));
+                    match tokio::time::timeout(SHUTDOWN_TIMEOUT, disconnect_all).await {
+                        Ok(results) => for error in results.into_iter().filter_map(Result::err) {
+                            log::warn!("Failed to disconnect on quit: {error}");
+                        }
+                        Err(_) => log::warn!("Disconnecting on quit timed out"),
+                    }
+                    CommandOutput::ShutdownComplete
+                });
+            }
+
+            Input::RemoveBond(address) => {
+                if let Some(adapter) = self.adapter.clone() {
+                    // Device is removed from the list by the discovery event
+                    relm4::spawn(async move {
+                        match adapter.remove_device(address).await {
+                            Ok(()) => log::info!("Bond with {address} is removed"),
+                            Err(error) => {
+                                log::error!("Failed to remove bond with {address}: {error}");
+                                ui::BROKER.send(ui::Input::Toast(gettext("Failed to remove bond")));
+                            }
+                        }
+                    });
                 }
             }
 
@@ -399,7 +1228,63 @@ impl Component for Model {
                 _ = self.settings.set_string(super::SETTING_DEVICE_ADDRESS, &address_str);
                 self.devices.broadcast(DeviceInput::SavedAddress(address));
             }
+
+            Input::ToggleSaved(address) => {
+                self.update_saved_address(SavedAddressEvent::Toggled(address), &sender);
+            }
+
+            Input::GattServerToggled(enabled) => {
+                if !enabled {
+                    self.stop_gatt_server();
+                } else if self.adapter_powered == Some(true) && self.gatt_server.is_none() && self.gatt_task.is_none() {
+                    self.start_gatt_server(&sender);
+                }
+            }
+
+            Input::NicknamesChanged => {
+                for (index, device) in self.devices.iter().enumerate() {
+                    let nickname = super::device_nickname(&self.settings, &device.address.to_string());
+                    self.devices.send(index, DeviceInput::Nickname(nickname));
+                }
+            }
+
+            Input::ForgetAll => {
+                log::info!("Forgetting saved devices");
+                self.autoconnect_address = None;
+                sender.input(Input::SaveAddress(None));
+                sender.input(Input::StopDiscovery);
+                let mut disconnecting = false;
+                for (index, device) in self.devices.iter().enumerate() {
+                    match device.state {
+                        DeviceState::Connected => {
+                            self.devices.send(index, DeviceInput::Disconnect);
+                            disconnecting = true;
+                        }
+                        DeviceState::Transitioning => {
+                            self.devices.send(index, DeviceInput::CancelConnect);
+                        }
+                        DeviceState::Disconnected => {}
+                    }
+                }
+                // Otherwise discovery is restarted once the device is disconnected
+                if !disconnecting {
+                    sender.input(Input::StartDiscovery);
+                }
+            }
+
+            Input::NameFilterChanged(name_filter) => {
+                if name_filter.is_empty() {
+                    ui::BROKER.send(ui::Input::Toast(gettext("Name filter is empty, all nearby devices will be listed")));
+                }
+                self.name_filter = name_filter;
+                self.update_discovery_filter(&sender);
+            }
+
+            Input::DiscoveryFilterChanged => {
+                self.update_discovery_filter(&sender);
+            }
         }
+        self.update_reconnecting(&sender);
     }
 
     fn update_cmd(&mut self, msg: Self::CommandOutput, sender: ComponentSender<Self>, _root: &Self::Root) {
@@ -408,7 +1293,12 @@ impl Component for Model {
                 Ok(session) => {
                     let session = Arc::new(session);
                     self.session = Some(session.clone());
-                    relm4::spawn(Self::run_session_stream(session, sender.clone()));
+                    relm4::spawn(Self::run_session_stream(session.clone(), sender.clone()));
+                    let agent = Self::pairing_agent(sender.clone());
+                    sender.oneshot_command(async move {
+                        CommandOutput::AgentResult(session.register_agent(agent).await)
+                    });
+                    self.refresh_adapter_names(&sender);
                     sender.input(Input::InitAdapter);
                 }
                 Err(error) => {
@@ -420,50 +1310,84 @@ impl Component for Model {
                     log::debug!("Bluetooth adapter is initialized");
                     let adapter = Arc::new(adapter);
                     self.adapter = Some(adapter.clone());
+                    self.adapter_powered = None;
 
-                    // Start GATT serices
-                    let adapter_ = adapter.clone();
-                    sender.oneshot_command(async move {
-                        CommandOutput::GattServicesResult(bt::start_gatt_services(&adapter_).await)
-                    });
-
-                    // Read known devices list
-                    let saved_address = self.saved_address.clone();
+                    // Monitor power state, known devices are loaded once it's on
+                    self.adapter_task.take().map(|h| h.abort());
+                    self.adapter_task = Some(relm4::spawn(Self::run_adapter_stream(adapter.clone(), sender.clone())));
                     sender.oneshot_command(async move {
-                        let mut devices = Vec::new();
-                        for device in bt::InfiniTime::list_known_devices(&adapter).await.unwrap() {
-                            let saved = Some(device.address()) == saved_address;
-                            devices.push(DeviceInfo::new(Arc::new(device), saved).await.unwrap())
-                        }
-                        CommandOutput::KnownDevices(devices)
+                        CommandOutput::AdapterPoweredResult(adapter.is_powered().await)
                     });
                 }
                 Err(error) => {
                     log::error!("Failed to initialize bluetooth adapter: {error}");
                 }
             }
+            CommandOutput::AgentResult(result) => match result {
+                Ok(handle) => {
+                    log::debug!("Pairing agent is registered");
+                    self.agent = Some(handle);
+                }
+                Err(error) => {
+                    log::error!("Failed to register pairing agent: {error}");
+                }
+            }
+            CommandOutput::AdapterNames(result) => match result {
+                Ok(names) => {
+                    sender.output(Output::AdapterNames(names)).unwrap();
+                }
+                Err(error) => {
+                    log::error!("Failed to list bluetooth adapters: {error}");
+                }
+            }
+            CommandOutput::AdapterPoweredResult(result) => match result {
+                Ok(powered) => {
+                    sender.input(Input::AdapterPowered(powered));
+                }
+                Err(error) => {
+                    log::error!("Failed to read bluetooth adapter power state: {error}");
+                }
+            }
             CommandOutput::GattServicesResult(result) => match result {
                 Ok(handle) => {
-                    self.gatt_server = Some(handle);
+                    self.gatt_task = None;
+                    // Might have been disabled while starting
+                    if self.settings.boolean(super::SETTING_GATT_SERVER) {
+                        self.gatt_server = Some(handle);
+                    }
                 }
                 Err(error) => {
+                    self.gatt_task = None;
                     log::error!("Failed to start GATT server: {error}");
-                    ui::BROKER.send(ui::Input::ToastStatic("Failed to start GATT server"));
+                    let message = if Self::is_gatt_error_permanent(&error) {
+                        gettext("GATT server registration is rejected, the watch can't sync time. Check Bluetooth permissions")
+                    } else {
+                        gettext("Failed to start GATT server, the watch can't sync time. Try restarting Bluetooth")
+                    };
+                    ui::BROKER.send(ui::Input::Toast(message));
                 }
             }
 
+            CommandOutput::ShutdownComplete => {
+                sender.output(Output::ShutdownComplete).unwrap();
+            }
+
             CommandOutput::KnownDevices(devices) => {
+                self.known_devices_task = None;
                 let connected = devices.iter()
                     .find(|d| d.state == DeviceState::Connected)
                     .map(|d| d.address);
 
-                let mut devices_guard = self.devices.guard();
-                for device in devices {
-                    devices_guard.push_back(device);
+                for mut device in devices {
+                    device.nickname = super::device_nickname(&self.settings, &device.address.to_string());
+                    self.insert_device(device);
                 }
 
                 // Automatic device selection logic
-                if let Some(address) = connected {
+                if !self.auto_connect_enabled() {
+                    // Only list the devices, connection is up to the user
+                    sender.input(Input::AutoStartDiscovery);
+                } else if let Some(address) = connected {
                     // If suitable device is already connected - just report it as connected
                     if let Some(adapter) = &self.adapter {
                         if let Ok(device) = adapter.device(address) {
@@ -474,18 +1398,19 @@ impl Component for Model {
                         }
                     }
                 } else {
-                    if let Some((i, d)) = devices_guard.iter().enumerate().find(
+                    if let Some((i, d)) = self.devices.iter().enumerate().find(
                         |(_, d)| Some(d.address) == self.autoconnect_address
                     ) {
                         log::info!("Trying to connect to InfiniTime ({})", d.address.to_string());
-                        devices_guard.send(i, DeviceInput::Connect);
+                        self.devices.send(i, DeviceInput::Connect);
                     } else {
                         // Otherwise, start discovery
-                        sender.input(Input::StartDiscovery);
+                        sender.input(Input::AutoStartDiscovery);
                     }
                 }
             }
         }
+        self.update_reconnecting(&sender);
     }
 }
 
@@ -493,10 +1418,15 @@ impl Component for Model {
 pub struct DeviceInfo {
     address: bluer::Address,
     alias: String,
+    // Local name, shown instead of the alias if set
+    nickname: Option<String>,
     rssi: Option<i16>,
     state: DeviceState,
     device: Arc<bluer::Device>,
     saved: bool,
+    paired: bool,
+    pairing: bool,
+    connect_task: Option<Arc<JoinHandle<()>>>,
 }
 
 impl DeviceInfo {
@@ -509,12 +1439,20 @@ impl DeviceInfo {
         Ok(Self {
             address: device.address(),
             alias: device.alias().await?,
+            nickname: None,
             rssi: device.rssi().await?,
             state,
+            paired: device.is_paired().await?,
+            pairing: false,
             device,
             saved,
+            connect_task: None,
         })
     }
+
+    fn list_order(&self) -> ListOrder {
+        list_order(self.state == DeviceState::Connected, self.saved, self.rssi)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -527,19 +1465,70 @@ pub enum DeviceState {
 #[derive(Clone, Debug)]
 pub enum DeviceInput {
     Connect,
+    CancelConnect,
     Disconnect,
     StateUpdated(DeviceState),
     SavedToggle,
     SavedAddress(Option<bluer::Address>),
+    Nickname(Option<String>),
+    Pair,
+    Paired(bool),
+    RemoveBond,
 }
 
 #[derive(Debug)]
 pub enum DeviceOutput {
+    Connecting,
     Connected(Arc<bluer::Device>),
     Disconnected(Arc<bluer::Device>),
     Disconnecting(Arc<bluer::Device>),
     ConnectionFailed,
-    SaveAddress(Option<bluer::Address>),
+    ToggleSaved(bluer::Address),
+    RemoveBond(bluer::Address),
+}
+
+/// Events affecting the saved (auto-connect) device address
+#[derive(Debug, Clone, Copy)]
+enum SavedAddressEvent {
+    Connected(bluer::Address),
+    Toggled(bluer::Address),
+}
+
+/// Saved address after `event`. Only the explicit toggle changes it,
+/// connecting to a device doesn't make it the saved one
+fn saved_address_after(saved: Option<bluer::Address>, event: SavedAddressEvent) -> Option<bluer::Address> {
+    match event {
+        SavedAddressEvent::Connected(_) => saved,
+        SavedAddressEvent::Toggled(address) if saved == Some(address) => None,
+        SavedAddressEvent::Toggled(address) => Some(address),
+    }
+}
+
+/// RSSI threshold setting value, 0 means none
+fn rssi_threshold(value: i32) -> Option<i16> {
+    match value {
+        0 => None,
+        value => Some(value.clamp(i16::MIN as i32, 0) as i16),
+    }
+}
+
+/// Devices with unknown signal strength are kept
+fn is_too_far(rssi: Option<i16>, threshold: Option<i16>) -> bool {
+    matches!((rssi, threshold), (Some(rssi), Some(threshold)) if rssi < threshold)
+}
+
+/// Sort key of the device list: connected first, then saved,
+/// then by signal strength (devices out of range last)
+type ListOrder = (bool, bool, Reverse<Option<i16>>);
+
+fn list_order(connected: bool, saved: bool, rssi: Option<i16>) -> ListOrder {
+    (!connected, !saved, Reverse(rssi))
+}
+
+/// Position after the devices that go before or on par with `order`,
+/// so equal ones keep their insertion order
+fn insert_position(orders: impl Iterator<Item = ListOrder>, order: ListOrder) -> usize {
+    orders.take_while(|o| *o <= order).count()
 }
 
 // Factory for device list
@@ -563,10 +1552,10 @@ impl FactoryComponent for DeviceInfo {
 
                 gtk::Button {
                     #[watch]
-                    set_tooltip_text: match self.saved {
-                        true => Some("Disable automatic re-connection"),
-                        false => Some("Enable automatic re-connection"),
-                    },
+                    set_tooltip_text: Some(&match self.saved {
+                        true => gettext("Disable automatic re-connection"),
+                        false => gettext("Enable automatic re-connection"),
+                    }),
                     #[watch]
                     set_icon_name: match self.saved {
                         true => "heart-filled-symbolic",
@@ -583,7 +1572,9 @@ impl FactoryComponent for DeviceInfo {
                     gtk::Label {
                         set_halign: gtk::Align::Start,
                         set_hexpand: true,
-                        set_label: &self.alias,
+                        #[watch]
+                        set_label: self.nickname.as_deref().unwrap_or(&self.alias),
+                        set_tooltip_text: Some(&self.alias),
                     },
 
                     gtk::Box {
@@ -610,7 +1601,25 @@ impl FactoryComponent for DeviceInfo {
                 },
 
                 gtk::Button {
-                    set_tooltip_text: Some("Click to disconnect"),
+                    set_tooltip_text: Some(&gettext("Pair")),
+                    set_icon_name: "channel-insecure-symbolic",
+                    add_css_class: "flat",
+                    #[watch]
+                    set_visible: !self.paired && !self.pairing && self.state != DeviceState::Transitioning,
+                    connect_clicked => DeviceInput::Pair,
+                },
+
+                gtk::Button {
+                    set_tooltip_text: Some(&gettext("Paired, click to remove bond")),
+                    set_icon_name: "channel-secure-symbolic",
+                    add_css_class: "flat",
+                    #[watch]
+                    set_visible: self.paired && self.state != DeviceState::Transitioning,
+                    connect_clicked => DeviceInput::RemoveBond,
+                },
+
+                gtk::Button {
+                    set_tooltip_text: Some(&gettext("Click to disconnect")),
                     set_icon_name: "cross-symbolic",
                     add_css_class: "flat",
                     #[watch]
@@ -620,9 +1629,18 @@ impl FactoryComponent for DeviceInfo {
 
                 gtk::Spinner {
                     #[watch]
-                    set_visible: self.state == DeviceState::Transitioning,
+                    set_visible: self.state == DeviceState::Transitioning || self.pairing,
                     set_spinning: true,
                 },
+
+                gtk::Button {
+                    set_tooltip_text: Some(&gettext("Cancel connection")),
+                    set_icon_name: "cross-symbolic",
+                    add_css_class: "flat",
+                    #[watch]
+                    set_visible: self.state == DeviceState::Transitioning && self.connect_task.is_some(),
+                    connect_clicked => DeviceInput::CancelConnect,
+                },
             },
         }
     }
@@ -654,8 +1672,9 @@ impl FactoryComponent for DeviceInfo {
         match msg {
             DeviceInput::Connect => {
                 self.state = DeviceState::Transitioning;
+                _ = sender.output(DeviceOutput::Connecting);
                 let device = self.device.clone();
-                relm4::spawn(async move {
+                let task = relm4::spawn(async move {
                     match device.connect().await {
                         Ok(()) => {
                             sender.input(DeviceInput::StateUpdated(DeviceState::Connected));
@@ -668,6 +1687,23 @@ impl FactoryComponent for DeviceInfo {
                         }
                     }
                 });
+                self.connect_task = Some(Arc::new(task));
+            }
+
+            DeviceInput::CancelConnect => {
+                if let Some(task) = self.connect_task.take() {
+                    task.abort();
+                    log::info!("Connection to {} is cancelled", self.address);
+                    self.state = DeviceState::Disconnected;
+                    _ = sender.output(DeviceOutput::ConnectionFailed);
+                    // Clean up half-open connection, if any
+                    let device = self.device.clone();
+                    relm4::spawn(async move {
+                        if let Err(error) = device.disconnect().await {
+                            log::debug!("Disconnection after cancelled connection failed: {}", error);
+                        }
+                    });
+                }
             }
 
             DeviceInput::Disconnect => {
@@ -690,20 +1726,102 @@ impl FactoryComponent for DeviceInfo {
             }
 
             DeviceInput::StateUpdated(state) => {
+                if state != DeviceState::Transitioning {
+                    self.connect_task = None;
+                }
                 self.state = state;
             }
 
             DeviceInput::SavedToggle => {
-                let address = match self.saved {
-                    true => None,
-                    false => Some(self.address),
-                };
-                _ = sender.output(DeviceOutput::SaveAddress(address))
+                _ = sender.output(DeviceOutput::ToggleSaved(self.address))
             }
 
             DeviceInput::SavedAddress(address) => {
                 self.saved = Some(self.address) == address;
             }
+
+            DeviceInput::Nickname(nickname) => {
+                self.nickname = nickname;
+            }
+
+            DeviceInput::Pair => {
+                self.pairing = true;
+                let device = self.device.clone();
+                relm4::spawn(async move {
+                    // Connects to the device if needed
+                    if let Err(error) = device.pair().await {
+                        log::error!("Pairing with {} failed: {}", device.address(), error);
+                        ui::BROKER.send(ui::Input::Toast(gettext("Pairing failed")));
+                    }
+                    sender.input(DeviceInput::Paired(device.is_paired().await.unwrap_or(false)));
+                });
+            }
+
+            DeviceInput::Paired(paired) => {
+                self.pairing = false;
+                self.paired = paired;
+            }
+
+            DeviceInput::RemoveBond => {
+                _ = sender.output(DeviceOutput::RemoveBond(self.address));
+            }
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WATCH: bluer::Address = bluer::Address([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01]);
+    const OTHER_WATCH: bluer::Address = bluer::Address([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x02]);
+
+    #[test]
+    fn connecting_keeps_saved_address() {
+        assert_eq!(saved_address_after(None, SavedAddressEvent::Connected(WATCH)), None);
+        assert_eq!(saved_address_after(Some(OTHER_WATCH), SavedAddressEvent::Connected(WATCH)), Some(OTHER_WATCH));
+    }
+
+    #[test]
+    fn toggling_saves_and_forgets() {
+        assert_eq!(saved_address_after(None, SavedAddressEvent::Toggled(WATCH)), Some(WATCH));
+        assert_eq!(saved_address_after(Some(WATCH), SavedAddressEvent::Toggled(WATCH)), None);
+        // Saving another device replaces the saved one
+        assert_eq!(saved_address_after(Some(OTHER_WATCH), SavedAddressEvent::Toggled(WATCH)), Some(WATCH));
+    }
+
+    #[test]
+    fn rssi_filtering() {
+        assert_eq!(rssi_threshold(0), None);
+        assert_eq!(rssi_threshold(-70), Some(-70));
+        assert!(is_too_far(Some(-80), Some(-70)));
+        assert!(!is_too_far(Some(-70), Some(-70)));
+        assert!(!is_too_far(None, Some(-70)));
+        assert!(!is_too_far(Some(-100), None));
+    }
+
+    #[test]
+    fn list_order_priorities() {
+        assert!(list_order(true, false, None) < list_order(false, true, Some(-40)));
+        assert!(list_order(false, true, None) < list_order(false, false, Some(-40)));
+        assert!(list_order(false, false, Some(-40)) < list_order(false, false, Some(-80)));
+        assert!(list_order(false, false, Some(-80)) < list_order(false, false, None));
+    }
+
+    #[test]
+    fn insert_keeps_order() {
+        let listed = [
+            list_order(true, false, Some(-50)),
+            list_order(false, false, Some(-60)),
+            list_order(false, false, Some(-60)),
+            list_order(false, false, None),
+        ];
+        let position = |order| insert_position(listed.into_iter(), order);
+        assert_eq!(position(list_order(false, true, None)), 1);
+        assert_eq!(position(list_order(false, false, Some(-60))), 3);
+        assert_eq!(position(list_order(false, false, Some(-90))), 3);
+        assert_eq!(position(list_order(false, false, None)), 4);
+        assert_eq!(insert_position(std::iter::empty(), list_order(true, true, None)), 0);
+    }
+}
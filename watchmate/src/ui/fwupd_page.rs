@@ -1,12 +1,14 @@
-use crate::ui;
+use crate::{i18n::{gettext, gettext_f}, ui};
 use infinitime::{
     tokio::{self, io::AsyncReadExt},
     bt::{self, ProgressEvent, InfiniTime}, gh
 };
 
-use std::{sync::Arc, path::PathBuf};
-use gtk::prelude::{BoxExt, ButtonExt, OrientableExt, WidgetExt};
+use std::{future::Future, sync::Arc, path::PathBuf, time::Instant};
+use futures::channel::oneshot;
+use gtk::prelude::{BoxExt, ButtonExt, GtkApplicationExt, OrientableExt, WidgetExt};
 use relm4::{adw, gtk, ComponentParts, ComponentSender, Component, JoinHandle, RelmWidgetExt};
+use ashpd::{desktop::inhibit::{InhibitFlags, InhibitProxy}, WindowIdentifier};
 
 #[derive(Debug)]
 pub enum Input {
@@ -14,6 +16,7 @@ pub enum Input {
     Disconnected,
 
     FlashAssetFromFile(PathBuf, AssetType),
+    FlashAssetFromFiles(Vec<PathBuf>, AssetType),
     FlashAssetFromUrl(String, AssetType),
 
     ContentReady(Vec<u8>),
@@ -23,7 +26,18 @@ pub enum Input {
     OtaFailed(String),
 
     Retry,
+    TaskEnded,
     Abort,
+    TargetConfirmed,
+}
+
+#[derive(Debug)]
+pub enum Output {
+    OtaProgress(AssetType, u32),
+    OtaCompleted(AssetType),
+    /// Resources of the known version are flashed
+    ResourcesInstalled(String),
+    OtaFailed(AssetType, String),
 }
 
 pub enum Source {
@@ -31,9 +45,11 @@ pub enum Source {
     Url(Arc<String>),
 }
 
-#[derive(PartialEq, Default)]
+#[derive(Debug, PartialEq, Default)]
 pub enum State {
     InProgress,
+    // Waiting for the user to confirm flashing unexpected DFU target
+    Confirming,
     Aborted,
     #[default]
     Finished,
@@ -47,57 +63,459 @@ pub enum AssetType {
 }
 
 impl AssetType {
-    fn name(&self) -> &'static str {
+    pub fn name(&self) -> &'static str {
         match self {
             AssetType::Firmware => "Firmware",
             AssetType::Resources => "Resources",
         }
     }
+
+    /// Translated name for display
+    pub fn display_name(&self) -> String {
+        match self {
+            AssetType::Firmware => gettext("Firmware"),
+            AssetType::Resources => gettext("Resources"),
+        }
+    }
+}
+
+/// Side effects requested by [`Model::handle`]. They are performed
+/// separately, so that the update logic can be tested without a device
+#[derive(Debug)]
+enum Effect {
+    ReadFile(Arc<PathBuf>),
+    Download(Arc<String>),
+    Flash(Arc<Vec<u8>>),
+    AbortTask,
+    Output(Output),
+}
+
+impl Effect {
+    fn starts_task(&self) -> bool {
+        matches!(self, Effect::ReadFile(_) | Effect::Download(_) | Effect::Flash(_))
+    }
+}
+
+/// Reports the end of a task, including abortion, when dropped
+struct TaskEndGuard(ComponentSender<Model>);
+
+impl Drop for TaskEndGuard {
+    fn drop(&mut self) {
+        self.0.input(Input::TaskEnded);
+    }
 }
 
+/// Smoothing factor of the transfer rate moving average
+const RATE_SMOOTHING: f64 = 0.2;
+
 #[derive(Default)]
 pub struct Model {
     progress_status: String,
     progress_current: u32,
     progress_total: u32,
+    progress_fraction: f32,
+    progress_timestamp: Option<Instant>,
+    // Smoothed transfer rate in bytes per second
+    transfer_rate: Option<f64>,
     state: State,
     asset_type: AssetType,
     asset_content: Option<Arc<Vec<u8>>>,
     asset_source: Option<Source>,
+    // Resources version from the source file name
+    asset_version: Option<String>,
+    // Files to flash one after another, and the index of the current one
+    file_queue: Vec<Arc<PathBuf>>,
+    queue_index: usize,
+    // Application the firmware is built for, and whether the user
+    // agreed to flash it even though it doesn't look like PineTime one
+    dfu_target: Option<String>,
+    target_confirmed: bool,
 
     infinitime: Option<Arc<bt::InfiniTime>>,
+    // Kept after the task ends, so that the next one can wait for it
     task_handle: Option<JoinHandle<()>>,
+    // Tasks that haven't ended yet, including aborted ones that are still
+    // unwinding. Retry is unavailable until they are gone
+    running_tasks: usize,
+    // Releases the suspend inhibitor when sent or dropped
+    inhibit_release: Option<oneshot::Sender<()>>,
 }
 
 impl Model {
-    fn download_asset(url: Arc<String>, sender: ComponentSender<Self>) -> JoinHandle<()> {
-        relm4::spawn(async move {
-            match gh::download_content(url.as_str()).await {
-                Ok(content) => sender.input(Input::ContentReady(content)),
-                Err(_) => sender.input(Input::OtaFailed("Downloading failed".to_string())),
+    fn reset_progress(&mut self) {
+        self.progress_current = 0;
+        self.progress_total = 0;
+        self.progress_fraction = 0.0;
+        self.progress_timestamp = None;
+        self.transfer_rate = None;
+    }
+
+    /// Keep the host from suspending or idling while flashing, as an
+    /// interrupted transfer can leave the watch unbootable
+    fn inhibit_suspend(&mut self) {
+        let (release_tx, release_rx) = oneshot::channel::<()>();
+        self.inhibit_release = Some(release_tx);
+        let native_window = relm4::main_application()
+            .active_window()
+            .and_then(|w| w.native());
+        relm4::spawn_local(async move {
+            let identifier = match native_window {
+                Some(native) => WindowIdentifier::from_native(&native).await,
+                None => WindowIdentifier::default(),
+            };
+            let reason = gettext("Firmware update is in progress");
+            let flags = InhibitFlags::Suspend | InhibitFlags::Idle;
+            let request = match InhibitProxy::new().await {
+                Ok(proxy) => proxy.inhibit(&identifier, flags, &reason).await,
+                Err(error) => Err(error),
+            };
+            match request {
+                Ok(request) => {
+                    log::info!("Suspend inhibited for the firmware update");
+                    // Either released explicitly or the sender is dropped
+                    _ = release_rx.await;
+                    match request.close().await {
+                        Ok(()) => log::info!("Suspend inhibit released"),
+                        Err(error) => log::error!("Failed to release suspend inhibit: {error}"),
+                    }
+                }
+                Err(error) => {
+                    log::warn!("Failed to inhibit suspend, the host may sleep during the update: {error}");
+                }
             }
-        })
+        });
+    }
+
+    fn uninhibit_suspend(&mut self) {
+        if let Some(release) = self.inhibit_release.take() {
+            _ = release.send(());
+        }
+    }
+
+    fn reset_target(&mut self) {
+        self.dfu_target = None;
+        self.target_confirmed = false;
     }
 
-    fn read_asset_file(filepath: Arc<PathBuf>, sender: ComponentSender<Self>) -> JoinHandle<()> {
-        relm4::spawn(async move {
-            match tokio::fs::File::open(filepath.as_path()).await {
-                Ok(mut file) => {
-                    let mut content = Vec::new();
-                    match file.read_to_end(&mut content).await {
-                        Ok(_) => sender.input(Input::ContentReady(content)),
-                        Err(_) => sender.input(Input::OtaFailed("Failed to open file".to_string())),
+    fn start_file_queue(&mut self, filepaths: Vec<PathBuf>, asset_type: AssetType) -> Option<Effect> {
+        self.file_queue = filepaths.into_iter().map(Arc::new).collect();
+        self.queue_index = 0;
+        self.asset_type = asset_type;
+        self.reset_target();
+        self.flash_queued_file()
+    }
+
+    /// Flash the content, unless it's firmware built for something other
+    /// than PineTime, which needs to be confirmed first. It's checked here,
+    /// so that the check is the same for files and downloaded releases
+    fn flash_content(&mut self, content: Arc<Vec<u8>>) -> Option<Effect> {
+        if let (AssetType::Firmware, false) = (self.asset_type, self.target_confirmed) {
+            match bt::dfu_target(&content) {
+                Ok(target) => {
+                    log::info!("DFU target: {target}");
+                    let is_pinetime = bt::is_pinetime_target(&target);
+                    if !is_pinetime {
+                        self.progress_status = gettext_f(
+                            "This DFU targets {target}, which doesn't look like PineTime firmware",
+                            &[("target", &target)],
+                        );
+                        self.state = State::Confirming;
+                    }
+                    self.dfu_target = Some(target);
+                    if !is_pinetime {
+                        return None;
                     }
                 }
-                Err(err) => {
-                    sender.input(Input::OtaFailed("Failed to read file".to_string()));
-                    log::error!("Failed to read file '{:?}': {}", &filepath, err)
+                // Invalid package is reported by flashing itself
+                Err(error) => log::warn!("Failed to read DFU target: {error}"),
+            }
+        }
+        Some(Effect::Flash(content))
+    }
+
+    fn target_status(&self) -> String {
+        match &self.dfu_target {
+            Some(target) => gettext_f("This DFU targets {target}", &[("target", target)]),
+            None => String::new(),
+        }
+    }
+
+    fn flash_queued_file(&mut self) -> Option<Effect> {
+        let filepath = self.file_queue.get(self.queue_index).cloned()?;
+        self.progress_status = gettext_f("Reading {asset} file", &[("asset", &self.asset_type.display_name().to_lowercase())]);
+        self.reset_progress();
+        self.state = State::InProgress;
+        self.asset_content = None;
+        self.asset_version = filepath.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(bt::resources_version_from_filename)
+            .map(String::from);
+        self.asset_source = Some(Source::File(filepath.clone()));
+        Some(Effect::ReadFile(filepath))
+    }
+
+    fn is_multi_file(&self) -> bool {
+        self.file_queue.len() > 1
+    }
+
+    fn queued_file_name(&self) -> String {
+        self.file_queue.get(self.queue_index)
+            .and_then(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn queue_status(&self) -> String {
+        gettext_f("File {current}/{total}: {name}", &[
+            ("current", &(self.queue_index + 1).to_string()),
+            ("total", &self.file_queue.len().to_string()),
+            ("name", &self.queued_file_name()),
+        ])
+    }
+
+    /// Progress across all queued files, in 10% steps
+    fn overall_percent(&self, current: u32, total: u32) -> u32 {
+        let fraction = ProgressEvent::Numbers { current, total }.fraction().unwrap_or(0.0);
+        let files = self.file_queue.len().max(1) as f32;
+        let overall = (self.queue_index as f32 + fraction) / files;
+        (overall * 10.0) as u32 * 10
+    }
+
+    fn update_transfer_rate(&mut self, current: u32) {
+        let now = Instant::now();
+        match self.progress_timestamp {
+            // New transfer started (e.g. next resource file)
+            _ if current < self.progress_current => {
+                self.transfer_rate = None;
+            }
+            Some(previous) => {
+                let elapsed = now.duration_since(previous).as_secs_f64();
+                if elapsed > 0.0 {
+                    let rate = (current - self.progress_current) as f64 / elapsed;
+                    self.transfer_rate = Some(match self.transfer_rate {
+                        Some(average) => RATE_SMOOTHING * rate + (1.0 - RATE_SMOOTHING) * average,
+                        None => rate,
+                    });
                 }
             }
-        })
+            None => {}
+        }
+        self.progress_timestamp = Some(now);
     }
 
-    fn flash_asset(infinitime: Arc<InfiniTime>, content: Arc<Vec<u8>>, asset_type: AssetType, sender: ComponentSender<Self>) -> JoinHandle<()> {
+    /// Update the state according to `msg`, returning the effects to perform
+    fn handle(&mut self, msg: Input) -> Vec<Effect> {
+        let mut effects = Vec::new();
+        match msg {
+            Input::Connected(infinitime) => {
+                self.infinitime = Some(infinitime);
+            }
+            Input::Disconnected => {
+                self.infinitime = None;
+            }
+            Input::FlashAssetFromFile(filepath, asset_type) => {
+                effects.extend(self.start_file_queue(vec![filepath], asset_type));
+            }
+            Input::FlashAssetFromFiles(filepaths, asset_type) => {
+                if !filepaths.is_empty() {
+                    effects.extend(self.start_file_queue(filepaths, asset_type));
+                }
+            }
+            Input::FlashAssetFromUrl(url, asset_type) => {
+                let url = Arc::new(url);
+                self.file_queue.clear();
+                self.queue_index = 0;
+                self.progress_status = gettext_f("Downloading {asset}", &[("asset", &asset_type.display_name().to_lowercase())]);
+                self.reset_progress();
+                self.reset_target();
+                self.state = State::InProgress;
+                self.asset_type = asset_type;
+                self.asset_version = url.split(['?', '#']).next()
+                    .and_then(|path| path.rsplit('/').next())
+                    .and_then(bt::resources_version_from_filename)
+                    .map(String::from);
+                self.asset_source = Some(Source::Url(url.clone()));
+                effects.push(Effect::Download(url));
+            }
+            Input::ContentReady(content) => {
+                let content = Arc::new(content);
+                self.asset_source = None;
+                self.asset_content = Some(content.clone());
+                effects.extend(self.flash_content(content));
+            }
+            Input::OtaFinished => {
+                if let (State::InProgress, AssetType::Resources, Some(version)) =
+                    (&self.state, self.asset_type, &self.asset_version)
+                {
+                    effects.push(Effect::Output(Output::ResourcesInstalled(version.clone())));
+                }
+                if self.state == State::InProgress && self.queue_index + 1 < self.file_queue.len() {
+                    // Continue with the next queued file
+                    self.queue_index += 1;
+                    effects.extend(self.flash_queued_file());
+                } else {
+                    // Report only the transition from in-progress, so that
+                    // each flashing attempt produces a single output
+                    if self.state == State::InProgress {
+                        effects.push(Effect::Output(Output::OtaCompleted(self.asset_type)));
+                    }
+                    self.progress_status = gettext_f("{asset} update complete :)", &[("asset", &self.asset_type.display_name())]);
+                    self.state = State::Finished;
+                    self.transfer_rate = None;
+                    self.asset_content = None;
+                }
+            }
+            Input::OtaFailed(message) => {
+                // Name the failed file, the rest of the queue is kept for retry
+                let message = if self.is_multi_file() {
+                    format!("{}: {}", self.queued_file_name(), message)
+                } else {
+                    message
+                };
+                if self.state == State::InProgress {
+                    effects.push(Effect::Output(Output::OtaFailed(self.asset_type, message.clone())));
+                }
+                self.progress_status = gettext_f("{asset} update failed: {error}", &[
+                    ("asset", &self.asset_type.display_name()), ("error", &message)
+                ]);
+                self.state = State::Aborted;
+            }
+            Input::OtaProgress(event) => {
+                if let Some(fraction) = event.fraction() {
+                    self.progress_fraction = fraction;
+                }
+                match event {
+                    ProgressEvent::Message(text) => {
+                        self.progress_status = text;
+                    }
+                    ProgressEvent::Numbers { current, total } => {
+                        // Report progress percentage in 10% steps only
+                        let percent = self.overall_percent(current, total);
+                        if percent != self.overall_percent(self.progress_current, self.progress_total) {
+                            effects.push(Effect::Output(Output::OtaProgress(self.asset_type, percent)));
+                        }
+                        self.update_transfer_rate(current);
+                        self.progress_current = current;
+                        self.progress_total = total;
+                    }
+                }
+            }
+            Input::Retry if self.state == State::Aborted && self.running_tasks == 0 => {
+                self.reset_progress();
+                // Prefer the content already in memory, otherwise fetch it again
+                let effect = match (self.asset_content.clone(), &self.asset_source) {
+                    (Some(content), _) => {
+                        self.state = State::InProgress;
+                        self.flash_content(content)
+                    }
+                    (None, Some(Source::File(filepath))) => Some(Effect::ReadFile(filepath.clone())),
+                    (None, Some(Source::Url(url))) => Some(Effect::Download(url.clone())),
+                    (None, None) => None,
+                };
+                if let Some(effect) = effect {
+                    self.state = State::InProgress;
+                    effects.push(effect);
+                }
+            }
+            Input::Retry => {
+                log::debug!("Retry is not available in {:?} state", self.state);
+            }
+            Input::TaskEnded => {
+                self.running_tasks = self.running_tasks.saturating_sub(1);
+            }
+            Input::TargetConfirmed => {
+                if let (State::Confirming, Some(content)) = (&self.state, self.asset_content.clone()) {
+                    log::warn!("Flashing unexpected DFU target, confirmed by user");
+                    self.target_confirmed = true;
+                    self.state = State::InProgress;
+                    effects.push(Effect::Flash(content));
+                }
+            }
+            Input::Abort => {
+                if let State::InProgress | State::Confirming = self.state {
+                    effects.push(Effect::AbortTask);
+                    effects.push(Effect::Output(Output::OtaFailed(self.asset_type, gettext("Aborted"))));
+                    self.progress_status = gettext_f("{asset} update aborted", &[("asset", &self.asset_type.display_name())]);
+                    self.state = State::Aborted;
+                }
+            }
+        }
+        self.running_tasks += effects.iter().filter(|e| e.starts_task()).count();
+        effects
+    }
+
+    fn perform(&mut self, effect: Effect, sender: &ComponentSender<Self>) {
+        match effect {
+            Effect::ReadFile(filepath) => {
+                self.start_task(Self::read_asset_file(filepath, sender.clone()), sender);
+            }
+            Effect::Download(url) => {
+                self.start_task(Self::download_asset(url, sender.clone()), sender);
+            }
+            Effect::Flash(content) => match self.infinitime.clone() {
+                Some(infinitime) => {
+                    let task = Self::flash_asset(infinitime, content, self.asset_type, sender.clone());
+                    self.start_task(task, sender);
+                }
+                None => {
+                    sender.input(Input::OtaFailed(gettext("InfiniTime watch is not connected")));
+                    sender.input(Input::TaskEnded);
+                }
+            }
+            Effect::AbortTask => {
+                // The handle is kept, so that the next task waits for this one to unwind
+                if let Some(handle) = &self.task_handle {
+                    handle.abort();
+                }
+            }
+            Effect::Output(output) => {
+                sender.output(output).unwrap();
+            }
+        }
+    }
+
+    /// Run the task once the previous one is aborted and gone. Otherwise,
+    /// e.g. a retry could start a DFU session while the old one still runs
+    fn start_task(&mut self, task: impl Future<Output = ()> + Send + 'static, sender: &ComponentSender<Self>) {
+        let previous = self.task_handle.take();
+        let guard = TaskEndGuard(sender.clone());
+        self.task_handle = Some(relm4::spawn(async move {
+            let _guard = guard;
+            if let Some(previous) = previous {
+                previous.abort();
+                _ = previous.await;
+            }
+            task.await;
+        }));
+    }
+
+    async fn download_asset(url: Arc<String>, sender: ComponentSender<Self>) {
+        match gh::download_content(url.as_str()).await {
+            Ok(content) => sender.input(Input::ContentReady(content)),
+            Err(error) => {
+                log::error!("Failed to download asset: {error}");
+                sender.input(Input::OtaFailed(ui::github_error_message(&error, gettext("Downloading failed"))));
+            }
+        }
+    }
+
+    async fn read_asset_file(filepath: Arc<PathBuf>, sender: ComponentSender<Self>) {
+        match tokio::fs::File::open(filepath.as_path()).await {
+            Ok(mut file) => {
+                let mut content = Vec::new();
+                match file.read_to_end(&mut content).await {
+                    Ok(_) => sender.input(Input::ContentReady(content)),
+                    Err(_) => sender.input(Input::OtaFailed(gettext("Failed to open file"))),
+                }
+            }
+            Err(err) => {
+                sender.input(Input::OtaFailed(gettext("Failed to read file")));
+                log::error!("Failed to read file '{:?}': {}", &filepath, err)
+            }
+        }
+    }
+
+    async fn flash_asset(infinitime: Arc<InfiniTime>, content: Arc<Vec<u8>>, asset_type: AssetType, sender: ComponentSender<Self>) {
         let (progress_tx, mut progress_rx) = bt::progress_channel(32);
 
         let sender_ = sender.clone();
@@ -118,13 +536,11 @@ impl Model {
             }
         };
 
-        relm4::spawn(async move {
-            let (_, result) = tokio::join!(progress_updater, flasher);
-            match result {
-                Ok(()) => sender.input(Input::OtaFinished),
-                Err(err) => sender.input(Input::OtaFailed(err.to_string())),
-            }
-        })
+        let (_, result) = tokio::join!(progress_updater, flasher);
+        match result {
+            Ok(()) => sender.input(Input::OtaFinished),
+            Err(err) => sender.input(Input::OtaFailed(err.to_string())),
+        }
     }
 }
 
@@ -133,7 +549,7 @@ impl Component for Model {
     type CommandOutput = ();
     type Init = ();
     type Input = Input;
-    type Output = ();
+    type Output = Output;
     type Widgets = Widgets;
 
     view! {
@@ -144,11 +560,11 @@ impl Component for Model {
             adw::HeaderBar {
                 #[wrap(Some)]
                 set_title_widget = &gtk::Label {
-                    set_label: "Firmware Update",
+                    set_label: &gettext("Firmware Update"),
                 },
 
                 pack_start = &gtk::Button {
-                    set_tooltip_text: Some("Back"),
+                    set_tooltip_text: Some(&gettext("Back")),
                     set_icon_name: "go-previous-symbolic",
                     #[watch]
                     set_visible: model.state != State::InProgress,
@@ -168,6 +584,16 @@ impl Component for Model {
                     set_spacing: 10,
                     set_valign: gtk::Align::Center,
 
+                    gtk::Label {
+                        add_css_class: "heading",
+                        #[watch]
+                        set_label: &model.queue_status(),
+                        #[watch]
+                        set_visible: model.is_multi_file() && model.state != State::Finished,
+                        set_halign: gtk::Align::Center,
+                        set_margin_top: 20,
+                    },
+
                     gtk::Label {
                         #[watch]
                         set_label: &model.progress_status,
@@ -175,12 +601,19 @@ impl Component for Model {
                         set_margin_top: 20,
                     },
 
+                    gtk::Label {
+                        add_css_class: "dim-label",
+                        #[watch]
+                        set_label: &model.target_status(),
+                        #[watch]
+                        set_visible: model.dfu_target.is_some() && model.state == State::InProgress,
+                    },
+
                     gtk::LevelBar {
                         set_min_value: 0.0,
+                        set_max_value: 1.0,
                         #[watch]
-                        set_max_value: model.progress_total as f64,
-                        #[watch]
-                        set_value: model.progress_current as f64,
+                        set_value: model.progress_fraction as f64,
                         #[watch]
                         set_visible: model.state == State::InProgress && model.progress_current > 0,
                     },
@@ -192,6 +625,14 @@ impl Component for Model {
                         set_visible: model.state == State::InProgress && model.progress_current > 0,
                     },
 
+                    gtk::Label {
+                        add_css_class: "dim-label",
+                        #[watch]
+                        set_label: &format!("{:.1} KB/s", model.transfer_rate.unwrap_or(0.0) / 1024.0),
+                        #[watch]
+                        set_visible: model.state == State::InProgress && model.transfer_rate.is_some(),
+                    },
+
                     gtk::Spinner {
                         #[watch]
                         set_visible: model.state == State::InProgress && model.progress_current == 0,
@@ -204,7 +645,7 @@ impl Component for Model {
                         set_halign: gtk::Align::Center,
 
                         gtk::Button {
-                            set_label: "Abort",
+                            set_label: &gettext("Abort"),
                             add_css_class:"destructive-action",
                             #[watch]
                             set_visible: model.state == State::InProgress,
@@ -212,16 +653,33 @@ impl Component for Model {
                         },
 
                         gtk::Button {
-                            set_label: "Retry",
+                            set_label: &gettext("Flash Anyway"),
+                            add_css_class: "destructive-action",
+                            #[watch]
+                            set_visible: model.state == State::Confirming,
+                            connect_clicked => Input::TargetConfirmed,
+                        },
+
+                        gtk::Button {
+                            set_label: &gettext("Cancel"),
+                            #[watch]
+                            set_visible: model.state == State::Confirming,
+                            connect_clicked => Input::Abort,
+                        },
+
+                        gtk::Button {
+                            set_label: &gettext("Retry"),
                             #[watch]
                             set_visible: model.state == State::Aborted,
+                            #[watch]
+                            set_sensitive: model.running_tasks == 0,
                             connect_clicked => Input::Retry,
                         },
 
                         gtk::Button {
-                            set_label: "Back",
+                            set_label: &gettext("Back"),
                             #[watch]
-                            set_visible: model.state != State::InProgress,
+                            set_visible: !matches!(model.state, State::InProgress | State::Confirming),
                             connect_clicked => |_| {
                                 ui::BROKER.send(ui::Input::SetView(ui::View::Dashboard));
                             },
@@ -239,90 +697,245 @@ impl Component for Model {
     }
 
     fn update(&mut self, msg: Self::Input, sender: ComponentSender<Self>, _root: &Self::Root) {
-        match msg {
-            Input::Connected(infinitime) => {
-                self.infinitime = Some(infinitime);
-            }
-            Input::Disconnected => {
-                self.infinitime = None;
-            }
-            Input::FlashAssetFromFile(filepath, asset_type) => {
-                let filepath = Arc::new(filepath);
-                self.progress_status = format!("Reading {} file", asset_type.name().to_lowercase());
-                self.progress_current = 0;
-                self.progress_total = 0;
-                self.state = State::InProgress;
-                self.asset_type = asset_type;
-                self.asset_source = Some(Source::File(filepath.clone()));
-                self.task_handle = Some(Self::read_asset_file(filepath.clone(), sender));
-            }
-            Input::FlashAssetFromUrl(url, asset_type) => {
-                let url = Arc::new(url);
-                self.progress_status = format!("Downloading {}", asset_type.name().to_lowercase());
-                self.progress_current = 0;
-                self.progress_total = 0;
-                self.state = State::InProgress;
-                self.asset_type = asset_type;
-                self.asset_source = Some(Source::Url(url.clone()));
-                self.task_handle = Some(Self::download_asset(url.clone(), sender));
-            }
-            Input::ContentReady(content) => {
-                if let Some(infinitime) = self.infinitime.clone() {
-                    let content = Arc::new(content);
-                    self.asset_source = None;
-                    self.asset_content = Some(content.clone());
-                    self.task_handle = Some(Self::flash_asset(infinitime, content, self.asset_type, sender));
-                }
-            }
-            Input::OtaFinished => {
-                self.progress_status = format!("{} update complete :)", self.asset_type.name());
-                self.state = State::Finished;
-                self.task_handle = None;
-                self.asset_content = None;
-            }
-            Input::OtaFailed(message) => {
-                self.progress_status = format!("{} update failed: {}", self.asset_type.name(), message);
-                self.state = State::Aborted;
-                self.task_handle = None;
-            }
-            Input::OtaProgress(event) => {
-                match event {
-                    ProgressEvent::Message(text) => {
-                        self.progress_status = text;
-                    }
-                    ProgressEvent::Numbers { current, total } => {
-                        self.progress_current = current;
-                        self.progress_total = total;
-                    }
-                }
-            }
-            Input::Retry => {
-                self.progress_current = 0;
-                self.progress_total = 0;
-                if let Some(content) = self.asset_content.clone() {
-                    if let Some(infinitime) = self.infinitime.clone() {
-                        self.state = State::InProgress;
-                        self.task_handle = Some(Self::flash_asset(infinitime, content, self.asset_type, sender));
-                    }
-                } else {
-                    match &self.asset_source {
-                        Some(Source::File(filepath)) => {
-                            self.task_handle = Some(Self::read_asset_file(filepath.clone(), sender));
-                        }
-                        Some(Source::Url(url)) => {
-                            self.task_handle = Some(Self::download_asset(url.clone(), sender));
-                        }
-                        None => {}
-                    }
-                }
-            }
-            Input::Abort => {
-                if let Some(handle) = self.task_handle.take() {
-                    handle.abort();
-                    self.progress_status = format!("{} update aborted", self.asset_type.name());
-                    self.state = State::Aborted;
-                }
-            }
+        let was_in_progress = self.state == State::InProgress;
+        for effect in self.handle(msg) {
+            self.perform(effect, &sender);
+        }
+        // Hold the suspend inhibitor for as long as flashing is in progress
+        match (was_in_progress, self.state == State::InProgress) {
+            (false, true) => self.inhibit_suspend(),
+            (true, false) => self.uninhibit_suspend(),
+            _ => {}
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_path(name: &str) -> PathBuf {
+        PathBuf::from(format!("/tmp/{name}"))
+    }
+
+    fn assert_read_file(effects: &[Effect], name: &str) {
+        assert!(
+            matches!(effects, [Effect::ReadFile(path)] if path.as_path() == file_path(name)),
+            "{effects:?}",
+        );
+    }
+
+    fn assert_flash(effects: &[Effect], content: &[u8]) {
+        assert!(
+            matches!(effects, [Effect::Flash(c)] if c.as_slice() == content),
+            "{effects:?}",
+        );
+    }
+
+    fn flash_file(model: &mut Model, name: &str) {
+        model.handle(Input::FlashAssetFromFile(file_path(name), AssetType::Firmware));
+        model.handle(Input::ContentReady(vec![1, 2, 3]));
+        // File reading task
+        model.handle(Input::TaskEnded);
+    }
+
+    #[test]
+    fn flash_from_file() {
+        let mut model = Model::default();
+        let effects = model.handle(Input::FlashAssetFromFile(file_path("fw.zip"), AssetType::Firmware));
+        assert_read_file(&effects, "fw.zip");
+        assert_eq!(model.state, State::InProgress);
+
+        let effects = model.handle(Input::ContentReady(vec![1, 2, 3]));
+        assert_flash(&effects, &[1, 2, 3]);
+        assert!(model.asset_source.is_none());
+
+        let effects = model.handle(Input::OtaProgress(ProgressEvent::Numbers { current: 50, total: 100 }));
+        assert!(matches!(effects[..], [Effect::Output(Output::OtaProgress(_, 50))]), "{effects:?}");
+        assert_eq!((model.progress_current, model.progress_total), (50, 100));
+        assert_eq!(model.progress_fraction, 0.5);
+
+        // Progress within the same 10% step is not reported
+        let effects = model.handle(Input::OtaProgress(ProgressEvent::Numbers { current: 55, total: 100 }));
+        assert!(effects.is_empty(), "{effects:?}");
+
+        let effects = model.handle(Input::OtaFinished);
+        assert!(matches!(effects[..], [Effect::Output(Output::OtaCompleted(_))]), "{effects:?}");
+        assert_eq!(model.state, State::Finished);
+        assert!(model.asset_content.is_none());
+    }
+
+    #[test]
+    fn resources_version() {
+        let mut model = Model::default();
+        let path = file_path("infinitime-resources-1.14.0.zip");
+        model.handle(Input::FlashAssetFromFile(path, AssetType::Resources));
+        model.handle(Input::ContentReady(vec![1, 2, 3]));
+
+        let effects = model.handle(Input::OtaFinished);
+        assert!(
+            matches!(&effects[..], [
+                Effect::Output(Output::ResourcesInstalled(version)),
+                Effect::Output(Output::OtaCompleted(_)),
+            ] if version == "1.14.0"),
+            "{effects:?}",
+        );
+    }
+
+    #[test]
+    fn retry_from_content() {
+        let mut model = Model::default();
+        flash_file(&mut model, "fw.zip");
+        model.handle(Input::OtaProgress(ProgressEvent::Numbers { current: 50, total: 100 }));
+
+        let effects = model.handle(Input::OtaFailed(String::from("error")));
+        assert!(matches!(effects[..], [Effect::Output(Output::OtaFailed(_, _))]), "{effects:?}");
+        assert_eq!(model.state, State::Aborted);
+        model.handle(Input::TaskEnded);
+
+        // Content is already read, so the file is not read again
+        let effects = model.handle(Input::Retry);
+        assert_flash(&effects, &[1, 2, 3]);
+        assert_eq!(model.state, State::InProgress);
+        assert_eq!((model.progress_current, model.progress_total), (0, 0));
+    }
+
+    #[test]
+    fn retry_from_file() {
+        let mut model = Model::default();
+        model.handle(Input::FlashAssetFromFile(file_path("fw.zip"), AssetType::Firmware));
+        model.handle(Input::OtaFailed(String::from("error")));
+        model.handle(Input::TaskEnded);
+
+        let effects = model.handle(Input::Retry);
+        assert_read_file(&effects, "fw.zip");
+        assert_eq!(model.state, State::InProgress);
+    }
+
+    #[test]
+    fn retry_from_url() {
+        let url = "https://example.com/fw.zip";
+        let mut model = Model::default();
+        let effects = model.handle(Input::FlashAssetFromUrl(url.to_string(), AssetType::Firmware));
+        assert!(matches!(&effects[..], [Effect::Download(u)] if u.as_str() == url), "{effects:?}");
+        model.handle(Input::OtaFailed(String::from("error")));
+        model.handle(Input::TaskEnded);
+
+        let effects = model.handle(Input::Retry);
+        assert!(matches!(&effects[..], [Effect::Download(u)] if u.as_str() == url), "{effects:?}");
+        assert_eq!(model.state, State::InProgress);
+    }
+
+    #[test]
+    fn retry_after_task_ended() {
+        let mut model = Model::default();
+        flash_file(&mut model, "fw.zip");
+        model.handle(Input::OtaFailed(String::from("error")));
+
+        // The failed task is still unwinding
+        assert!(model.handle(Input::Retry).is_empty());
+        assert_eq!(model.state, State::Aborted);
+
+        model.handle(Input::TaskEnded);
+        assert_flash(&model.handle(Input::Retry), &[1, 2, 3]);
+        // Only one attempt at a time
+        assert!(model.handle(Input::Retry).is_empty());
+        assert_eq!(model.state, State::InProgress);
+    }
+
+    #[test]
+    fn retry_without_source() {
+        let mut model = Model::default();
+        assert!(model.handle(Input::Retry).is_empty());
+        assert_eq!(model.state, State::Finished);
+    }
+
+    #[test]
+    fn abort() {
+        let mut model = Model::default();
+        flash_file(&mut model, "fw.zip");
+
+        let effects = model.handle(Input::Abort);
+        assert!(
+            matches!(effects[..], [Effect::AbortTask, Effect::Output(Output::OtaFailed(_, _))]),
+            "{effects:?}",
+        );
+        assert_eq!(model.state, State::Aborted);
+
+        // Nothing left to abort, and late failure is not reported twice
+        assert!(model.handle(Input::Abort).is_empty());
+        assert!(model.handle(Input::OtaFailed(String::from("error"))).is_empty());
+        assert_eq!(model.state, State::Aborted);
+
+        // Not until the aborted task is gone
+        assert!(model.handle(Input::Retry).is_empty());
+        model.handle(Input::TaskEnded);
+        let effects = model.handle(Input::Retry);
+        assert_flash(&effects, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn abort_when_finished() {
+        let mut model = Model::default();
+        flash_file(&mut model, "fw.zip");
+        model.handle(Input::OtaFinished);
+        assert!(model.handle(Input::Abort).is_empty());
+        assert_eq!(model.state, State::Finished);
+    }
+
+    #[test]
+    fn target_confirmation() {
+        let mut model = Model::default();
+        flash_file(&mut model, "fw.zip");
+        assert!(model.handle(Input::TargetConfirmed).is_empty());
+
+        // As if the DFU target didn't look like PineTime
+        model.state = State::Confirming;
+        let effects = model.handle(Input::Abort);
+        assert!(
+            matches!(effects[..], [Effect::AbortTask, Effect::Output(Output::OtaFailed(_, _))]),
+            "{effects:?}",
+        );
+        assert_eq!(model.state, State::Aborted);
+
+        model.state = State::Confirming;
+        let effects = model.handle(Input::TargetConfirmed);
+        assert_flash(&effects, &[1, 2, 3]);
+        assert!(model.target_confirmed);
+        assert_eq!(model.state, State::InProgress);
+    }
+
+    #[test]
+    fn file_queue() {
+        let mut model = Model::default();
+        let files = vec![file_path("a.zip"), file_path("b.zip")];
+        let effects = model.handle(Input::FlashAssetFromFiles(files, AssetType::Resources));
+        assert_read_file(&effects, "a.zip");
+        model.handle(Input::ContentReady(vec![1]));
+        model.handle(Input::TaskEnded);
+
+        // The next file is read instead of reporting completion
+        let effects = model.handle(Input::OtaFinished);
+        assert_read_file(&effects, "b.zip");
+        assert_eq!(model.queue_index, 1);
+        assert_eq!(model.state, State::InProgress);
+        model.handle(Input::TaskEnded);
+
+        // Failed file is named and retried on its own
+        model.handle(Input::ContentReady(vec![2]));
+        model.handle(Input::TaskEnded);
+        let effects = model.handle(Input::OtaFailed(String::from("error")));
+        assert!(
+            matches!(&effects[..], [Effect::Output(Output::OtaFailed(_, message))] if message == "b.zip: error"),
+            "{effects:?}",
+        );
+        model.handle(Input::TaskEnded);
+        let effects = model.handle(Input::Retry);
+        assert_flash(&effects, &[2]);
+
+        let effects = model.handle(Input::OtaFinished);
+        assert!(matches!(effects[..], [Effect::Output(Output::OtaCompleted(_))]), "{effects:?}");
+        assert_eq!(model.state, State::Finished);
+    }
+}
@@ -1,12 +1,24 @@
-use crate::ui;
+use crate::{i18n::{gettext, gettext_f}, logging, ui};
+use infinitime::gh;
 use gtk::{
-    gio, glib::Propagation, prelude::{
-        GtkApplicationExt, OrientableExt, WidgetExt, ButtonExt, SettingsExt
+    gio, glib::{self, Propagation}, prelude::{
+        GtkApplicationExt, ListBoxRowExt, OrientableExt, WidgetExt, ButtonExt, SettingsExt, SettingsExtManual
     }
 };
-use adw::prelude::{PreferencesPageExt, PreferencesGroupExt, PreferencesRowExt, ActionRowExt};
-use relm4::{adw, gtk, ComponentParts, ComponentSender, Component};
+use adw::prelude::{PreferencesPageExt, PreferencesGroupExt, PreferencesRowExt, ActionRowExt, ComboRowExt, EntryRowExt, EditableExt};
+use relm4::{
+    actions::{RelmAction, RelmActionGroup},
+    adw, gtk, ComponentController, ComponentParts, ComponentSender, Component, Controller,
+};
+use relm4_components::{open_dialog::*, save_dialog::*};
 use ashpd::{desktop::background::Background, WindowIdentifier, Error};
+use std::path::{Path, PathBuf};
+
+mod backup;
+
+/// Values of the color scheme setting, in the order of the selector options
+const COLOR_SCHEMES: &[&str] = &["system", "light", "dark"];
+const WEATHER_PROVIDERS: &[&str] = &["none", "open-meteo"];
 
 
 #[derive(Debug)]
@@ -15,13 +27,34 @@ pub enum Input {
     RunInBackgroundResponse(bool),
     AutoStartRequest(bool),
     AutoStartResponse(bool),
+    FirmwareRepoChanged(String),
+    NotificationFormatChanged(String),
+    WeatherLocationChanged(String),
+    DownloadDirChanged(String),
+    AdapterNames(Vec<String>),
+    AdapterSelected,
+    Export,
+    ExportTo(PathBuf),
+    Import,
+    ImportFrom(PathBuf),
+    OpenLogFolder,
+    None,
 }
 
 
 pub struct Model {
     background_switch: gtk::Switch,
     autostart_switch: gtk::Switch,
+    firmware_repo_row: adw::EntryRow,
+    notification_format_row: adw::EntryRow,
+    weather_location_row: adw::EntryRow,
+    download_dir_row: adw::EntryRow,
+    adapter_row: adw::ComboRow,
+    // Options of the adapter selector, empty name stands for the default adapter
+    adapter_names: Vec<String>,
     settings: gio::Settings,
+    export_dialog: Controller<SaveDialog>,
+    import_dialog: Controller<OpenDialog>,
 }
 
 impl Model {
@@ -34,11 +67,12 @@ impl Model {
             .unwrap();
         relm4::spawn_local(async move {
             let identifier = WindowIdentifier::from_native(&native_window).await;
+            let reason = gettext("Keep the watch connected, forward notifications, control media player");
             let request = Background::request()
                 .identifier(identifier)
                 .auto_start(autostart)
                 .command(["watchmate", "--background"])
-                .reason("Keep the watch connected, forward notifications, control media player");
+                .reason(reason.as_str());
             let response = request.send().await.and_then(|r| r.response());
             handler(response);
         });
@@ -49,25 +83,31 @@ impl Model {
 #[relm4::component(pub)]
 impl Component for Model {
     type CommandOutput = ();
-    type Init = gio::Settings;
+    type Init = (adw::ApplicationWindow, gio::Settings);
     type Input = Input;
     type Output = ();
     type Widgets = Widgets;
 
     menu! {
         main_menu: {
-            "Back to Dashboard" => super::DashboardViewAction,
-            "Devices" => super::DevicesViewAction,
+            &gettext("Back to Dashboard") => super::DashboardViewAction,
+            &gettext("Devices") => super::DevicesViewAction,
             section! {
-                "About" => super::AboutAction,
+                &gettext("Export Settings…") => ExportAction,
+                &gettext("Import Settings…") => ImportAction,
             },
             section! {
-                "Quit" => super::QuitAction,
+                &gettext("Keyboard Shortcuts") => super::ShortcutsAction,
+                &gettext("About") => super::AboutAction,
+            },
+            section! {
+                &gettext("Quit") => super::QuitAction,
             }
         }
     }
 
     view! {
+        #[name = "root"]
         gtk::Box {
             set_hexpand: true,
             set_orientation: gtk::Orientation::Vertical,
@@ -75,11 +115,11 @@ impl Component for Model {
             adw::HeaderBar {
                 #[wrap(Some)]
                 set_title_widget = &gtk::Label {
-                    set_label: "Settings",
+                    set_label: &gettext("Settings"),
                 },
 
                 pack_start = &gtk::Button {
-                    set_tooltip_text: Some("Back"),
+                    set_tooltip_text: Some(&gettext("Back")),
                     set_icon_name: "go-previous-symbolic",
                     connect_clicked => |_| {
                         ui::BROKER.send(ui::Input::SetView(super::View::Dashboard));
@@ -95,8 +135,8 @@ impl Component for Model {
             adw::PreferencesPage {
                 add = &adw::PreferencesGroup {
                     add = &adw::ActionRow {
-                        set_title: "Run in background",
-                        set_subtitle: "When closed",
+                        set_title: &gettext("Run in background"),
+                        set_subtitle: &gettext("When closed"),
                         #[local]
                         add_suffix = &background_switch -> gtk::Switch {
                             set_active: model.settings.boolean(super::SETTING_BACKGROUND),
@@ -108,8 +148,8 @@ impl Component for Model {
                         }
                     },
                     add = &adw::ActionRow {
-                        set_title: "Auto-start",
-                        set_subtitle: "In background at login",
+                        set_title: &gettext("Auto-start"),
+                        set_subtitle: &gettext("In background at login"),
                         #[local]
                         add_suffix = &autostart_switch -> gtk::Switch {
                             set_active: model.settings.boolean(super::SETTING_AUTO_START),
@@ -120,21 +160,310 @@ impl Component for Model {
                             }
                         }
                     },
+                },
+                add = &adw::PreferencesGroup {
+                    set_title: &gettext("Appearance"),
+                    #[name = "color_scheme_row"]
+                    add = &adw::ComboRow {
+                        set_title: &gettext("Style"),
+                        set_model: Some(&gtk::StringList::new(&[
+                            gettext("Follow system").as_str(),
+                            gettext("Light").as_str(),
+                            gettext("Dark").as_str(),
+                        ])),
+                        set_selected: color_scheme_index(&model.settings),
+                        connect_selected_notify[settings = model.settings.clone()] => move |row| {
+                            if let Some(scheme) = COLOR_SCHEMES.get(row.selected() as usize) {
+                                _ = settings.set_string(super::SETTING_COLOR_SCHEME, scheme);
+                            }
+                        },
+                    },
+                },
+                add = &adw::PreferencesGroup {
+                    set_title: &gettext("Discovery"),
+                    #[local]
+                    add = &adapter_row -> adw::ComboRow {
+                        set_title: &gettext("Bluetooth adapter"),
+                        connect_selected_notify => Input::AdapterSelected,
+                    },
+                    add = &adw::EntryRow {
+                        set_title: &gettext("Device name filter"),
+                        set_show_apply_button: true,
+                        set_text: &model.settings.string(super::SETTING_NAME_FILTER),
+                        connect_apply[settings = model.settings.clone()] => move |row| {
+                            _ = settings.set_string(super::SETTING_NAME_FILTER, &row.text());
+                        },
+                    },
+                    #[name = "auto_connect_row"]
+                    add = &adw::SwitchRow {
+                        set_title: &gettext("Connect automatically"),
+                        set_subtitle: &gettext("To the saved device, on startup and when connection is lost"),
+                    },
+                    #[name = "disconnect_on_quit_row"]
+                    add = &adw::SwitchRow {
+                        set_title: &gettext("Disconnect on quit"),
+                        set_subtitle: &gettext("Otherwise the system keeps the watch connected"),
+                    },
+                    #[name = "auto_discovery_row"]
+                    add = &adw::SwitchRow {
+                        set_title: &gettext("Scan automatically"),
+                        set_subtitle: &gettext("When the devices page is opened"),
+                    },
+                    #[name = "scan_timeout_row"]
+                    add = &adw::SpinRow::with_range(0.0, 3600.0, 10.0) {
+                        set_title: &gettext("Scan timeout"),
+                        set_subtitle: &gettext("Seconds without finding a device, 0 to scan forever"),
+                    },
+                    #[name = "reconnect_delay_row"]
+                    add = &adw::SpinRow::with_range(0.0, 600.0, 5.0) {
+                        set_title: &gettext("Reconnection delay"),
+                        set_subtitle: &gettext("Seconds to wait before each attempt to restore lost connection"),
+                    },
+                    #[name = "reconnect_attempts_row"]
+                    add = &adw::SpinRow::with_range(0.0, 1000.0, 1.0) {
+                        set_title: &gettext("Reconnection attempts"),
+                        set_subtitle: &gettext("Give up after this many attempts, 0 for unlimited"),
+                    },
+                },
+                add = &adw::PreferencesGroup {
+                    set_title: &gettext("Advanced Bluetooth"),
+                    set_description: Some(&gettext("Changes restart the device discovery")),
+                    #[name = "rssi_threshold_row"]
+                    add = &adw::SpinRow::with_range(-120.0, 0.0, 5.0) {
+                        set_title: &gettext("Signal strength threshold"),
+                        set_subtitle: &gettext("Hide discovered devices with weaker signal, in dBm. 0 to show all"),
+                    },
+                    #[name = "duplicate_data_row"]
+                    add = &adw::SwitchRow {
+                        set_title: &gettext("Report duplicate advertisements"),
+                        set_subtitle: &gettext("Let BlueZ report every advertisement, not only the changed ones"),
+                    },
+                },
+                add = &adw::PreferencesGroup {
+                    set_title: &gettext("Firmware"),
+                    #[local]
+                    add = &firmware_repo_row -> adw::EntryRow {
+                        set_title: &gettext("Releases repository"),
+                        set_show_apply_button: true,
+                        set_text: &model.settings.string(super::SETTING_FIRMWARE_REPO),
+                        connect_apply[sender] => move |row| {
+                            sender.input(Input::FirmwareRepoChanged(row.text().to_string()));
+                        },
+                    },
+                    #[local]
+                    add = &download_dir_row -> adw::EntryRow {
+                        set_title: &gettext("Download folder"),
+                        set_show_apply_button: true,
+                        set_text: &super::download_dir(&model.settings).to_string_lossy(),
+                        connect_apply[sender] => move |row| {
+                            sender.input(Input::DownloadDirChanged(row.text().to_string()));
+                        },
+                    },
+                    #[name = "download_auto_save_row"]
+                    add = &adw::SwitchRow {
+                        set_title: &gettext("Save downloads automatically"),
+                        set_subtitle: &gettext("To the download folder, without asking for location"),
+                    },
+                },
+                add = &adw::PreferencesGroup {
+                    set_title: &gettext("Activity"),
+                    #[name = "step_goal_row"]
+                    add = &adw::SpinRow::with_range(100.0, 100000.0, 500.0) {
+                        set_title: &gettext("Daily step goal"),
+                    },
+                },
+                add = &adw::PreferencesGroup {
+                    set_title: &gettext("Notifications"),
+                    set_description: Some(&gettext("Placeholders: {app}, {summary}, {body}")),
+                    #[local]
+                    add = &notification_format_row -> adw::EntryRow {
+                        set_title: &gettext("Title format"),
+                        set_show_apply_button: true,
+                        set_text: &model.settings.string(super::SETTING_NOTIFICATION_FORMAT),
+                        connect_apply[sender] => move |row| {
+                            sender.input(Input::NotificationFormatChanged(row.text().to_string()));
+                        },
+                    },
+                },
+                add = &adw::PreferencesGroup {
+                    set_title: &gettext("Weather"),
+                    #[name = "weather_provider_row"]
+                    add = &adw::ComboRow {
+                        set_title: &gettext("Provider"),
+                        set_subtitle: &gettext("The location is sent to the provider"),
+                        set_model: Some(&gtk::StringList::new(&[
+                            gettext("Disabled").as_str(),
+                            "Open-Meteo",
+                        ])),
+                        set_selected: weather_provider_index(&model.settings),
+                        connect_selected_notify[settings = model.settings.clone()] => move |row| {
+                            if let Some(provider) = WEATHER_PROVIDERS.get(row.selected() as usize) {
+                                _ = settings.set_string(super::SETTING_WEATHER_PROVIDER, provider);
+                            }
+                        },
+                    },
+                    #[local]
+                    add = &weather_location_row -> adw::EntryRow {
+                        set_title: &gettext("Location name"),
+                        set_show_apply_button: true,
+                        set_text: &model.settings.string(super::SETTING_WEATHER_LOCATION),
+                        connect_apply[sender] => move |row| {
+                            sender.input(Input::WeatherLocationChanged(row.text().to_string()));
+                        },
+                    },
+                    #[name = "weather_latitude_row"]
+                    add = &adw::SpinRow::with_range(-90.0, 90.0, 0.1) {
+                        set_title: &gettext("Latitude"),
+                        set_digits: 4,
+                    },
+                    #[name = "weather_longitude_row"]
+                    add = &adw::SpinRow::with_range(-180.0, 180.0, 0.1) {
+                        set_title: &gettext("Longitude"),
+                        set_digits: 4,
+                    },
+                },
+                add = &adw::PreferencesGroup {
+                    set_title: &gettext("Host"),
+                    #[name = "host_battery_row"]
+                    add = &adw::SwitchRow {
+                        set_title: &gettext("Forward battery level"),
+                        set_subtitle: &gettext("Send an alert to the watch when the host battery changes by 10%. The watch vibrates on each alert"),
+                    },
+                    #[name = "gatt_server_row"]
+                    add = &adw::SwitchRow {
+                        set_title: &gettext("Host services"),
+                        set_subtitle: &gettext("Let the watch read the time from this computer and find it"),
+                    },
+                    #[name = "volume_step_row"]
+                    add = &adw::SpinRow::with_range(0.01, 0.5, 0.01) {
+                        set_title: &gettext("Media volume step"),
+                        set_subtitle: &gettext("Volume change per watch button press, as a fraction"),
+                        set_digits: 2,
+                    },
+                },
+                add = &adw::PreferencesGroup {
+                    set_title: &gettext("Developer"),
+                    #[name = "developer_mode_row"]
+                    add = &adw::SwitchRow {
+                        set_title: &gettext("Developer mode"),
+                        set_subtitle: &gettext("Show the GATT services inspector in the dashboard"),
+                    },
+                    #[name = "verbose_logging_row"]
+                    add = &adw::SwitchRow {
+                        set_title: &gettext("Verbose logging"),
+                        set_subtitle: &gettext("Include debug messages"),
+                    },
+                    #[name = "log_to_file_row"]
+                    add = &adw::SwitchRow {
+                        set_title: &gettext("Write logs to file"),
+                        set_subtitle: &gettext("Attach them to bug reports"),
+                    },
+                    add = &adw::ActionRow {
+                        set_title: &gettext("Open log folder"),
+                        set_subtitle: &logging::log_dir().to_string_lossy(),
+                        set_activatable: true,
+                        add_suffix = &gtk::Image {
+                            set_icon_name: Some("folder-open-symbolic"),
+                        },
+                        connect_activated => Input::OpenLogFolder,
+                    },
                 }
             }
         }
     }
 
-    fn init(settings: Self::Init, root: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
+    fn init((main_window, settings): Self::Init, root: Self::Root, sender: ComponentSender<Self>) -> ComponentParts<Self> {
+        let file_filter = gtk::FileFilter::new();
+        file_filter.set_name(Some(&gettext("Settings files")));
+        file_filter.add_pattern("*.ini");
+
+        let export_dialog = SaveDialog::builder()
+            .transient_for_native(&main_window)
+            .launch(SaveDialogSettings {
+                filters: vec![file_filter.clone()],
+                ..Default::default()
+            })
+            .forward(sender.input_sender(), |message| match message {
+                SaveDialogResponse::Accept(path) => Input::ExportTo(path),
+                SaveDialogResponse::Cancel => Input::None,
+            });
+
+        let import_dialog = OpenDialog::builder()
+            .transient_for_native(&main_window)
+            .launch(OpenDialogSettings {
+                create_folders: false,
+                filters: vec![file_filter],
+                ..Default::default()
+            })
+            .forward(sender.input_sender(), |message| match message {
+                OpenDialogResponse::Accept(path) => Input::ImportFrom(path),
+                OpenDialogResponse::Cancel => Input::None,
+            });
+
         let model = Self {
             background_switch: gtk::Switch::new(),
             autostart_switch: gtk::Switch::new(),
+            firmware_repo_row: adw::EntryRow::new(),
+            notification_format_row: adw::EntryRow::new(),
+            weather_location_row: adw::EntryRow::new(),
+            download_dir_row: adw::EntryRow::new(),
+            adapter_row: adw::ComboRow::new(),
+            adapter_names: Vec::new(),
             settings,
+            export_dialog,
+            import_dialog,
         };
 
         let background_switch = model.background_switch.clone();
         let autostart_switch = model.autostart_switch.clone();
+        let firmware_repo_row = model.firmware_repo_row.clone();
+        let notification_format_row = model.notification_format_row.clone();
+        let weather_location_row = model.weather_location_row.clone();
+        let download_dir_row = model.download_dir_row.clone();
+        let adapter_row = model.adapter_row.clone();
         let widgets = view_output!();
+        model.settings.bind(super::SETTING_STEP_GOAL, &widgets.step_goal_row, "value").build();
+        model.settings.bind(super::SETTING_AUTO_CONNECT, &widgets.auto_connect_row, "active").build();
+        model.settings.bind(super::SETTING_DISCONNECT_ON_QUIT, &widgets.disconnect_on_quit_row, "active").build();
+        model.settings.bind(super::SETTING_AUTO_DISCOVERY, &widgets.auto_discovery_row, "active").build();
+        model.settings.bind(super::SETTING_SCAN_TIMEOUT, &widgets.scan_timeout_row, "value").build();
+        model.settings.bind(super::SETTING_RECONNECT_DELAY, &widgets.reconnect_delay_row, "value").build();
+        model.settings.bind(super::SETTING_RECONNECT_MAX_ATTEMPTS, &widgets.reconnect_attempts_row, "value").build();
+        model.settings.bind(super::SETTING_RSSI_THRESHOLD, &widgets.rssi_threshold_row, "value").build();
+        model.settings.bind(super::SETTING_DUPLICATE_DATA, &widgets.duplicate_data_row, "active").build();
+        model.settings.bind(super::SETTING_HOST_BATTERY, &widgets.host_battery_row, "active").build();
+        model.settings.bind(super::SETTING_GATT_SERVER, &widgets.gatt_server_row, "active").build();
+        model.settings.bind(super::SETTING_MEDIA_VOLUME_STEP, &widgets.volume_step_row, "value").build();
+        model.settings.bind(super::SETTING_VERBOSE_LOGGING, &widgets.verbose_logging_row, "active").build();
+        model.settings.bind(super::SETTING_LOG_TO_FILE, &widgets.log_to_file_row, "active").build();
+        model.settings.bind(super::SETTING_DEVELOPER_MODE, &widgets.developer_mode_row, "active").build();
+        model.settings.bind(super::SETTING_WEATHER_LATITUDE, &widgets.weather_latitude_row, "value").build();
+        model.settings.bind(super::SETTING_WEATHER_LONGITUDE, &widgets.weather_longitude_row, "value").build();
+        model.settings.bind(super::SETTING_DOWNLOAD_AUTO_SAVE, &widgets.download_auto_save_row, "active").build();
+
+        // Not bound, so follow the changes made elsewhere (e.g. on import)
+        let color_scheme_row = widgets.color_scheme_row.clone();
+        model.settings.connect_changed(Some(super::SETTING_COLOR_SCHEME), move |settings, _| {
+            color_scheme_row.set_selected(color_scheme_index(settings));
+        });
+        let weather_provider_row = widgets.weather_provider_row.clone();
+        model.settings.connect_changed(Some(super::SETTING_WEATHER_PROVIDER), move |settings, _| {
+            weather_provider_row.set_selected(weather_provider_index(settings));
+        });
+
+        let mut group = RelmActionGroup::<SettingsActionGroup>::new();
+        group.add_action(RelmAction::<ExportAction>::new_stateless(
+            glib::clone!(#[strong] sender, move |_| {
+                sender.input(Input::Export);
+            }
+        )));
+        group.add_action(RelmAction::<ImportAction>::new_stateless(
+            glib::clone!(#[strong] sender, move |_| {
+                sender.input(Input::Import);
+            }
+        )));
+        group.register_for_widget(&widgets.root);
+
         ComponentParts { model, widgets }
     }
 
@@ -149,7 +478,7 @@ impl Component for Model {
                         Ok(response) => {
                             sender.input(Input::RunInBackgroundResponse(response.run_in_background()));
                             if !response.run_in_background() {
-                                ui::BROKER.send(ui::Input::ToastStatic("Not allowed to run in background"));
+                                ui::BROKER.send(ui::Input::Toast(gettext("Not allowed to run in background")));
                             }
                         }
                         Err(error) => {
@@ -157,7 +486,7 @@ impl Component for Model {
                             // permission if the portal is not implemented
                             sender.input(Input::RunInBackgroundResponse(true));
                             log::error!("Background portal request failed: {error}");
-                            ui::BROKER.send(ui::Input::ToastStatic("Background permission request failed"));
+                            ui::BROKER.send(ui::Input::Toast(gettext("Background permission request failed")));
                         }
                     });
                 } else {
@@ -173,13 +502,13 @@ impl Component for Model {
                         Ok(response) => {
                             sender.input(Input::AutoStartResponse(response.auto_start()));
                             if response.auto_start() != enabled {
-                                ui::BROKER.send(ui::Input::ToastStatic("Not allowed to change autostart setting"));
+                                ui::BROKER.send(ui::Input::Toast(gettext("Not allowed to change autostart setting")));
                             }
                         }
                         Err(error) => {
                             sender.input(Input::AutoStartResponse(old_state));
                             log::error!("Background portal request failed: {error}");
-                            ui::BROKER.send(ui::Input::ToastStatic("Autostart request failed"));
+                            ui::BROKER.send(ui::Input::Toast(gettext("Autostart request failed")));
                         }
                     });
                 }
@@ -194,6 +523,133 @@ impl Component for Model {
                 self.autostart_switch.set_active(enabled);
                 _ = self.settings.set_boolean(super::SETTING_AUTO_START, enabled);
             }
+            Input::FirmwareRepoChanged(repo) => {
+                let repo = repo.trim();
+                if gh::is_valid_repo(repo) {
+                    _ = self.settings.set_string(super::SETTING_FIRMWARE_REPO, repo);
+                } else {
+                    self.firmware_repo_row.set_text(&self.settings.string(super::SETTING_FIRMWARE_REPO));
+                    ui::BROKER.send(ui::Input::Toast(gettext("Repository must be in owner/repo format")));
+                }
+            }
+            Input::NotificationFormatChanged(format) => {
+                if format.trim().is_empty() {
+                    self.settings.reset(super::SETTING_NOTIFICATION_FORMAT);
+                    self.notification_format_row.set_text(&self.settings.string(super::SETTING_NOTIFICATION_FORMAT));
+                } else {
+                    _ = self.settings.set_string(super::SETTING_NOTIFICATION_FORMAT, format.trim());
+                }
+            }
+            Input::WeatherLocationChanged(name) => {
+                _ = self.settings.set_string(super::SETTING_WEATHER_LOCATION, name.trim());
+            }
+            Input::DownloadDirChanged(dir) => {
+                let dir = dir.trim();
+                if dir.is_empty() || Path::new(dir).is_absolute() {
+                    // Empty resets to the default
+                    _ = self.settings.set_string(super::SETTING_DOWNLOAD_DIR, dir);
+                } else {
+                    ui::BROKER.send(ui::Input::Toast(gettext("Download folder must be an absolute path")));
+                }
+                self.download_dir_row.set_text(&super::download_dir(&self.settings).to_string_lossy());
+            }
+            Input::AdapterNames(names) => {
+                // Keep the selected adapter listed even if it's gone
+                let selected = self.settings.string(super::SETTING_ADAPTER).to_string();
+                self.adapter_names = vec![String::new()];
+                self.adapter_names.extend(names);
+                if !self.adapter_names.contains(&selected) {
+                    self.adapter_names.push(selected.clone());
+                }
+                let default_label = gettext("Default");
+                let labels = self.adapter_names.iter()
+                    .map(|name| if name.is_empty() { default_label.as_str() } else { name.as_str() })
+                    .collect::<Vec<_>>();
+                self.adapter_row.set_model(Some(&gtk::StringList::new(&labels)));
+                let index = self.adapter_names.iter().position(|name| *name == selected).unwrap_or(0);
+                self.adapter_row.set_selected(index as u32);
+            }
+            Input::AdapterSelected => {
+                // Read the selection when the message is handled, since
+                // updating the options emits intermediate selections
+                let index = self.adapter_row.selected() as usize;
+                if let Some(name) = self.adapter_names.get(index) {
+                    if *name != self.settings.string(super::SETTING_ADAPTER).as_str() {
+                        _ = self.settings.set_string(super::SETTING_ADAPTER, name);
+                    }
+                }
+            }
+            Input::Export => {
+                self.export_dialog.emit(SaveDialogMsg::SaveAs(String::from("watchmate-settings.ini")));
+            }
+            Input::OpenLogFolder => {
+                let dir = logging::log_dir();
+                if let Err(error) = std::fs::create_dir_all(&dir) {
+                    log::error!("Failed to create log folder: {error}");
+                    return;
+                }
+                let window = relm4::main_application().active_window();
+                gtk::FileLauncher::new(Some(&gio::File::for_path(dir)))
+                    .launch(window.as_ref(), gio::Cancellable::NONE, |result| {
+                        if let Err(error) = result {
+                            log::error!("Failed to open log folder: {error}");
+                        }
+                    });
+            }
+            Input::ExportTo(path) => {
+                let result = backup::export(&self.settings)
+                    .and_then(|data| Ok(std::fs::write(&path, data)?));
+                match result {
+                    Ok(()) => {
+                        log::info!("Settings exported to {}", path.display());
+                        ui::BROKER.send(ui::Input::Toast(gettext("Settings exported")));
+                    }
+                    Err(error) => {
+                        log::error!("Failed to export settings: {error}");
+                        ui::BROKER.send(ui::Input::Toast(gettext("Failed to export settings")));
+                    }
+                }
+            }
+            Input::Import => {
+                self.import_dialog.emit(OpenDialogMsg::Open);
+            }
+            Input::ImportFrom(path) => {
+                let result = std::fs::read_to_string(&path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|data| backup::import(&self.settings, &data));
+                match result {
+                    Ok(count) => {
+                        log::info!("{count} settings imported from {}", path.display());
+                        // Entries are not bound, since they are validated on apply
+                        self.firmware_repo_row.set_text(&self.settings.string(super::SETTING_FIRMWARE_REPO));
+                        self.notification_format_row.set_text(&self.settings.string(super::SETTING_NOTIFICATION_FORMAT));
+                        self.weather_location_row.set_text(&self.settings.string(super::SETTING_WEATHER_LOCATION));
+                        self.download_dir_row.set_text(&super::download_dir(&self.settings).to_string_lossy());
+                        ui::BROKER.send(ui::Input::Toast(gettext_f(
+                            "Imported {count} settings", &[("count", &count.to_string())]
+                        )));
+                    }
+                    Err(error) => {
+                        log::error!("Failed to import settings: {error}");
+                        ui::BROKER.send(ui::Input::Toast(gettext("Failed to import settings")));
+                    }
+                }
+            }
+            Input::None => {}
         };
     }
 }
+
+fn color_scheme_index(settings: &gio::Settings) -> u32 {
+    let scheme = settings.string(super::SETTING_COLOR_SCHEME);
+    COLOR_SCHEMES.iter().position(|s| *s == scheme.as_str()).unwrap_or(0) as u32
+}
+
+fn weather_provider_index(settings: &gio::Settings) -> u32 {
+    let provider = settings.string(super::SETTING_WEATHER_PROVIDER);
+    WEATHER_PROVIDERS.iter().position(|p| *p == provider.as_str()).unwrap_or(0) as u32
+}
+
+relm4::new_action_group!(SettingsActionGroup, "settings");
+relm4::new_stateless_action!(ExportAction, SettingsActionGroup, "export");
+relm4::new_stateless_action!(ImportAction, SettingsActionGroup, "import");
@@ -0,0 +1,69 @@
+use crate::ui;
+use anyhow::{bail, Context, Result};
+use gtk::{gio, glib, prelude::{SettingsExt, SettingsExtManual}};
+
+
+/// Key file group holding the settings
+const GROUP: &str = "Settings";
+
+/// Window state isn't configuration, and background permissions are granted
+/// via the portal, so neither is carried over
+const EXCLUDED_KEYS: &[&str] = &[
+    ui::SETTING_WINDOW_WIDTH,
+    ui::SETTING_WINDOW_HEIGHT,
+    ui::SETTING_LAST_VIEW,
+    ui::SETTING_BACKGROUND,
+    ui::SETTING_AUTO_START,
+];
+
+fn schema(settings: &gio::Settings) -> Result<gio::SettingsSchema> {
+    settings.settings_schema().context("Settings schema is not found")
+}
+
+/// Serialize settings into a key file, with values in GVariant text format
+pub fn export(settings: &gio::Settings) -> Result<String> {
+    let keyfile = glib::KeyFile::new();
+    for key in schema(settings)?.list_keys() {
+        if !EXCLUDED_KEYS.contains(&key.as_str()) {
+            keyfile.set_value(GROUP, &key, &settings.value(&key).print(true));
+        }
+    }
+    Ok(keyfile.to_data().to_string())
+}
+
+/// Apply settings from a key file created by [`export`], returning the number
+/// of imported keys. Nothing is applied unless all known keys are valid,
+/// unknown and excluded ones are skipped
+pub fn import(settings: &gio::Settings, data: &str) -> Result<usize> {
+    let keyfile = glib::KeyFile::new();
+    keyfile.load_from_data(data, glib::KeyFileFlags::NONE)?;
+    let schema = schema(settings)?;
+    let mut values = Vec::new();
+    for key in keyfile.keys(GROUP)?.iter() {
+        let key = key.as_str();
+        if !schema.has_key(key) || EXCLUDED_KEYS.contains(&key) {
+            log::warn!("Skipping setting on import: {key}");
+            continue;
+        }
+        let schema_key = schema.key(key);
+        let text = keyfile.value(GROUP, key)?;
+        let value = glib::Variant::parse(Some(&schema_key.value_type()), &text)
+            .with_context(|| format!("Invalid value of {key}: {text}"))?;
+        if !schema_key.range_check(&value) {
+            bail!("Value of {key} is out of range: {text}");
+        }
+        values.push((key.to_string(), value));
+    }
+    // Apply all at once. Delay-apply mode can't be left, so it's enabled on
+    // a separate instance rather than on the one shared across the app
+    let batch = gio::Settings::new(ui::APP_ID);
+    batch.delay();
+    for (key, value) in &values {
+        if let Err(error) = batch.set_value(key, value) {
+            batch.revert();
+            bail!("Failed to set {key}: {error}");
+        }
+    }
+    batch.apply();
+    Ok(values.len())
+}
@@ -0,0 +1,48 @@
+use crate::i18n::gettext;
+use relm4::gtk::{self, glib, prelude::{GtkWindowExt, IsA}};
+
+
+/// Build keyboard shortcuts help window. It has to be defined in UI
+/// description, so titles are translated here and inserted into it
+pub fn window(transient_for: &impl IsA<gtk::Window>) -> gtk::ShortcutsWindow {
+    let groups = [
+        (gettext("General"), vec![
+            (gettext("Keyboard shortcuts"), "<primary>question"),
+            (gettext("Close window"), "<primary>W"),
+            (gettext("Quit"), "<primary>Q"),
+        ]),
+        (gettext("Navigation"), vec![
+            (gettext("Dashboard"), "<primary>H"),
+            (gettext("Devices"), "<primary>D"),
+            (gettext("Settings"), "<primary>comma"),
+        ]),
+        (gettext("Devices"), vec![
+            (gettext("Scan for devices"), "<primary>R"),
+        ]),
+    ];
+
+    let mut groups_ui = String::new();
+    for (title, shortcuts) in groups {
+        groups_ui += &format!(
+            r#"<child><object class="GtkShortcutsGroup"><property name="title">{}</property>"#,
+            glib::markup_escape_text(&title)
+        );
+        for (title, accelerator) in shortcuts {
+            groups_ui += &format!(
+                r#"<child><object class="GtkShortcutsShortcut"><property name="title">{}</property><property name="accelerator">{}</property></object></child>"#,
+                glib::markup_escape_text(&title),
+                glib::markup_escape_text(accelerator)
+            );
+        }
+        groups_ui += "</object></child>";
+    }
+    let ui = format!(
+        r#"<interface><object class="GtkShortcutsWindow" id="window"><property name="modal">1</property><child><object class="GtkShortcutsSection"><property name="section-name">shortcuts</property>{groups_ui}</object></child></object></interface>"#
+    );
+
+    let window = gtk::Builder::from_string(&ui)
+        .object::<gtk::ShortcutsWindow>("window")
+        .expect("Shortcuts window is defined in UI description");
+    window.set_transient_for(Some(transient_for));
+    window
+}